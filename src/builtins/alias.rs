@@ -0,0 +1,139 @@
+use super::{BuiltinCommand, BuiltinCommandContext};
+use crate::process::ExitStatus;
+use crate::quote::shell_quote;
+
+pub struct Alias;
+
+impl BuiltinCommand for Alias {
+    fn name(&self) -> &'static str {
+        "alias"
+    }
+
+    fn usage(&self) -> &'static str {
+        "alias [-p] [name[=value] ...]"
+    }
+
+    fn run(&self, ctx: &mut BuiltinCommandContext) -> anyhow::Result<ExitStatus> {
+        let argv = ctx.argv;
+        let args = match argv.get(1).map(String::as_str) {
+            Some("-p") => &argv[2..],
+            Some(arg) if arg.starts_with('-') && arg != "-" => {
+                writeln!(ctx.stderr(), "smash: alias: {}: invalid option", arg).ok();
+                writeln!(ctx.stderr(), "alias: usage: {}", self.usage()).ok();
+                return Ok(ExitStatus::ExitedWith(2));
+            }
+            _ => &argv[1..],
+        };
+
+        if args.is_empty() || argv.get(1).map(String::as_str) == Some("-p") {
+            let lines: Vec<String> = ctx
+                .shell
+                .aliases()
+                .into_iter()
+                .map(|(name, value)| format_alias(name, value))
+                .collect();
+            for line in lines {
+                writeln!(ctx.stdout(), "{}", line).ok();
+            }
+        }
+
+        let mut status = 0;
+        for arg in args {
+            match arg.split_once('=') {
+                Some((name, value)) if is_valid_alias_name(name) => {
+                    ctx.shell.set_alias(name, value);
+                }
+                Some((name, _)) => {
+                    writeln!(ctx.stderr(), "smash: alias: `{}': invalid alias name", name).ok();
+                    status = 1;
+                }
+                None => match ctx.shell.alias(arg) {
+                    Some(value) => {
+                        let line = format_alias(arg, value);
+                        writeln!(ctx.stdout(), "{}", line).ok();
+                    }
+                    None => {
+                        writeln!(ctx.stderr(), "smash: alias: {}: not found", arg).ok();
+                        status = 1;
+                    }
+                },
+            }
+        }
+
+        Ok(ExitStatus::ExitedWith(status))
+    }
+}
+
+pub struct Unalias;
+
+impl BuiltinCommand for Unalias {
+    fn name(&self) -> &'static str {
+        "unalias"
+    }
+
+    fn usage(&self) -> &'static str {
+        "unalias [-a] name [name ...]"
+    }
+
+    fn run(&self, ctx: &mut BuiltinCommandContext) -> anyhow::Result<ExitStatus> {
+        let argv = ctx.argv;
+        if argv.get(1).map(String::as_str) == Some("-a") {
+            ctx.shell.remove_all_aliases();
+            return Ok(ExitStatus::ExitedWith(0));
+        }
+
+        if argv.len() < 2 {
+            writeln!(ctx.stderr(), "unalias: usage: {}", self.usage()).ok();
+            return Ok(ExitStatus::ExitedWith(2));
+        }
+
+        let mut status = 0;
+        for name in &argv[1..] {
+            if !ctx.shell.remove_alias(name) {
+                writeln!(ctx.stderr(), "smash: unalias: {}: not found", name).ok();
+                status = 1;
+            }
+        }
+
+        Ok(ExitStatus::ExitedWith(status))
+    }
+}
+
+/// Formats an alias to be reused as input.
+fn format_alias(name: &str, value: &str) -> String {
+    format!("alias {}={}", name, shell_quote(value))
+}
+
+fn is_valid_alias_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name
+            .chars()
+            .any(|ch| ch.is_whitespace() || "/$`=|&;()<>'\"\\".contains(ch))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::process::ExitStatus;
+    use crate::shell::{SharedBuffer, Shell};
+
+    #[test]
+    fn define_and_list_aliases() {
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        shell.run_script("alias ll='ls -l' la=ls; alias; alias ll");
+        assert_eq!(
+            stdout.contents(),
+            "alias la=ls\nalias ll='ls -l'\nalias ll='ls -l'\n"
+        );
+
+        stdout.clear();
+        assert_eq!(
+            shell.run_script("unalias la nosuch"),
+            ExitStatus::ExitedWith(1)
+        );
+        assert_eq!(shell.run_script("alias la"), ExitStatus::ExitedWith(1));
+        assert_eq!(shell.run_script("alias a/b=c"), ExitStatus::ExitedWith(1));
+        shell.run_script("unalias -a; alias");
+        assert_eq!(stdout.contents(), "");
+    }
+}
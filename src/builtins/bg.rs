@@ -0,0 +1,78 @@
+use super::{BuiltinCommand, BuiltinCommandContext};
+use crate::process::{continue_job, resolve_job_spec, ExitStatus};
+
+pub struct Bg;
+
+impl BuiltinCommand for Bg {
+    fn name(&self) -> &'static str {
+        "bg"
+    }
+
+    fn usage(&self) -> &'static str {
+        "bg [job_spec ...]"
+    }
+
+    fn run(&self, ctx: &mut BuiltinCommandContext) -> anyhow::Result<ExitStatus> {
+        let mut specs: Vec<&str> = ctx.argv.iter().skip(1).map(String::as_str).collect();
+        if specs.is_empty() {
+            specs.push("%%");
+        }
+
+        let mut status = 0;
+        for spec in specs {
+            match resolve_job_spec(ctx.shell, spec) {
+                Some(job) => {
+                    writeln!(ctx.stdout(), "[{}] {} &", job.id(), job.cmd).ok();
+                    continue_job(ctx.shell, &job, true);
+                }
+                None => {
+                    smash_err!("bg: {}: no such job", spec);
+                    status = 1;
+                }
+            }
+        }
+
+        Ok(ExitStatus::ExitedWith(status))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::process::{wait_for_job, ProcessState};
+    use crate::shell::{Shell, FD_LOCK};
+
+    use nix::sys::signal::{kill, Signal};
+    use nix::sys::wait::{waitpid, WaitStatus};
+
+    #[test]
+    fn stopped_job_stays_usable() {
+        let _lock = FD_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let mut shell = Shell::new();
+        shell.run_script("/bin/sleep 5 &");
+        let job = shell.current_job().unwrap();
+
+        kill(job.pgid, Signal::SIGSTOP).unwrap();
+        assert!(matches!(
+            wait_for_job(&mut shell, &job),
+            ProcessState::Stopped(_)
+        ));
+        assert!(job.stopped(&shell));
+        assert!(shell.jobs().contains_key(&job.id()));
+        assert_eq!(shell.current_job().unwrap().id(), job.id());
+
+        shell.run_script("bg");
+        assert!(!job.stopped(&shell));
+        assert!(shell.jobs().contains_key(&job.id()));
+        #[cfg(target_os = "linux")]
+        {
+            let status = std::fs::read_to_string(format!("/proc/{}/status", job.pgid)).unwrap();
+            assert!(!status.contains("State:\tT"));
+        }
+
+        kill(job.pgid, Signal::SIGKILL).unwrap();
+        assert_eq!(
+            waitpid(job.pgid, None),
+            Ok(WaitStatus::Signaled(job.pgid, Signal::SIGKILL, false))
+        );
+    }
+}
@@ -1,13 +1,23 @@
 use super::{BuiltinCommand, BuiltinCommandContext};
+use crate::path::edit_distance;
 use crate::process::ExitStatus;
 
-use std::path::Path;
+use nix::unistd::chdir;
+use std::path::{Component, Path, PathBuf};
 use tracing::debug;
 
 pub struct Cd;
 
 impl BuiltinCommand for Cd {
-    fn run(&self, ctx: &mut BuiltinCommandContext) -> ExitStatus {
+    fn name(&self) -> &'static str {
+        "cd"
+    }
+
+    fn usage(&self) -> &'static str {
+        "cd [dir | -]"
+    }
+
+    fn run(&self, ctx: &mut BuiltinCommandContext) -> anyhow::Result<ExitStatus> {
         debug!("cd: argv={:?}", ctx.argv);
         let current_dir = std::env::current_dir().expect("failed to getcwd()");
         let (dir, pushd) = match ctx.argv.get(1).map(|s| s.as_str()) {
@@ -15,7 +25,7 @@ impl BuiltinCommand for Cd {
                 if let Some(d) = ctx.shell.popd() {
                     (d, false)
                 } else {
-                    return ExitStatus::ExitedWith(1);
+                    return Ok(ExitStatus::ExitedWith(1));
                 }
             }
             Some(dir) if dir.starts_with('/') => (dir.to_string(), true),
@@ -38,16 +48,157 @@ impl BuiltinCommand for Cd {
             ),
         };
 
+        // `cdspell`: fix a typo in each component of a missing directory.
+        let mut dir = dir;
+        if ctx.shell.is_shopt_set("cdspell") && !Path::new(&dir).exists() {
+            if let Some(corrected) = correct_spelling(Path::new(&dir)) {
+                dir = corrected.to_string_lossy().into_owned();
+                writeln!(ctx.stdout(), "{}", dir).ok();
+            }
+        }
+
+        // Report the directory as given by the user.
+        let name = ctx.argv.get(1).unwrap_or(&dir);
+        chdir(dir.as_str()).map_err(|err| anyhow::anyhow!("{}: {}", name, err.desc()))?;
         if pushd {
             ctx.shell.pushd(current_dir.to_str().unwrap().to_owned());
         }
 
-        match std::env::set_current_dir(&dir) {
-            Ok(_) => ExitStatus::ExitedWith(0),
-            Err(err) => {
-                smash_err!("cd: {}", err);
-                ExitStatus::ExitedWith(1)
+        ctx.shell.sync_pwd();
+
+        Ok(ExitStatus::ExitedWith(0))
+    }
+}
+
+/// Replaces each missing component of `path` with a directory whose name is
+/// one edit (a transposition, or a missing, extra, or wrong character) away.
+/// Returns `None` if a component can't be corrected.
+fn correct_spelling(path: &Path) -> Option<PathBuf> {
+    let mut corrected = PathBuf::new();
+    for component in path.components() {
+        let name = match component {
+            Component::Normal(name) => name.to_string_lossy(),
+            _ => {
+                corrected.push(component);
+                continue;
             }
+        };
+
+        if corrected.join(name.as_ref()).exists() {
+            corrected.push(name.as_ref());
+            continue;
         }
+
+        let candidate = std::fs::read_dir(&corrected)
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_dir())
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .filter(|candidate| edit_distance(&name, candidate) == 1)
+            .min()?;
+        corrected.push(candidate);
+    }
+
+    Some(corrected)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::process::ExitStatus;
+    use crate::shell::{SharedBuffer, Shell, CWD_LOCK};
+
+    use nix::unistd::geteuid;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn cd_fails(target: &str, message: &str) {
+        let stderr = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), std::io::sink(), stderr.clone());
+        let current_dir = std::env::current_dir().unwrap();
+        assert_eq!(
+            shell.run_script(&format!("cd {}", target)),
+            ExitStatus::ExitedWith(1)
+        );
+        assert_eq!(
+            stderr.contents(),
+            format!("smash: cd: {}: {}\n", target, message)
+        );
+        assert_eq!(std::env::current_dir().unwrap(), current_dir);
+        assert!(shell.dir_stack().is_empty());
+    }
+
+    #[test]
+    fn errors() {
+        let _lock = CWD_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let base = std::env::temp_dir().join(format!("smash-cd-{}", std::process::id()));
+        let file = base.join("file");
+        let locked = base.join("locked");
+        std::fs::create_dir_all(&locked).unwrap();
+        std::fs::write(&file, "").unwrap();
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o000)).unwrap();
+
+        cd_fails(file.to_str().unwrap(), "Not a directory");
+        cd_fails(
+            base.join("missing").to_str().unwrap(),
+            "No such file or directory",
+        );
+        // The superuser can enter any directory.
+        if !geteuid().is_root() {
+            cd_fails(locked.to_str().unwrap(), "Permission denied");
+        }
+
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o755)).unwrap();
+        std::fs::remove_dir_all(base).ok();
+    }
+
+    #[test]
+    fn update_pwd() {
+        let _lock = CWD_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let saved_cwd = std::env::current_dir().unwrap();
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        shell.run_script("cd /tmp; cd /; cd usr; echo $PWD; pwd");
+        assert_eq!(stdout.contents(), "/usr\n/usr\n");
+
+        stdout.clear();
+        shell.run_script("echo $(cd /tmp; echo $PWD) $PWD");
+        assert_eq!(stdout.contents(), "/tmp /usr\n");
+
+        // Changed behind the shell.
+        stdout.clear();
+        std::env::set_current_dir("/").unwrap();
+        shell.run_script("echo $PWD");
+        assert_eq!(stdout.contents(), "/\n");
+
+        std::env::set_current_dir(saved_cwd).unwrap();
+    }
+
+    #[test]
+    fn cdspell() {
+        let _lock = CWD_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let saved_cwd = std::env::current_dir().unwrap();
+        let base = std::env::temp_dir().join(format!("smash-cdspell-{}", std::process::id()));
+        std::fs::create_dir_all(base.join("dir/sub")).unwrap();
+        std::fs::write(base.join("dirx"), "").unwrap();
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        shell.run_script(&format!("cd {}", base.display()));
+
+        assert_eq!(shell.run_script("cd dirr"), ExitStatus::ExitedWith(1));
+        shell.run_script("shopt -s cdspell");
+        assert_eq!(shell.run_script("cd dirr"), ExitStatus::ExitedWith(0));
+        assert_eq!(std::env::current_dir().unwrap(), base.join("dir"));
+        assert_eq!(
+            stdout.contents(),
+            format!("{}\n", base.join("dir").display())
+        );
+
+        // A transposition in each component.
+        shell.run_script(&format!("cd {}/idr/usb", base.display()));
+        assert_eq!(std::env::current_dir().unwrap(), base.join("dir/sub"));
+        // Too different.
+        assert_eq!(shell.run_script("cd ../../xyz"), ExitStatus::ExitedWith(1));
+
+        std::env::set_current_dir(saved_cwd).unwrap();
+        std::fs::remove_dir_all(base).ok();
     }
 }
@@ -0,0 +1,97 @@
+use super::{builtin_command, BuiltinCommand, BuiltinCommandContext};
+use crate::process::{run_external_command, ExitStatus};
+
+use nix::unistd::{access, AccessFlags};
+
+pub struct Command;
+
+impl BuiltinCommand for Command {
+    fn name(&self) -> &'static str {
+        "command"
+    }
+
+    fn usage(&self) -> &'static str {
+        "command [-v] name [arg ...]"
+    }
+
+    fn run(&self, ctx: &mut BuiltinCommandContext) -> anyhow::Result<ExitStatus> {
+        let argv = ctx.argv;
+        match argv.get(1).map(String::as_str) {
+            None => Ok(ExitStatus::ExitedWith(0)),
+            Some("-v") => {
+                // Print how each name is resolved. Unresolved names are
+                // silently ignored except for the exit status.
+                let mut status = 0;
+                for name in &argv[2..] {
+                    match resolve(ctx, name) {
+                        Some(resolved) => {
+                            writeln!(ctx.stdout(), "{}", resolved).ok();
+                        }
+                        None => status = 1,
+                    }
+                }
+
+                Ok(ExitStatus::ExitedWith(status))
+            }
+            Some(name) => match builtin_command(name) {
+                Some(command) => {
+                    ctx.argv = &argv[1..];
+                    let result = command.run(ctx);
+                    ctx.argv = argv;
+                    result
+                }
+                None => {
+                    run_external_command(ctx.process_ctx, ctx.shell, argv[1..].to_vec(), &[], &[])
+                }
+            },
+        }
+    }
+}
+
+/// Returns the name of a builtin or a function, or the path to an executable.
+fn resolve(ctx: &BuiltinCommandContext, name: &str) -> Option<String> {
+    if builtin_command(name).is_some() || ctx.shell.get_function(name).is_some() {
+        return Some(name.to_owned());
+    }
+
+    if name.contains('/') {
+        return access(name, AccessFlags::X_OK)
+            .ok()
+            .map(|_| name.to_owned());
+    }
+
+    ctx.shell.path_table().lookup(name).map(str::to_owned)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::process::ExitStatus;
+    use crate::shell::{SharedBuffer, Shell, FD_LOCK};
+
+    #[test]
+    fn resolve_commands() {
+        let _lock = FD_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        shell.run_script("command -v echo /bin/sh");
+        assert_eq!(stdout.contents(), "echo\n/bin/sh\n");
+
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        shell.run_script("if command -v echo > /dev/null; then echo found; fi");
+        shell.run_script("if command -v no-such-command; then echo found; else echo missing; fi");
+        assert_eq!(stdout.contents(), "found\nmissing\n");
+        assert_eq!(
+            shell.run_script("command -v echo no-such-command"),
+            ExitStatus::ExitedWith(1)
+        );
+    }
+
+    #[test]
+    fn run_builtin() {
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        shell.run_script("command echo hello");
+        assert_eq!(stdout.contents(), "hello\n");
+    }
+}
@@ -0,0 +1,92 @@
+use super::{BuiltinCommand, BuiltinCommandContext};
+use crate::process::ExitStatus;
+
+pub struct Complete;
+
+impl BuiltinCommand for Complete {
+    fn name(&self) -> &'static str {
+        "complete"
+    }
+
+    fn usage(&self) -> &'static str {
+        "complete [-F function | -r] [name ...]"
+    }
+
+    fn run(&self, ctx: &mut BuiltinCommandContext) -> anyhow::Result<ExitStatus> {
+        let argv = ctx.argv;
+        match argv.get(1).map(String::as_str) {
+            None | Some("-p") => {
+                let mut completions: Vec<(String, String)> = ctx
+                    .shell
+                    .completions()
+                    .iter()
+                    .map(|(cmd, function)| (cmd.clone(), function.clone()))
+                    .collect();
+                completions.sort();
+                for (cmd, function) in completions {
+                    writeln!(ctx.stdout(), "complete -F {} {}", function, cmd).ok();
+                }
+
+                Ok(ExitStatus::ExitedWith(0))
+            }
+            Some("-F") => {
+                let (function, names) = match argv.get(2) {
+                    Some(function) if argv.len() > 3 => (function, &argv[3..]),
+                    _ => {
+                        writeln!(ctx.stderr(), "smash: complete: usage: {}", self.usage()).ok();
+                        return Ok(ExitStatus::ExitedWith(2));
+                    }
+                };
+
+                for name in names {
+                    ctx.shell.set_completion(name, function);
+                }
+
+                Ok(ExitStatus::ExitedWith(0))
+            }
+            Some("-r") => {
+                let mut status = 0;
+                for name in &argv[2..] {
+                    if !ctx.shell.remove_completion(name) {
+                        writeln!(
+                            ctx.stderr(),
+                            "smash: complete: {}: no completion specification",
+                            name
+                        )
+                        .ok();
+                        status = 1;
+                    }
+                }
+
+                Ok(ExitStatus::ExitedWith(status))
+            }
+            Some(arg) => {
+                writeln!(ctx.stderr(), "smash: complete: {}: invalid option", arg).ok();
+                Ok(ExitStatus::ExitedWith(2))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::process::ExitStatus;
+    use crate::shell::{SharedBuffer, Shell};
+
+    #[test]
+    fn register_completions() {
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        shell.run_script("complete -F _git git g");
+        assert_eq!(shell.completion("g"), Some("_git"));
+        shell.run_script("complete");
+        assert_eq!(
+            stdout.contents(),
+            "complete -F _git g\ncomplete -F _git git\n"
+        );
+
+        assert_eq!(shell.run_script("complete -r g"), ExitStatus::ExitedWith(0));
+        assert_eq!(shell.completion("g"), None);
+        assert_eq!(shell.run_script("complete -r g"), ExitStatus::ExitedWith(1));
+    }
+}
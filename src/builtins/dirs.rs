@@ -0,0 +1,27 @@
+use super::{BuiltinCommand, BuiltinCommandContext};
+use crate::path::abbreviate_home;
+use crate::process::ExitStatus;
+
+pub struct Dirs;
+
+impl BuiltinCommand for Dirs {
+    fn name(&self) -> &'static str {
+        "dirs"
+    }
+
+    fn usage(&self) -> &'static str {
+        "dirs"
+    }
+
+    fn run(&self, ctx: &mut BuiltinCommandContext) -> anyhow::Result<ExitStatus> {
+        // The current directory followed by the most recently pushed ones.
+        let current_dir = std::env::current_dir()?;
+        let mut dirs = vec![abbreviate_home(&current_dir.to_string_lossy())];
+        for dir in ctx.shell.dir_stack().iter().rev() {
+            dirs.push(abbreviate_home(dir));
+        }
+
+        writeln!(ctx.stdout(), "{}", dirs.join(" ")).ok();
+        Ok(ExitStatus::ExitedWith(0))
+    }
+}
@@ -0,0 +1,151 @@
+use super::{BuiltinCommand, BuiltinCommandContext};
+use crate::process::{destroy_job, resolve_job_spec, ExitStatus, JobState};
+
+pub struct Disown;
+
+impl BuiltinCommand for Disown {
+    fn name(&self) -> &'static str {
+        "disown"
+    }
+
+    fn usage(&self) -> &'static str {
+        "disown [-h] [-ar] [job_spec ...]"
+    }
+
+    fn run(&self, ctx: &mut BuiltinCommandContext) -> anyhow::Result<ExitStatus> {
+        let argv = ctx.argv;
+        let mut keep_in_table = false;
+        let mut all = false;
+        let mut running_only = false;
+        let mut args = argv.iter().skip(1).peekable();
+        while let Some(arg) = args.next_if(|arg| arg.starts_with('-') && arg.len() > 1) {
+            for flag in arg.chars().skip(1) {
+                match flag {
+                    'h' => keep_in_table = true,
+                    'a' => all = true,
+                    'r' => running_only = true,
+                    _ => {
+                        smash_err!("disown: -{}: invalid option", flag);
+                        return Ok(ExitStatus::ExitedWith(2));
+                    }
+                }
+            }
+        }
+
+        let mut jobs = Vec::new();
+        let mut status = 0;
+        let specs: Vec<&str> = args.map(String::as_str).collect();
+        if specs.is_empty() && (all || running_only) {
+            jobs.extend(ctx.shell.jobs().values().cloned());
+            jobs.sort_by_key(|job| job.id());
+        } else if specs.is_empty() {
+            jobs.extend(ctx.shell.current_job());
+        }
+
+        for spec in specs {
+            match resolve_job_spec(ctx.shell, spec) {
+                Some(job) => jobs.push(job),
+                None => {
+                    smash_err!("disown: {}: no such job", spec);
+                    status = 1;
+                }
+            }
+        }
+
+        for job in jobs {
+            if running_only && job.state(ctx.shell) != JobState::Running {
+                continue;
+            }
+
+            if keep_in_table {
+                job.nohup.set(true);
+            } else {
+                destroy_job(ctx.shell, &job);
+            }
+        }
+
+        Ok(ExitStatus::ExitedWith(status))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::process::{wait_for_any_process, JobState};
+    use crate::shell::{Shell, FD_LOCK};
+
+    use nix::sys::signal::{kill, Signal};
+    use nix::sys::wait::{waitpid, WaitStatus};
+
+    #[test]
+    fn disowned_job_is_untracked() {
+        let _lock = FD_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let mut shell = Shell::new();
+        shell.run_script("/bin/sleep 5 &");
+        let job = shell.current_job().unwrap();
+
+        shell.run_script("disown -h %1");
+        assert!(job.nohup.get());
+        assert_eq!(shell.jobs().len(), 1);
+
+        shell.run_script("disown %1");
+        assert!(shell.jobs().is_empty());
+        assert!(shell.current_job().is_none());
+
+        kill(job.pgid, Signal::SIGKILL).unwrap();
+        assert_eq!(
+            waitpid(job.pgid, None),
+            Ok(WaitStatus::Signaled(job.pgid, Signal::SIGKILL, false))
+        );
+    }
+
+    #[test]
+    fn disown_all_jobs() {
+        let _lock = FD_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let mut shell = Shell::new();
+        shell.run_script("/bin/sleep 5 &");
+        let first = shell.current_job().unwrap();
+        shell.run_script("/bin/sleep 5 &");
+        let second = shell.current_job().unwrap();
+
+        shell.run_script("disown -a");
+        assert!(shell.jobs().is_empty());
+
+        for job in [first, second] {
+            kill(job.pgid, Signal::SIGKILL).unwrap();
+            assert_eq!(
+                waitpid(job.pgid, None),
+                Ok(WaitStatus::Signaled(job.pgid, Signal::SIGKILL, false))
+            );
+        }
+    }
+
+    #[test]
+    fn disown_running_jobs() {
+        let _lock = FD_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let mut shell = Shell::new();
+        shell.run_script("/bin/sleep 5 &");
+        let running = shell.current_job().unwrap();
+        shell.run_script("/bin/sh -c 'kill -STOP $$' &");
+        let stopped = shell.current_job().unwrap();
+        for _ in 0..100 {
+            if stopped.state(&shell) == JobState::Stopped {
+                break;
+            }
+
+            wait_for_any_process(&mut shell, true);
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        shell.run_script("disown -r");
+        assert_eq!(shell.jobs().len(), 1);
+        assert!(shell.jobs().contains_key(&stopped.id()));
+
+        for job in [running, stopped] {
+            kill(job.pgid, Signal::SIGKILL).unwrap();
+            assert_eq!(
+                waitpid(job.pgid, None),
+                Ok(WaitStatus::Signaled(job.pgid, Signal::SIGKILL, false))
+            );
+        }
+    }
+}
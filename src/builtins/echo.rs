@@ -0,0 +1,27 @@
+use super::{BuiltinCommand, BuiltinCommandContext};
+use crate::process::ExitStatus;
+
+pub struct Echo;
+
+impl BuiltinCommand for Echo {
+    fn name(&self) -> &'static str {
+        "echo"
+    }
+
+    fn usage(&self) -> &'static str {
+        "echo [-n] [arg ...]"
+    }
+
+    fn run(&self, ctx: &mut BuiltinCommandContext) -> anyhow::Result<ExitStatus> {
+        let mut args = ctx.argv.iter().skip(1).peekable();
+        let newline = args.next_if(|arg| *arg == "-n").is_none();
+
+        let mut output = args.map(String::as_str).collect::<Vec<_>>().join(" ");
+        if newline {
+            output.push('\n');
+        }
+
+        ctx.stdout().write_all(output.as_bytes())?;
+        Ok(ExitStatus::ExitedWith(0))
+    }
+}
@@ -4,13 +4,21 @@ use crate::process::ExitStatus;
 pub struct Eval;
 
 impl BuiltinCommand for Eval {
-    fn run(&self, ctx: &mut BuiltinCommandContext) -> ExitStatus {
+    fn name(&self) -> &'static str {
+        "eval"
+    }
+
+    fn usage(&self) -> &'static str {
+        "eval [arg ...]"
+    }
+
+    fn run(&self, ctx: &mut BuiltinCommandContext) -> anyhow::Result<ExitStatus> {
         let mut program = String::new();
         for arg in ctx.argv.iter().skip(1) {
             program += arg;
             program.push(' ');
         }
 
-        ctx.shell.run_script(&program)
+        Ok(ctx.shell.run_script(&program))
     }
 }
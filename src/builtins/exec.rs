@@ -0,0 +1,86 @@
+use super::{BuiltinCommand, BuiltinCommandContext};
+use crate::process::{environ, ExitStatus};
+
+use nix::unistd::execve;
+use std::ffi::CString;
+
+pub struct Exec;
+
+impl BuiltinCommand for Exec {
+    fn name(&self) -> &'static str {
+        "exec"
+    }
+
+    fn usage(&self) -> &'static str {
+        "exec [command [arg ...]] [redirection ...]"
+    }
+
+    /// Replaces the shell with `command`. Without a command, redirections
+    /// are applied to the shell itself (see `run_simple_command`).
+    fn run(&self, ctx: &mut BuiltinCommandContext) -> anyhow::Result<ExitStatus> {
+        let argv = ctx.argv;
+        let name = match argv.get(1) {
+            Some(name) => name,
+            None => return Ok(ExitStatus::ExitedWith(0)),
+        };
+
+        let path = if name.contains('/') {
+            name.clone()
+        } else {
+            match ctx.shell.path_table().lookup(name) {
+                Some(path) => path.to_owned(),
+                None => {
+                    writeln!(ctx.stderr(), "smash: exec: {}: not found", name).ok();
+                    return Ok(ExitStatus::ExitedWith(127));
+                }
+            }
+        };
+
+        let path = CString::new(path)?;
+        let args = argv[1..]
+            .iter()
+            .map(|arg| CString::new(arg.as_str()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let envs = environ(ctx.shell, &[])?;
+
+        ctx.stdout().flush().ok();
+        let err = execve(&path, &args, &envs).unwrap_err();
+        writeln!(ctx.stderr(), "smash: exec: {}: {}", name, err.desc()).ok();
+        Ok(ExitStatus::ExitedWith(126))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::process::ExitStatus;
+    use crate::shell::{Shell, FD_LOCK};
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("smash-exec-{}-{}", std::process::id(), name))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    // The descriptor is high to stay clear of ones opened by other tests
+    // running in parallel. Writing to one is tested in tests/smash.rs.
+
+    #[test]
+    fn open_and_read_fd() {
+        let _lock = FD_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let path = temp_path("read");
+        std::fs::write(&path, "first\nsecond\n").unwrap();
+        let mut shell = Shell::new();
+        shell.run_script(&format!("exec 58<{}", path));
+        shell.run_script("read a <&58; read b <&58");
+        shell.run_script("exec 58<&-");
+        assert_eq!(shell.get("a").unwrap().as_str(), "first");
+        assert_eq!(shell.get("b").unwrap().as_str(), "second");
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(
+            shell.run_script("exec /no/such/command"),
+            ExitStatus::ExitedWith(126)
+        );
+    }
+}
@@ -1,10 +1,101 @@
 use super::{BuiltinCommand, BuiltinCommandContext};
-use crate::process::ExitStatus;
+use crate::process::{exit_shell, ExitStatus};
 
 pub struct Exit;
 
 impl BuiltinCommand for Exit {
-    fn run(&self, _: &mut BuiltinCommandContext) -> ExitStatus {
-        std::process::exit(0);
+    fn name(&self) -> &'static str {
+        "exit"
+    }
+
+    fn usage(&self) -> &'static str {
+        "exit [n]"
+    }
+
+    fn run(&self, ctx: &mut BuiltinCommandContext) -> anyhow::Result<ExitStatus> {
+        if !ctx.shell.confirm_exit() {
+            writeln!(ctx.stderr(), "There are stopped jobs.").ok();
+            return Ok(ExitStatus::ExitedWith(1));
+        }
+
+        // Defaults to the status of the last command.
+        let status = match ctx.argv.get(1) {
+            Some(arg) => match arg.parse::<i32>() {
+                Ok(status) => status & 0xff,
+                Err(_) => {
+                    let arg = arg.clone();
+                    writeln!(
+                        ctx.stderr(),
+                        "smash: exit: {}: numeric argument required",
+                        arg
+                    )
+                    .ok();
+                    2
+                }
+            },
+            None => ctx.shell.last_status(),
+        };
+
+        exit_shell(ctx.shell, status);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::process::{hangup_jobs, wait_for_job, ExitStatus, ProcessState};
+    use crate::shell::{SharedBuffer, Shell, FD_LOCK};
+
+    use nix::sys::signal::{kill, Signal};
+    use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
+
+    #[test]
+    fn stopped_jobs_block_first_exit() {
+        let stderr = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), std::io::sink(), stderr.clone());
+        shell.run_script("/bin/sleep 5 &");
+        let job = shell.current_job().unwrap();
+        kill(job.pgid, Signal::SIGSTOP).unwrap();
+        assert!(matches!(
+            wait_for_job(&mut shell, &job),
+            ProcessState::Stopped(_)
+        ));
+
+        assert_eq!(shell.run_script("exit"), ExitStatus::ExitedWith(1));
+        assert_eq!(stderr.contents(), "There are stopped jobs.\n");
+
+        // The second attempt proceeds and hangs up the job.
+        assert!(shell.confirm_exit());
+        hangup_jobs(&shell);
+        assert_eq!(
+            waitpid(job.pgid, None),
+            Ok(WaitStatus::Signaled(job.pgid, Signal::SIGHUP, false))
+        );
+    }
+
+    #[test]
+    fn jobs_are_hung_up_on_exit() {
+        let _lock = FD_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let mut shell = Shell::new();
+        shell.run_script("/bin/sleep 5 &");
+        let job = shell.current_job().unwrap();
+        shell.run_script("/bin/sleep 5 &");
+        let nohup_job = shell.current_job().unwrap();
+        shell.run_script("disown -h");
+
+        hangup_jobs(&shell);
+        assert_eq!(
+            waitpid(job.pgid, None),
+            Ok(WaitStatus::Signaled(job.pgid, Signal::SIGHUP, false))
+        );
+
+        assert_eq!(
+            waitpid(nohup_job.pgid, Some(WaitPidFlag::WNOHANG)),
+            Ok(WaitStatus::StillAlive)
+        );
+        kill(nohup_job.pgid, Signal::SIGKILL).unwrap();
+        assert_eq!(
+            waitpid(nohup_job.pgid, None),
+            Ok(WaitStatus::Signaled(nohup_job.pgid, Signal::SIGKILL, false))
+        );
     }
 }
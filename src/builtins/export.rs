@@ -0,0 +1,185 @@
+use super::{BuiltinCommand, BuiltinCommandContext};
+use crate::process::ExitStatus;
+use crate::quote::shell_quote;
+use crate::variable::{is_valid_name, Value};
+
+pub struct Export;
+
+impl BuiltinCommand for Export {
+    fn name(&self) -> &'static str {
+        "export"
+    }
+
+    fn usage(&self) -> &'static str {
+        "export [-fn] [name[=value] ...]"
+    }
+
+    fn run(&self, ctx: &mut BuiltinCommandContext) -> anyhow::Result<ExitStatus> {
+        let mut args = ctx.argv.iter().skip(1).peekable();
+        let mut exported = true;
+        let mut functions = false;
+        while let Some(flags) = args.next_if(|arg| arg.starts_with('-') && arg.len() > 1) {
+            for flag in flags.chars().skip(1) {
+                match flag {
+                    'n' => exported = false,
+                    'f' => functions = true,
+                    _ => {
+                        writeln!(ctx.stderr(), "smash: export: -{}: invalid option", flag).ok();
+                        return Ok(ExitStatus::ExitedWith(2));
+                    }
+                }
+            }
+        }
+
+        let args: Vec<&String> = args.collect();
+        if args.is_empty() {
+            let mut vars = ctx.shell.exported_vars();
+            vars.sort();
+            for (name, value) in vars {
+                let function_name = name
+                    .strip_prefix("BASH_FUNC_")
+                    .and_then(|name| name.strip_suffix("%%"));
+                match function_name {
+                    Some(name) if functions => {
+                        writeln!(ctx.stdout(), "declare -fx {}", name).ok();
+                    }
+                    None if !functions => {
+                        writeln!(ctx.stdout(), "declare -x {}={}", name, shell_quote(&value)).ok();
+                    }
+                    _ => (),
+                }
+            }
+
+            return Ok(ExitStatus::ExitedWith(0));
+        }
+
+        if functions {
+            let mut status = 0;
+            for name in args {
+                if !ctx.shell.export_function(name, exported) {
+                    writeln!(ctx.stderr(), "smash: export: {}: not a function", name).ok();
+                    status = 1;
+                }
+            }
+
+            return Ok(ExitStatus::ExitedWith(status));
+        }
+
+        let mut status = 0;
+        for arg in args {
+            let (name, value) = match arg.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (arg.as_str(), None),
+            };
+
+            if !is_valid_name(name) {
+                writeln!(
+                    ctx.stderr(),
+                    "smash: export: {}: not a valid identifier",
+                    name
+                )
+                .ok();
+                status = 1;
+                continue;
+            }
+
+            if let Some(value) = value {
+                ctx.shell.set(name, Value::String(value.to_owned()), false);
+            }
+
+            ctx.shell.export(name, exported);
+        }
+
+        Ok(ExitStatus::ExitedWith(status))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::process::ExitStatus;
+    use crate::shell::{SharedBuffer, Shell, FD_LOCK};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn child_env(shell: &mut Shell) -> String {
+        // Unique for each call as tests run in parallel.
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "smash-export-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        shell.run_script(&format!("/usr/bin/env > {}", path.display()));
+        let env = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        env
+    }
+
+    #[test]
+    fn unexport() {
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        shell.run_script("export FOO=bar");
+        assert!(child_env(&mut shell).lines().any(|line| line == "FOO=bar"));
+
+        shell.run_script("export -n FOO");
+        assert!(!child_env(&mut shell).contains("FOO="));
+        shell.run_script("echo $FOO");
+        assert_eq!(stdout.contents(), "bar\n");
+
+        // Assignments keep the flag.
+        shell.run_script("export FOO");
+        shell.run_script("FOO=baz");
+        assert!(child_env(&mut shell).lines().any(|line| line == "FOO=baz"));
+    }
+
+    #[test]
+    fn invalid_names() {
+        let _lock = FD_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let stderr = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), std::io::sink(), stderr.clone());
+        assert_eq!(shell.run_script("export 1abc=x"), ExitStatus::ExitedWith(1));
+        assert_eq!(shell.run_script("export a-b=x"), ExitStatus::ExitedWith(1));
+        assert_eq!(
+            stderr.contents(),
+            "smash: export: 1abc: not a valid identifier\n\
+             smash: export: a-b: not a valid identifier\n"
+        );
+        assert_eq!(
+            shell.run_script("read 1abc <<< x"),
+            ExitStatus::ExitedWith(1)
+        );
+        assert_eq!(shell.run_script("1abc=x"), ExitStatus::ExitedWith(1));
+        assert!(shell.get("1abc").is_none());
+        assert!(shell.get("a-b").is_none());
+
+        assert_eq!(shell.run_script("export _a1=x"), ExitStatus::ExitedWith(0));
+        assert_eq!(shell.run_script("_a1=y"), ExitStatus::ExitedWith(0));
+        assert_eq!(shell.get("_a1").unwrap().as_str(), "y");
+    }
+
+    #[test]
+    fn export_functions() {
+        let mut shell = Shell::with_io(std::io::empty(), std::io::sink(), std::io::sink());
+        shell.run_script("greet() { echo \"hello $1\"; }");
+        assert_eq!(
+            shell.run_script("export -f no_such_function"),
+            ExitStatus::ExitedWith(1)
+        );
+        assert!(!child_env(&mut shell).contains("BASH_FUNC_greet%%"));
+        shell.run_script("export -f greet");
+        assert!(child_env(&mut shell)
+            .lines()
+            .any(|line| line == "BASH_FUNC_greet%%=() { echo \"hello $1\"; }"));
+
+        // A child shell imports it from the environment.
+        let stdout = SharedBuffer::new();
+        let mut child = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        child.import_env(shell.exported_vars().into_iter());
+        child.run_script("greet world");
+        assert_eq!(stdout.contents(), "hello world\n");
+        assert!(child.get("BASH_FUNC_greet%%").is_none());
+
+        shell.run_script("export -fn greet");
+        assert!(!child_env(&mut shell).contains("BASH_FUNC_greet%%"));
+    }
+}
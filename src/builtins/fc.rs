@@ -0,0 +1,251 @@
+use super::{BuiltinCommand, BuiltinCommandContext};
+use crate::history::History;
+use crate::process::ExitStatus;
+
+pub struct Fc;
+
+/// The number of entries listed by `fc -l` without a range.
+const DEFAULT_LIST_LEN: usize = 16;
+
+impl BuiltinCommand for Fc {
+    fn name(&self) -> &'static str {
+        "fc"
+    }
+
+    fn usage(&self) -> &'static str {
+        "fc [-e editor] [-lnr] [first [last]] or fc -s [pat=rep] [command]"
+    }
+
+    fn run(&self, ctx: &mut BuiltinCommandContext) -> anyhow::Result<ExitStatus> {
+        let mut args = &ctx.argv[1..];
+        let (mut list, mut numbered, mut reverse, mut substitute) = (false, true, false, false);
+        let mut editor = None;
+        while let Some(arg) = args.first() {
+            if arg == "--" {
+                args = &args[1..];
+                break;
+            }
+
+            // `-2` is an entry relative to the current one.
+            if !arg.starts_with('-') || arg.len() == 1 || arg[1..].parse::<usize>().is_ok() {
+                break;
+            }
+
+            for flag in arg[1..].chars() {
+                match flag {
+                    'l' => list = true,
+                    'n' => numbered = false,
+                    'r' => reverse = true,
+                    's' => substitute = true,
+                    'e' => match args.get(1) {
+                        Some(name) => {
+                            editor = Some(name.clone());
+                            args = &args[1..];
+                        }
+                        None => {
+                            writeln!(ctx.stderr(), "smash: fc: -e: option requires an argument")
+                                .ok();
+                            return Ok(ExitStatus::ExitedWith(2));
+                        }
+                    },
+                    _ => {
+                        writeln!(ctx.stderr(), "smash: fc: -{}: invalid option", flag).ok();
+                        writeln!(ctx.stderr(), "fc: usage: {}", self.usage()).ok();
+                        return Ok(ExitStatus::ExitedWith(2));
+                    }
+                }
+            }
+
+            args = &args[1..];
+        }
+
+        // The most recent entry is the command line running `fc`.
+        let len = ctx.shell.history().len().saturating_sub(1);
+        if substitute {
+            let (replacement, spec) = match args.first() {
+                Some(arg) if arg.contains('=') => (arg.split_once('='), args.get(1)),
+                _ => (None, args.first()),
+            };
+
+            let command = match resolve(ctx.shell.history(), spec.map(String::as_str), len) {
+                Some(n) => ctx.shell.history().get(n).unwrap_or_default().to_owned(),
+                None => {
+                    writeln!(ctx.stderr(), "smash: fc: no command found").ok();
+                    return Ok(ExitStatus::ExitedWith(1));
+                }
+            };
+
+            let command = match replacement {
+                Some((pat, rep)) if !pat.is_empty() => command.replace(pat, rep),
+                _ => command,
+            };
+
+            return Ok(rerun(ctx, &command));
+        }
+
+        let first_spec = args.first().map(String::as_str);
+        let last_spec = args.get(1).map(String::as_str);
+        let range = if list {
+            let first = match first_spec {
+                Some(_) => resolve(ctx.shell.history(), first_spec, len),
+                None => Some(len.saturating_sub(DEFAULT_LIST_LEN - 1).max(1)),
+            };
+            let last = match last_spec {
+                Some(_) => resolve(ctx.shell.history(), last_spec, len),
+                None => Some(len),
+            };
+            first.zip(last)
+        } else {
+            let first = resolve(ctx.shell.history(), first_spec, len);
+            let last = match last_spec {
+                Some(_) => resolve(ctx.shell.history(), last_spec, len),
+                None => first,
+            };
+            first.zip(last)
+        };
+
+        let (mut first, mut last) = match range {
+            Some(range) if len > 0 => range,
+            _ => {
+                writeln!(
+                    ctx.stderr(),
+                    "smash: fc: history specification out of range"
+                )
+                .ok();
+                return Ok(ExitStatus::ExitedWith(1));
+            }
+        };
+
+        if first > last {
+            std::mem::swap(&mut first, &mut last);
+            reverse = !reverse;
+        }
+
+        let mut numbers: Vec<usize> = (first..=last).collect();
+        if reverse {
+            numbers.reverse();
+        }
+
+        if list {
+            for n in numbers {
+                let entry = ctx.shell.history().get(n).unwrap_or_default().to_owned();
+                if numbered {
+                    writeln!(ctx.stdout(), "{}\t {}", n, entry).ok();
+                } else {
+                    writeln!(ctx.stdout(), "\t {}", entry).ok();
+                }
+            }
+
+            return Ok(ExitStatus::ExitedWith(0));
+        }
+
+        // Edit the entries and run the result.
+        let mut script = String::new();
+        for n in numbers {
+            script.push_str(ctx.shell.history().get(n).unwrap_or_default());
+            script.push('\n');
+        }
+
+        let editor = editor
+            .or_else(|| ctx.shell.get("FCEDIT").map(|var| var.as_str().to_owned()))
+            .or_else(|| ctx.shell.get("EDITOR").map(|var| var.as_str().to_owned()))
+            .filter(|editor| !editor.is_empty())
+            .unwrap_or_else(|| "vi".to_owned());
+        let path = std::env::temp_dir().join(format!("smash-fc-{}", std::process::id()));
+        std::fs::write(&path, script)?;
+
+        let status = ctx
+            .shell
+            .run_script(&format!("{} {}", editor, path.display()));
+        let edited = std::fs::read_to_string(&path);
+        std::fs::remove_file(&path).ok();
+        match (status, edited) {
+            (ExitStatus::ExitedWith(0), Ok(edited)) => Ok(rerun(ctx, edited.trim_end())),
+            (ExitStatus::ExitedWith(0), Err(err)) => Err(err.into()),
+            (status, _) => Ok(status),
+        }
+    }
+}
+
+/// Returns the number of the entry specified by a number, a negative offset
+/// from the current entry, or a prefix. `None` means the previous entry.
+fn resolve(history: &History, spec: Option<&str>, len: usize) -> Option<usize> {
+    let spec = match spec {
+        Some(spec) => spec,
+        None => return (len > 0).then_some(len),
+    };
+
+    match spec.parse::<isize>() {
+        Ok(n) if n > 0 => Some(n as usize).filter(|n| *n <= len),
+        Ok(n) if n < 0 => (len + 1).checked_sub(n.unsigned_abs()).filter(|n| *n > 0),
+        Ok(_) => (len > 0).then_some(len),
+        Err(_) => history.find_prefix(spec, len),
+    }
+}
+
+/// Prints and runs `command` in place of the `fc` command line.
+fn rerun(ctx: &mut BuiltinCommandContext, command: &str) -> ExitStatus {
+    writeln!(ctx.stdout(), "{}", command).ok();
+    ctx.shell.history_mut().replace_last(command);
+    ctx.shell.run_script(command)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::process::ExitStatus;
+    use crate::shell::{SharedBuffer, Shell};
+
+    /// Runs a command line entered at the prompt.
+    fn run_line(shell: &mut Shell, line: &str) -> ExitStatus {
+        shell.history_mut().push(line);
+        shell.run_script(line)
+    }
+
+    #[test]
+    fn list_history() {
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        run_line(&mut shell, "echo a");
+        run_line(&mut shell, "echo b");
+        run_line(&mut shell, "echo c");
+        stdout.clear();
+
+        run_line(&mut shell, "fc -l");
+        assert_eq!(stdout.contents(), "1\t echo a\n2\t echo b\n3\t echo c\n");
+
+        stdout.clear();
+        run_line(&mut shell, "fc -l 2");
+        assert_eq!(stdout.contents(), "2\t echo b\n3\t echo c\n4\t fc -l\n");
+
+        stdout.clear();
+        run_line(&mut shell, "fc -lnr echo\\ a -4");
+        assert_eq!(stdout.contents(), "\t echo b\n\t echo a\n");
+
+        assert_eq!(run_line(&mut shell, "fc -l 100"), ExitStatus::ExitedWith(1));
+    }
+
+    #[test]
+    fn substitute_and_rerun() {
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        run_line(&mut shell, "echo hello");
+        run_line(&mut shell, "true");
+        stdout.clear();
+
+        run_line(&mut shell, "fc -s hello=world ec");
+        assert_eq!(stdout.contents(), "echo world\nworld\n");
+        assert_eq!(shell.history().get(3).unwrap(), "echo world");
+
+        stdout.clear();
+        run_line(&mut shell, "fc -s");
+        assert_eq!(stdout.contents(), "echo world\nworld\n");
+
+        stdout.clear();
+        assert_eq!(run_line(&mut shell, "fc -s -4"), ExitStatus::ExitedWith(0));
+        assert_eq!(stdout.contents(), "echo hello\nhello\n");
+        assert_eq!(
+            run_line(&mut shell, "fc -s nothing"),
+            ExitStatus::ExitedWith(1)
+        );
+    }
+}
@@ -0,0 +1,30 @@
+use super::{BuiltinCommand, BuiltinCommandContext};
+use crate::eval::foreground_job_status;
+use crate::process::{continue_job, resolve_job_spec, ExitStatus};
+
+pub struct Fg;
+
+impl BuiltinCommand for Fg {
+    fn name(&self) -> &'static str {
+        "fg"
+    }
+
+    fn usage(&self) -> &'static str {
+        "fg [job_spec]"
+    }
+
+    fn run(&self, ctx: &mut BuiltinCommandContext) -> anyhow::Result<ExitStatus> {
+        let spec = ctx.argv.get(1).map(String::as_str).unwrap_or("%%");
+        let job = match resolve_job_spec(ctx.shell, spec) {
+            Some(job) => job,
+            None => {
+                smash_err!("fg: {}: no such job", spec);
+                return Ok(ExitStatus::ExitedWith(1));
+            }
+        };
+
+        writeln!(ctx.stdout(), "{}", job.cmd).ok();
+        let state = continue_job(ctx.shell, &job, false);
+        Ok(foreground_job_status(ctx.shell, state, job.pgid))
+    }
+}
@@ -0,0 +1,155 @@
+use super::{BuiltinCommand, BuiltinCommandContext};
+use crate::process::ExitStatus;
+use crate::variable::{is_valid_name, Value};
+
+pub struct Getopts;
+
+impl BuiltinCommand for Getopts {
+    fn name(&self) -> &'static str {
+        "getopts"
+    }
+
+    fn usage(&self) -> &'static str {
+        "getopts optstring name [arg ...]"
+    }
+
+    /// Parses the next option in the arguments (or the positional
+    /// parameters) into `name`, `$OPTARG`, and `$OPTIND`. Returns 1 when the
+    /// options are exhausted.
+    fn run(&self, ctx: &mut BuiltinCommandContext) -> anyhow::Result<ExitStatus> {
+        let argv = ctx.argv;
+        let (optstring, name) = match (argv.get(1), argv.get(2)) {
+            (Some(optstring), Some(name)) => (optstring.as_str(), name.as_str()),
+            _ => {
+                writeln!(ctx.stderr(), "getopts: usage: {}", self.usage()).ok();
+                return Ok(ExitStatus::ExitedWith(2));
+            }
+        };
+
+        if !is_valid_name(name) {
+            writeln!(
+                ctx.stderr(),
+                "smash: getopts: {}: not a valid identifier",
+                name
+            )
+            .ok();
+            return Ok(ExitStatus::ExitedWith(1));
+        }
+
+        let args: Vec<String> = if argv.len() > 3 {
+            argv[3..].to_vec()
+        } else {
+            ctx.shell.args().to_vec()
+        };
+
+        // A leading `:` reports errors through `name` and `$OPTARG` silently.
+        let (silent, optstring) = match optstring.strip_prefix(':') {
+            Some(optstring) => (true, optstring),
+            None => (false, optstring),
+        };
+
+        let optind = ctx
+            .shell
+            .get("OPTIND")
+            .and_then(|var| var.as_str().parse::<usize>().ok())
+            .unwrap_or(1)
+            .max(1);
+        ctx.shell.unset("OPTARG");
+        let arg = match args.get(optind - 1) {
+            Some(arg) if arg == "--" => {
+                return Ok(finish(ctx, name, optind + 1));
+            }
+            Some(arg) if arg.starts_with('-') && arg.len() > 1 => arg,
+            _ => return Ok(finish(ctx, name, optind)),
+        };
+
+        let chars: Vec<char> = arg.chars().collect();
+        let offset = ctx.shell.getopts_offset().min(chars.len() - 1);
+        let opt = chars[offset];
+        let rest: String = chars[offset + 1..].iter().collect();
+        let (next_optind, next_offset) = if rest.is_empty() {
+            (optind + 1, 1)
+        } else {
+            (optind, offset + 1)
+        };
+
+        let spec = optstring.find(opt).filter(|_| opt != ':');
+        let takes_arg = spec.is_some_and(|i| optstring[i + opt.len_utf8()..].starts_with(':'));
+        let (result, optarg, next_optind, next_offset) = match spec {
+            None => {
+                if !silent {
+                    writeln!(ctx.stderr(), "smash: getopts: illegal option -- {}", opt).ok();
+                }
+                let optarg = silent.then(|| opt.to_string());
+                ('?', optarg, next_optind, next_offset)
+            }
+            // The rest of the argument or the next one is the option argument.
+            Some(_) if takes_arg && !rest.is_empty() => (opt, Some(rest), optind + 1, 1),
+            Some(_) if takes_arg => match args.get(optind) {
+                Some(optarg) => (opt, Some(optarg.clone()), optind + 2, 1),
+                None if silent => (':', Some(opt.to_string()), optind + 1, 1),
+                None => {
+                    writeln!(
+                        ctx.stderr(),
+                        "smash: getopts: option requires an argument -- {}",
+                        opt
+                    )
+                    .ok();
+                    ('?', None, optind + 1, 1)
+                }
+            },
+            Some(_) => (opt, None, next_optind, next_offset),
+        };
+
+        ctx.shell
+            .set(name, Value::String(result.to_string()), false);
+        if let Some(optarg) = optarg {
+            ctx.shell.set("OPTARG", Value::String(optarg), false);
+        }
+        ctx.shell
+            .set("OPTIND", Value::String(next_optind.to_string()), false);
+        ctx.shell.set_getopts_offset(next_offset);
+        Ok(ExitStatus::ExitedWith(0))
+    }
+}
+
+/// No option is left: sets `name` to `?` and `$OPTIND` to the index of the
+/// first operand.
+fn finish(ctx: &mut BuiltinCommandContext, name: &str, optind: usize) -> ExitStatus {
+    ctx.shell.set(name, Value::String("?".to_owned()), false);
+    ctx.shell
+        .set("OPTIND", Value::String(optind.to_string()), false);
+    ExitStatus::ExitedWith(1)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::shell::{SharedBuffer, Shell};
+
+    #[test]
+    fn parse_options() {
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        shell.run_script(
+            "while getopts ab:c opt -a -bx -ca -b y -- file; do echo $opt:$OPTARG; done; echo $OPTIND",
+        );
+        assert_eq!(stdout.contents(), "a:\nb:x\nc:\na:\nb:y\n7\n");
+
+        stdout.clear();
+        shell.run_script("OPTIND=1; while getopts :a:x opt -y -a; do echo $opt:$OPTARG; done");
+        assert_eq!(stdout.contents(), "?:y\n::a\n");
+    }
+
+    #[test]
+    fn reset_optind() {
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        // Stop in the middle of `-ab`.
+        shell.run_script("getopts abc opt -ab -c; echo $opt $OPTIND");
+        assert_eq!(stdout.contents(), "a 1\n");
+
+        stdout.clear();
+        shell.run_script("OPTIND=1; while getopts xyz opt -yz; do echo $opt; done; echo $OPTIND");
+        assert_eq!(stdout.contents(), "y\nz\n2\n");
+    }
+}
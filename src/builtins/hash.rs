@@ -0,0 +1,85 @@
+use super::{BuiltinCommand, BuiltinCommandContext};
+use crate::process::ExitStatus;
+
+pub struct Hash;
+
+impl BuiltinCommand for Hash {
+    fn name(&self) -> &'static str {
+        "hash"
+    }
+
+    fn usage(&self) -> &'static str {
+        "hash [-r] [name ...]"
+    }
+
+    fn run(&self, ctx: &mut BuiltinCommandContext) -> anyhow::Result<ExitStatus> {
+        let mut names = &ctx.argv[1..];
+        if names.first().map(String::as_str) == Some("-r") {
+            // Forget the locations and scan `$PATH` again.
+            ctx.shell.path_table_mut().rehash();
+            names = &names[1..];
+        }
+
+        let mut status = 0;
+        for name in names {
+            if !ctx.shell.path_table_mut().rehash_command(name) {
+                writeln!(ctx.stderr(), "smash: hash: {}: not found", name).ok();
+                status = 1;
+            }
+        }
+
+        Ok(ExitStatus::ExitedWith(status))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::process::ExitStatus;
+    use crate::shell::Shell;
+
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn invalidate_cache() {
+        let dir = std::env::temp_dir().join(format!("smash-hash-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let install = |name: &str| {
+            let path = dir.join(name);
+            std::fs::write(&path, "#!/bin/sh\n").unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        };
+
+        install("smash-hash-old");
+        let mut shell = Shell::with_io(std::io::empty(), std::io::sink(), std::io::sink());
+        shell.run_script(&format!("PATH={}", dir.display()));
+        let found = |shell: &mut Shell, name: &str| {
+            shell.run_script(&format!("command -v {}", name)) == ExitStatus::ExitedWith(0)
+        };
+        assert!(found(&mut shell, "smash-hash-old"));
+
+        // Changes in the directory are not visible until rescanned.
+        std::fs::remove_file(dir.join("smash-hash-old")).unwrap();
+        install("smash-hash-new");
+        assert!(found(&mut shell, "smash-hash-old"));
+        assert!(!found(&mut shell, "smash-hash-new"));
+
+        assert_eq!(shell.run_script("hash -r"), ExitStatus::ExitedWith(0));
+        assert!(!found(&mut shell, "smash-hash-old"));
+        assert!(found(&mut shell, "smash-hash-new"));
+
+        // `hash name` updates the location of the command.
+        std::fs::remove_file(dir.join("smash-hash-new")).unwrap();
+        assert_eq!(
+            shell.run_script("hash smash-hash-new"),
+            ExitStatus::ExitedWith(1)
+        );
+        assert!(!found(&mut shell, "smash-hash-new"));
+
+        // So does assigning `$PATH`.
+        install("smash-hash-old");
+        shell.run_script(&format!("PATH={}", dir.display()));
+        assert!(found(&mut shell, "smash-hash-old"));
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
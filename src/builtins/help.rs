@@ -0,0 +1,61 @@
+use super::{builtin_command, builtin_names, BuiltinCommand, BuiltinCommandContext};
+use crate::process::ExitStatus;
+
+pub struct Help;
+
+impl BuiltinCommand for Help {
+    fn name(&self) -> &'static str {
+        "help"
+    }
+
+    fn usage(&self) -> &'static str {
+        "help [name]"
+    }
+
+    fn run(&self, ctx: &mut BuiltinCommandContext) -> anyhow::Result<ExitStatus> {
+        let name = match ctx.argv.get(1) {
+            Some(name) => name,
+            None => {
+                for name in builtin_names() {
+                    let usage = builtin_command(name).unwrap().usage();
+                    writeln!(ctx.stdout(), "{}", usage).ok();
+                }
+
+                return Ok(ExitStatus::ExitedWith(0));
+            }
+        };
+
+        match builtin_command(name) {
+            Some(command) => {
+                writeln!(ctx.stdout(), "{}", command.usage()).ok();
+                Ok(ExitStatus::ExitedWith(0))
+            }
+            None => {
+                writeln!(ctx.stderr(), "smash: help: no help topics match `{}'", name).ok();
+                Ok(ExitStatus::ExitedWith(1))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::process::ExitStatus;
+    use crate::shell::{SharedBuffer, Shell};
+
+    #[test]
+    fn list_builtins() {
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        shell.run_script("help");
+        let lines: Vec<String> = stdout.contents().lines().map(String::from).collect();
+        assert!(lines.iter().any(|line| line.starts_with("cd ")));
+        assert!(lines.iter().any(|line| line == "exit [n]"));
+
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        assert_eq!(shell.run_script("help cd"), ExitStatus::ExitedWith(0));
+        assert_eq!(stdout.contents(), "cd [dir | -]\n");
+        assert_eq!(shell.run_script("help nothing"), ExitStatus::ExitedWith(1));
+    }
+}
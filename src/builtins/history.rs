@@ -0,0 +1,199 @@
+use super::{BuiltinCommand, BuiltinCommandContext};
+use crate::process::ExitStatus;
+use crate::prompt::{format_time, local_time_at};
+use nix::libc;
+use std::path::PathBuf;
+
+pub struct History;
+
+impl BuiltinCommand for History {
+    fn name(&self) -> &'static str {
+        "history"
+    }
+
+    fn usage(&self) -> &'static str {
+        "history [n] or history -anrw [filename]"
+    }
+
+    fn run(&self, ctx: &mut BuiltinCommandContext) -> anyhow::Result<ExitStatus> {
+        let argv = ctx.argv;
+        let flag = match argv.get(1).map(String::as_str) {
+            Some(arg @ ("-a" | "-n" | "-r" | "-w")) => arg,
+            Some(arg) if arg.starts_with('-') && arg != "-" => {
+                writeln!(ctx.stderr(), "smash: history: {}: invalid option", arg).ok();
+                writeln!(ctx.stderr(), "history: usage: {}", self.usage()).ok();
+                return Ok(ExitStatus::ExitedWith(2));
+            }
+            count => return Ok(list(ctx, count)),
+        };
+
+        // The history file defaults to `$HISTFILE`.
+        let path = match argv.get(2) {
+            Some(path) => PathBuf::from(path),
+            None => match ctx.shell.get("HISTFILE") {
+                Some(var) if !var.as_str().is_empty() => PathBuf::from(var.as_str()),
+                _ => {
+                    writeln!(ctx.stderr(), "smash: history: HISTFILE: not set").ok();
+                    return Ok(ExitStatus::ExitedWith(1));
+                }
+            },
+        };
+
+        // Like bash, timestamps are saved only if `$HISTTIMEFORMAT` is set,
+        // or the directories are needed by `shopt -s dirhistory`.
+        let timestamps =
+            ctx.shell.get("HISTTIMEFORMAT").is_some() || ctx.shell.is_shopt_set("dirhistory");
+        let history = ctx.shell.history_mut();
+        let result = match flag {
+            "-a" => history.append_to_file(&path, timestamps),
+            "-n" => history.read_new_lines(&path),
+            "-r" => history.read_file(&path),
+            _ => history.write_file(&path, timestamps),
+        };
+
+        match result {
+            Ok(()) => Ok(ExitStatus::ExitedWith(0)),
+            Err(err) => {
+                writeln!(ctx.stderr(), "smash: history: {}: {}", path.display(), err).ok();
+                Ok(ExitStatus::ExitedWith(1))
+            }
+        }
+    }
+}
+
+/// Prints the last `count` entries (all if `None`) with their numbers, and
+/// when they were entered if `$HISTTIMEFORMAT` is set.
+fn list(ctx: &mut BuiltinCommandContext, count: Option<&str>) -> ExitStatus {
+    let len = ctx.shell.history().len();
+    let count = match count.map(str::parse::<usize>) {
+        None => len,
+        Some(Ok(count)) => count.min(len),
+        Some(Err(_)) => {
+            writeln!(
+                ctx.stderr(),
+                "smash: history: {}: numeric argument required",
+                count.unwrap_or_default()
+            )
+            .ok();
+            return ExitStatus::ExitedWith(1);
+        }
+    };
+
+    let time_format = ctx
+        .shell
+        .get("HISTTIMEFORMAT")
+        .map(|var| var.as_str().to_owned());
+    for n in (len - count + 1)..=len {
+        let history = ctx.shell.history();
+        let time = match (&time_format, history.time(n)) {
+            (Some(format), Some(time)) => format_time(format, &local_time_at(time as libc::time_t)),
+            _ => String::new(),
+        };
+        let line = format!("{:>5}  {}{}", n, time, history.get(n).unwrap_or_default());
+        writeln!(ctx.stdout(), "{}", line).ok();
+    }
+
+    ExitStatus::ExitedWith(0)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::process::ExitStatus;
+    use crate::shell::{SharedBuffer, Shell};
+    use crate::variable::Value;
+
+    /// Runs a command line entered at the prompt.
+    fn run_line(shell: &mut Shell, line: &str) -> ExitStatus {
+        shell.history_mut().push(line);
+        shell.run_script(line)
+    }
+
+    #[test]
+    fn list_history() {
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        run_line(&mut shell, "echo a");
+        run_line(&mut shell, "echo b");
+        stdout.clear();
+
+        run_line(&mut shell, "history");
+        assert_eq!(
+            stdout.contents(),
+            "    1  echo a\n    2  echo b\n    3  history\n"
+        );
+
+        stdout.clear();
+        run_line(&mut shell, "history 2");
+        assert_eq!(stdout.contents(), "    3  history\n    4  history 2\n");
+        assert_eq!(run_line(&mut shell, "history x"), ExitStatus::ExitedWith(1));
+        assert_eq!(
+            run_line(&mut shell, "history -z"),
+            ExitStatus::ExitedWith(2)
+        );
+    }
+
+    #[test]
+    fn show_timestamps() {
+        let path = std::env::temp_dir().join(format!("smash-histfmt-{}", std::process::id()));
+        std::fs::write(&path, "#1667466300\necho a\n").unwrap();
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        shell.run_script(&format!("history -r {}", path.display()));
+        std::fs::remove_file(&path).ok();
+
+        shell.run_script("history");
+        assert_eq!(stdout.contents(), "    1  echo a\n");
+
+        stdout.clear();
+        shell.run_script("HISTTIMEFORMAT='%s: '; history");
+        assert_eq!(stdout.contents(), "    1  1667466300: echo a\n");
+    }
+
+    #[test]
+    fn synchronize_with_file() {
+        let path = std::env::temp_dir().join(format!("smash-histfile-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut first = Shell::with_io(std::io::empty(), std::io::sink(), std::io::sink());
+        let mut second = Shell::with_io(std::io::empty(), std::io::sink(), std::io::sink());
+        first.set(
+            "HISTFILE",
+            Value::String(path.to_str().unwrap().to_owned()),
+            false,
+        );
+        second.set(
+            "HISTFILE",
+            Value::String(path.to_str().unwrap().to_owned()),
+            false,
+        );
+
+        run_line(&mut first, "echo one");
+        run_line(&mut first, "history -a");
+        run_line(&mut second, "history -r");
+        assert_eq!(second.history().len(), 3);
+        assert_eq!(second.history().get(2), Some("echo one"));
+
+        // Only the new entries are appended, and only the new lines are read.
+        run_line(&mut second, "echo two");
+        run_line(&mut second, "history -a");
+        run_line(&mut first, "history -n");
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "echo one\nhistory -a\nhistory -r\necho two\nhistory -a\n"
+        );
+        assert_eq!(first.history().len(), 6);
+        assert_eq!(first.history().get(5), Some("echo two"));
+
+        // `-w` overwrites the file.
+        run_line(&mut first, "history -w");
+        let mut third = Shell::with_io(std::io::empty(), std::io::sink(), std::io::sink());
+        third.run_script(&format!("history -r {}", path.display()));
+        assert_eq!(third.history().len(), 7);
+        assert_eq!(third.history().get(7), Some("history -w"));
+
+        std::fs::remove_file(&path).ok();
+        assert_eq!(
+            third.run_script(&format!("history -n {}", path.display())),
+            ExitStatus::ExitedWith(1)
+        );
+    }
+}
@@ -0,0 +1,87 @@
+use super::{BuiltinCommand, BuiltinCommandContext};
+use crate::process::{destroy_job, wait_for_any_process, ExitStatus, JobState};
+
+pub struct Jobs;
+
+impl BuiltinCommand for Jobs {
+    fn name(&self) -> &'static str {
+        "jobs"
+    }
+
+    fn usage(&self) -> &'static str {
+        "jobs"
+    }
+
+    fn run(&self, ctx: &mut BuiltinCommandContext) -> anyhow::Result<ExitStatus> {
+        // Collect state changes of background jobs.
+        while wait_for_any_process(ctx.shell, true).is_some() {}
+
+        let mut jobs: Vec<_> = ctx.shell.jobs().values().cloned().collect();
+        jobs.sort_by_key(|job| job.id());
+        for job in jobs {
+            let state = match job.state(ctx.shell) {
+                JobState::Done(_) => {
+                    destroy_job(ctx.shell, &job);
+                    "Done"
+                }
+                JobState::Stopped => "Stopped",
+                JobState::Running => "Running",
+            };
+
+            writeln!(ctx.stdout(), "[{}] {}: {}", job.id(), state, job.cmd).ok();
+        }
+
+        Ok(ExitStatus::ExitedWith(0))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::shell::{SharedBuffer, Shell};
+
+    use nix::sys::signal::{kill, Signal};
+    use nix::sys::wait::{waitpid, WaitStatus};
+
+    #[test]
+    fn background_pipeline_is_a_job() {
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        shell.run_script("/bin/sleep 5 | /bin/sleep 5 &");
+        assert_eq!(shell.jobs().len(), 1);
+        let job = shell.current_job().unwrap();
+        assert_eq!(job.processes.len(), 2);
+        assert_eq!(job.pgid, job.processes[0]);
+
+        shell.run_script("jobs");
+        assert_eq!(
+            stdout.contents(),
+            "[1] Running: /bin/sleep 5 | /bin/sleep 5\n"
+        );
+
+        for pid in &job.processes {
+            kill(*pid, Signal::SIGKILL).unwrap();
+            assert_eq!(
+                waitpid(*pid, None),
+                Ok(WaitStatus::Signaled(*pid, Signal::SIGKILL, false))
+            );
+        }
+    }
+
+    #[test]
+    fn done_job_is_shown_once() {
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        shell.run_script("/bin/true 'a b' \"$HOME\" &");
+        // Reaped while waiting for the foreground job.
+        shell.run_script("/bin/sleep 0.2");
+
+        // The command is shown as typed: it's reusable as is.
+        shell.run_script("jobs");
+        assert_eq!(stdout.contents(), "[1] Done: /bin/true 'a b' \"$HOME\"\n");
+        assert!(shell.jobs().is_empty());
+
+        stdout.clear();
+        shell.run_script("jobs");
+        assert_eq!(stdout.contents(), "");
+    }
+}
@@ -0,0 +1,222 @@
+use super::{BuiltinCommand, BuiltinCommandContext};
+use crate::process::{kill_job, resolve_job_spec, ExitStatus};
+
+use nix::sys::signal::{kill, Signal};
+use nix::unistd::Pid;
+use std::str::FromStr;
+
+pub struct Kill;
+
+impl BuiltinCommand for Kill {
+    fn name(&self) -> &'static str {
+        "kill"
+    }
+
+    fn usage(&self) -> &'static str {
+        "kill [-s sigspec | -n signum | -sigspec] pid | jobspec ... or kill -l [sigspec]"
+    }
+
+    fn run(&self, ctx: &mut BuiltinCommandContext) -> anyhow::Result<ExitStatus> {
+        let argv = ctx.argv;
+        let mut args = &argv[1..];
+        let mut signal = Some(Signal::SIGTERM);
+        match args.first().map(String::as_str) {
+            Some("-l") | Some("-L") => return Ok(list_signals(ctx, &args[1..])),
+            Some("-s") | Some("-n") => {
+                let spec = match args.get(1) {
+                    Some(spec) => spec,
+                    None => {
+                        writeln!(
+                            ctx.stderr(),
+                            "smash: kill: {}: option requires an argument",
+                            args[0]
+                        )
+                        .ok();
+                        return Ok(ExitStatus::ExitedWith(2));
+                    }
+                };
+
+                signal = match parse_signal(spec) {
+                    Some(signal) => signal,
+                    None => return Ok(invalid_signal(ctx, spec)),
+                };
+                args = &args[2..];
+            }
+            Some("--") => args = &args[1..],
+            Some(arg) if arg.starts_with('-') && arg.len() > 1 => {
+                signal = match parse_signal(&arg[1..]) {
+                    Some(signal) => signal,
+                    None => return Ok(invalid_signal(ctx, &arg[1..])),
+                };
+                args = &args[1..];
+            }
+            _ => (),
+        }
+
+        if args.first().map(String::as_str) == Some("--") {
+            args = &args[1..];
+        }
+
+        if args.is_empty() {
+            writeln!(ctx.stderr(), "kill: usage: {}", self.usage()).ok();
+            return Ok(ExitStatus::ExitedWith(2));
+        }
+
+        let mut status = 0;
+        for target in args {
+            let result = if target.starts_with('%') {
+                match resolve_job_spec(ctx.shell, target) {
+                    Some(job) => kill_job(ctx.shell, &job, signal),
+                    None => {
+                        writeln!(ctx.stderr(), "smash: kill: {}: no such job", target).ok();
+                        status = 1;
+                        continue;
+                    }
+                }
+            } else {
+                match target.parse() {
+                    Ok(pid) => kill(Pid::from_raw(pid), signal),
+                    Err(_) => {
+                        writeln!(
+                            ctx.stderr(),
+                            "smash: kill: {}: arguments must be process or job IDs",
+                            target
+                        )
+                        .ok();
+                        status = 1;
+                        continue;
+                    }
+                }
+            };
+
+            if let Err(err) = result {
+                writeln!(ctx.stderr(), "smash: kill: ({}) - {}", target, err.desc()).ok();
+                status = 1;
+            }
+        }
+
+        Ok(ExitStatus::ExitedWith(status))
+    }
+}
+
+/// Parses a signal name (`TERM`, `SIGTERM`, or `term`) or number. `0` is
+/// `None`, which only checks if the process exists.
+pub fn parse_signal(spec: &str) -> Option<Option<Signal>> {
+    if let Ok(num) = spec.parse::<i32>() {
+        return match num {
+            0 => Some(None),
+            _ => Signal::try_from(num).ok().map(Some),
+        };
+    }
+
+    let name = spec.to_ascii_uppercase();
+    let name = if name.starts_with("SIG") {
+        name
+    } else {
+        format!("SIG{}", name)
+    };
+    Signal::from_str(&name).ok().map(Some)
+}
+
+/// The name of `signal` without the `SIG` prefix (e.g. `TERM`).
+pub fn signal_name(signal: Signal) -> &'static str {
+    &signal.as_str()[3..]
+}
+
+fn invalid_signal(ctx: &mut BuiltinCommandContext, spec: &str) -> ExitStatus {
+    writeln!(
+        ctx.stderr(),
+        "smash: kill: {}: invalid signal specification",
+        spec
+    )
+    .ok();
+    ExitStatus::ExitedWith(1)
+}
+
+/// `kill -l`: lists the signals, or translates signal names to numbers and
+/// numbers (including exit statuses of signaled processes) to names.
+fn list_signals(ctx: &mut BuiltinCommandContext, specs: &[String]) -> ExitStatus {
+    if specs.is_empty() {
+        let entries: Vec<String> = Signal::iterator()
+            .map(|signal| format!("{:2}) {}", signal as i32, signal.as_str()))
+            .collect();
+        for line in entries.chunks(5) {
+            writeln!(ctx.stdout(), "{}", line.join("\t")).ok();
+        }
+
+        return ExitStatus::ExitedWith(0);
+    }
+
+    let mut status = ExitStatus::ExitedWith(0);
+    for spec in specs {
+        let translated = match spec.parse::<i32>() {
+            // The exit status of a process killed by the signal.
+            Ok(num) => Signal::try_from(if num > 128 { num - 128 } else { num })
+                .ok()
+                .map(|signal| signal_name(signal).to_owned()),
+            Err(_) => parse_signal(spec)
+                .flatten()
+                .map(|signal| (signal as i32).to_string()),
+        };
+
+        match translated {
+            Some(translated) => {
+                writeln!(ctx.stdout(), "{}", translated).ok();
+            }
+            None => status = invalid_signal(ctx, spec),
+        }
+    }
+
+    status
+}
+
+#[cfg(test)]
+mod test {
+    use crate::process::ExitStatus;
+    use crate::shell::{SharedBuffer, Shell};
+
+    #[test]
+    fn list_signals() {
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        shell.run_script("kill -l TERM SIGINT kill");
+        assert_eq!(stdout.contents(), "15\n2\n9\n");
+
+        stdout.clear();
+        shell.run_script("kill -l 9 15 130");
+        assert_eq!(stdout.contents(), "KILL\nTERM\nINT\n");
+
+        stdout.clear();
+        shell.run_script("kill -l");
+        let listing = stdout.contents();
+        assert!(listing.starts_with(" 1) SIGHUP\t 2) SIGINT\t"));
+        assert!(listing.contains(" 9) SIGKILL\t"));
+        assert!(listing.contains("15) SIGTERM"));
+
+        assert_eq!(
+            shell.run_script("kill -l NOSUCH"),
+            ExitStatus::ExitedWith(1)
+        );
+        assert_eq!(shell.run_script("kill -l 999"), ExitStatus::ExitedWith(1));
+    }
+
+    #[test]
+    fn send_signals() {
+        let mut shell = Shell::with_io(std::io::empty(), std::io::sink(), std::io::sink());
+        shell.run_script("/bin/sleep 5 &");
+        assert_eq!(
+            shell.run_script("kill -s INT %1"),
+            ExitStatus::ExitedWith(0)
+        );
+        assert_eq!(shell.run_script("wait %1"), ExitStatus::ExitedWith(130));
+
+        shell.run_script("/bin/sleep 5 &");
+        assert_eq!(shell.run_script("kill -0 %1"), ExitStatus::ExitedWith(0));
+        assert_eq!(shell.run_script("kill %1"), ExitStatus::ExitedWith(0));
+        assert_eq!(shell.run_script("wait %1"), ExitStatus::ExitedWith(143));
+
+        assert_eq!(shell.run_script("kill %9"), ExitStatus::ExitedWith(1));
+        assert_eq!(shell.run_script("kill -FOO 1"), ExitStatus::ExitedWith(1));
+        assert_eq!(shell.run_script("kill"), ExitStatus::ExitedWith(2));
+    }
+}
@@ -0,0 +1,43 @@
+use super::exit::Exit;
+use super::{BuiltinCommand, BuiltinCommandContext};
+use crate::process::ExitStatus;
+
+pub struct Logout;
+
+impl BuiltinCommand for Logout {
+    fn name(&self) -> &'static str {
+        "logout"
+    }
+
+    fn usage(&self) -> &'static str {
+        "logout [n]"
+    }
+
+    fn run(&self, ctx: &mut BuiltinCommandContext) -> anyhow::Result<ExitStatus> {
+        if !ctx.shell.login() {
+            writeln!(ctx.stderr(), "smash: logout: not login shell: use `exit'").ok();
+            return Ok(ExitStatus::ExitedWith(1));
+        }
+
+        Exit.run(ctx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::process::ExitStatus;
+    use crate::shell::{SharedBuffer, Shell};
+
+    // Exiting a login shell is tested by running the binary in tests/smash.rs.
+
+    #[test]
+    fn not_login_shell() {
+        let stderr = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), std::io::sink(), stderr.clone());
+        assert_eq!(shell.run_script("logout"), ExitStatus::ExitedWith(1));
+        assert_eq!(
+            stderr.contents(),
+            "smash: logout: not login shell: use `exit'\n"
+        );
+    }
+}
@@ -0,0 +1,93 @@
+use super::{BuiltinCommand, BuiltinCommandContext};
+use crate::process::ExitStatus;
+use crate::variable::{is_valid_name, Value};
+
+pub struct Mapfile;
+
+/// An alias of `mapfile`.
+pub struct Readarray;
+
+impl BuiltinCommand for Mapfile {
+    fn name(&self) -> &'static str {
+        "mapfile"
+    }
+
+    fn usage(&self) -> &'static str {
+        "mapfile [-t] [array]"
+    }
+
+    fn run(&self, ctx: &mut BuiltinCommandContext) -> anyhow::Result<ExitStatus> {
+        let argv = ctx.argv;
+        let mut args = argv.iter().skip(1).peekable();
+        let trim = args.next_if(|arg| *arg == "-t").is_some();
+        let name = args.next().map(String::as_str).unwrap_or("MAPFILE");
+        if !is_valid_name(name) {
+            writeln!(
+                ctx.stderr(),
+                "smash: {}: {}: not a valid identifier",
+                argv[0],
+                name
+            )
+            .ok();
+            return Ok(ExitStatus::ExitedWith(1));
+        }
+
+        let mut input = Vec::new();
+        ctx.stdin().read_to_end(&mut input)?;
+        let input = String::from_utf8_lossy(&input);
+
+        // Each line is an element including the newline unless `-t`.
+        let lines = input
+            .split_inclusive('\n')
+            .map(|line| {
+                if trim {
+                    line.strip_suffix('\n').unwrap_or(line).to_owned()
+                } else {
+                    line.to_owned()
+                }
+            })
+            .collect();
+
+        ctx.shell.set(name, Value::Array(lines), false);
+        Ok(ExitStatus::ExitedWith(0))
+    }
+}
+
+impl BuiltinCommand for Readarray {
+    fn name(&self) -> &'static str {
+        "readarray"
+    }
+
+    fn usage(&self) -> &'static str {
+        "readarray [-t] [array]"
+    }
+
+    fn run(&self, ctx: &mut BuiltinCommandContext) -> anyhow::Result<ExitStatus> {
+        Mapfile.run(ctx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::shell::{SharedBuffer, Shell};
+
+    #[test]
+    fn read_lines_into_array() {
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(
+            "first\nsecond line\nthird\n".as_bytes(),
+            stdout.clone(),
+            std::io::sink(),
+        );
+        shell.run_script("mapfile -t arr");
+        shell.run_script("echo ${#arr[@]} \"${arr[1]}\"");
+        assert_eq!(stdout.contents(), "3 second line\n");
+
+        // Without `-t`, newlines are kept.
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io("a\nb".as_bytes(), stdout.clone(), std::io::sink());
+        shell.run_script("readarray");
+        shell.run_script("echo \"${MAPFILE[0]}${MAPFILE[1]}\"");
+        assert_eq!(stdout.contents(), "a\nb\n");
+    }
+}
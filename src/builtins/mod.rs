@@ -1,19 +1,116 @@
-use crate::process::ExitStatus;
+use crate::process::{Context, ExitStatus, FdReader};
 use crate::shell::Shell;
 
+use std::io::{Read, Write};
+use std::os::unix::io::RawFd;
 use thiserror::Error;
 
+mod alias;
+mod bg;
 mod cd;
+mod command;
+mod complete;
+mod dirs;
+mod disown;
+mod echo;
 mod eval;
+mod exec;
 mod exit;
+mod export;
+mod fc;
+mod fg;
+mod getopts;
+mod hash;
+mod help;
+mod history;
+mod jobs;
+mod kill;
+mod logout;
+mod mapfile;
+mod nohup;
+mod printf;
+mod pwd;
+mod read;
+mod set;
+mod shopt;
+mod suspend;
+mod trap;
+mod wait;
+mod which;
 
 pub trait BuiltinCommand {
-    fn run(&self, ctx: &mut BuiltinCommandContext) -> ExitStatus;
+    fn name(&self) -> &'static str;
+    /// A one-line usage summary shown by `help`.
+    fn usage(&self) -> &'static str;
+    fn run(&self, ctx: &mut BuiltinCommandContext) -> anyhow::Result<ExitStatus>;
 }
 
 pub struct BuiltinCommandContext<'a> {
     pub argv: &'a [String],
     pub shell: &'a mut Shell,
+    /// The context of the pipeline the builtin belongs to.
+    pub process_ctx: &'a Context,
+    /// Set if the command's stdin/stdout/stderr is redirected: use the
+    /// (redirected) file descriptor instead of the shell's stream.
+    redirected_stdin: Option<FdReader>,
+    redirected_stdout: Option<std::io::Stdout>,
+    redirected_stderr: Option<std::io::Stderr>,
+}
+
+impl<'a> BuiltinCommandContext<'a> {
+    pub fn new(
+        argv: &'a [String],
+        shell: &'a mut Shell,
+        process_ctx: &'a Context,
+    ) -> BuiltinCommandContext<'a> {
+        BuiltinCommandContext {
+            argv,
+            shell,
+            process_ctx,
+            redirected_stdin: None,
+            redirected_stdout: None,
+            redirected_stderr: None,
+        }
+    }
+
+    /// Marks `fd` as redirected.
+    pub fn redirected(&mut self, fd: i32) {
+        match fd {
+            0 => self.redirected_stdin = Some(FdReader(0)),
+            1 => self.redirected_stdout = Some(std::io::stdout()),
+            2 => self.redirected_stderr = Some(std::io::stderr()),
+            _ => (),
+        }
+    }
+
+    pub fn stdin(&mut self) -> &mut dyn Read {
+        match &mut self.redirected_stdin {
+            Some(stdin) => stdin,
+            None => self.shell.stdin(),
+        }
+    }
+
+    /// Returns the file descriptor `stdin()` reads from, if any.
+    pub fn stdin_fd(&self) -> Option<RawFd> {
+        match &self.redirected_stdin {
+            Some(stdin) => Some(stdin.0),
+            None => self.shell.stdin_fd(),
+        }
+    }
+
+    pub fn stdout(&mut self) -> &mut dyn Write {
+        match &mut self.redirected_stdout {
+            Some(stdout) => stdout,
+            None => self.shell.stdout(),
+        }
+    }
+
+    pub fn stderr(&mut self) -> &mut dyn Write {
+        match &mut self.redirected_stderr {
+            Some(stderr) => stderr,
+            None => self.shell.stderr(),
+        }
+    }
 }
 
 #[derive(Debug, Error)]
@@ -22,11 +119,87 @@ pub enum BuiltinCommandError {
     NotFound,
 }
 
-pub fn builtin_command(name: &str) -> Option<Box<dyn BuiltinCommand>> {
-    match name {
-        "exit" => Some(Box::new(exit::Exit)),
-        "cd" => Some(Box::new(cd::Cd)),
-        "eval" => Some(Box::new(eval::Eval)),
-        _ => None,
+/// All builtins in alphabetical order of their names.
+static BUILTIN_COMMANDS: &[&(dyn BuiltinCommand + Sync)] = &[
+    &alias::Alias,
+    &bg::Bg,
+    &cd::Cd,
+    &command::Command,
+    &complete::Complete,
+    &dirs::Dirs,
+    &disown::Disown,
+    &echo::Echo,
+    &eval::Eval,
+    &exec::Exec,
+    &exit::Exit,
+    &export::Export,
+    &fc::Fc,
+    &fg::Fg,
+    &getopts::Getopts,
+    &hash::Hash,
+    &help::Help,
+    &history::History,
+    &jobs::Jobs,
+    &kill::Kill,
+    &logout::Logout,
+    &mapfile::Mapfile,
+    &nohup::Nohup,
+    &printf::Printf,
+    &pwd::Pwd,
+    &read::Read,
+    &mapfile::Readarray,
+    &set::Set,
+    &shopt::Shopt,
+    &suspend::Suspend,
+    &trap::Trap,
+    &alias::Unalias,
+    &wait::Wait,
+    &which::Which,
+];
+
+/// The names accepted by `builtin_command` in alphabetical order.
+pub fn builtin_names() -> Vec<&'static str> {
+    BUILTIN_COMMANDS
+        .iter()
+        .map(|command| command.name())
+        .collect()
+}
+
+pub fn builtin_command(name: &str) -> Option<&'static dyn BuiltinCommand> {
+    BUILTIN_COMMANDS
+        .iter()
+        .find(|command| command.name() == name)
+        .map(|command| *command as &dyn BuiltinCommand)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::shell::{SharedBuffer, FD_LOCK};
+
+    #[test]
+    fn all_builtins_have_usage() {
+        let names = builtin_names();
+        assert!(
+            names.windows(2).all(|pair| pair[0] < pair[1]),
+            "{:?}",
+            names
+        );
+        assert!(names.contains(&"fc"));
+        for name in names {
+            let command = builtin_command(name).unwrap();
+            assert_eq!(command.name(), name);
+            assert!(!command.usage().is_empty());
+        }
+    }
+
+    #[test]
+    fn report_errors() {
+        let _lock = FD_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let stderr = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), std::io::sink(), stderr.clone());
+        // read(2) on a directory fails with EISDIR.
+        assert_eq!(shell.run_script("read x < /"), ExitStatus::ExitedWith(1));
+        assert!(stderr.contents().starts_with("smash: read: Is a directory"));
     }
 }
@@ -0,0 +1,98 @@
+use super::{BuiltinCommand, BuiltinCommandContext};
+use crate::process::{run_external_command, Context, ExitStatus};
+
+pub struct Nohup;
+
+impl BuiltinCommand for Nohup {
+    fn name(&self) -> &'static str {
+        "nohup"
+    }
+
+    fn usage(&self) -> &'static str {
+        "nohup command [arg ...]"
+    }
+
+    fn run(&self, ctx: &mut BuiltinCommandContext) -> anyhow::Result<ExitStatus> {
+        if ctx.argv.len() < 2 {
+            smash_err!("nohup: missing operand");
+            return Ok(ExitStatus::ExitedWith(1));
+        }
+
+        let process_ctx = Context {
+            nohup: true,
+            ..*ctx.process_ctx
+        };
+
+        run_external_command(&process_ctx, ctx.shell, ctx.argv[1..].to_vec(), &[], &[])
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod test {
+    use super::*;
+    use crate::shell::{Shell, CWD_LOCK};
+
+    use nix::sys::signal::{kill, Signal};
+    use nix::sys::wait::waitpid;
+    use nix::unistd::Pid;
+    use std::time::Duration;
+
+    /// Returns the bitmask of ignored signals (`SigIgn` in /proc/[pid]/status).
+    fn ignored_signals(pid: Pid) -> u64 {
+        let status = std::fs::read_to_string(format!("/proc/{}/status", pid)).unwrap();
+        let line = status
+            .lines()
+            .find(|line| line.starts_with("SigIgn:"))
+            .unwrap();
+        u64::from_str_radix(line["SigIgn:".len()..].trim(), 16).unwrap()
+    }
+
+    #[test]
+    fn child_ignores_sighup() {
+        // It may create nohup.out in the current directory.
+        let _lock = CWD_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let nohup_out_existed = std::path::Path::new("nohup.out").exists();
+
+        let mut shell = Shell::new();
+        let process_ctx = Context {
+            pgid: None,
+            background: false,
+            interactive: false,
+            nohup: false,
+            stdin: 0,
+            stdout: 1,
+            pipe_out: None,
+        };
+        let argv = vec!["nohup".to_owned(), "/bin/sleep".to_owned(), "5".to_owned()];
+        let status = Nohup
+            .run(&mut BuiltinCommandContext::new(
+                &argv,
+                &mut shell,
+                &process_ctx,
+            ))
+            .unwrap();
+
+        let pid = match status {
+            ExitStatus::Running(pid) => pid,
+            status => panic!("unexpected status: {:?}", status),
+        };
+
+        let sighup_mask = 1 << (Signal::SIGHUP as u64 - 1);
+        let mut ignored = false;
+        for _ in 0..100 {
+            if ignored_signals(pid) & sighup_mask != 0 {
+                ignored = true;
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        kill(pid, Signal::SIGKILL).unwrap();
+        waitpid(pid, None).unwrap();
+        if !nohup_out_existed {
+            std::fs::remove_file("nohup.out").ok();
+        }
+
+        assert!(ignored);
+    }
+}
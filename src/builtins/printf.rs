@@ -0,0 +1,578 @@
+use super::{BuiltinCommand, BuiltinCommandContext};
+use crate::process::ExitStatus;
+use crate::quote::shell_quote;
+use crate::variable::{is_valid_name, Value};
+
+pub struct Printf;
+
+impl BuiltinCommand for Printf {
+    fn name(&self) -> &'static str {
+        "printf"
+    }
+
+    fn usage(&self) -> &'static str {
+        "printf [-v var] format [arg ...]"
+    }
+
+    fn run(&self, ctx: &mut BuiltinCommandContext) -> anyhow::Result<ExitStatus> {
+        let argv = ctx.argv;
+        let mut args = &argv[1..];
+        let mut var = None;
+        if args.first().map(String::as_str) == Some("-v") {
+            match args.get(1) {
+                Some(name) if is_valid_name(name) => var = Some(name),
+                Some(name) => {
+                    writeln!(
+                        ctx.stderr(),
+                        "smash: printf: {}: not a valid identifier",
+                        name
+                    )
+                    .ok();
+                    return Ok(ExitStatus::ExitedWith(1));
+                }
+                None => {
+                    writeln!(
+                        ctx.stderr(),
+                        "smash: printf: -v: option requires an argument"
+                    )
+                    .ok();
+                    return Ok(ExitStatus::ExitedWith(2));
+                }
+            }
+            args = &args[2..];
+        }
+
+        if args.first().map(String::as_str) == Some("--") {
+            args = &args[1..];
+        }
+
+        let (format, args) = match args.split_first() {
+            Some(split) => split,
+            None => {
+                writeln!(ctx.stderr(), "printf: usage: {}", self.usage()).ok();
+                return Ok(ExitStatus::ExitedWith(2));
+            }
+        };
+
+        let mut formatter = Formatter::new(args);
+        formatter.format_all(format);
+        for error in &formatter.errors {
+            writeln!(ctx.stderr(), "smash: printf: {}", error).ok();
+        }
+
+        match var {
+            Some(name) => ctx.shell.set(name, Value::String(formatter.output), false),
+            None => ctx.stdout().write_all(formatter.output.as_bytes())?,
+        }
+
+        Ok(ExitStatus::ExitedWith(if formatter.errors.is_empty() {
+            0
+        } else {
+            1
+        }))
+    }
+}
+
+/// A conversion specification: `%[flags][width][.precision]conversion`.
+#[derive(Default)]
+struct Spec {
+    left: bool,
+    plus: bool,
+    space: bool,
+    alternate: bool,
+    zero: bool,
+    width: usize,
+    precision: Option<usize>,
+}
+
+struct Formatter<'a> {
+    args: &'a [String],
+    next_arg: usize,
+    output: String,
+    errors: Vec<String>,
+    /// Set by `\c` in `%b`: no more output.
+    stopped: bool,
+}
+
+impl<'a> Formatter<'a> {
+    fn new(args: &'a [String]) -> Formatter<'a> {
+        Formatter {
+            args,
+            next_arg: 0,
+            output: String::new(),
+            errors: Vec::new(),
+            stopped: false,
+        }
+    }
+
+    /// Formats `format` repeatedly until all arguments are consumed.
+    fn format_all(&mut self, format: &str) {
+        loop {
+            let consumed = self.next_arg;
+            self.format_once(format);
+            if self.stopped || self.next_arg >= self.args.len() || self.next_arg == consumed {
+                break;
+            }
+        }
+    }
+
+    fn next_arg(&mut self) -> Option<&'a str> {
+        let arg = self.args.get(self.next_arg)?;
+        self.next_arg += 1;
+        Some(arg)
+    }
+
+    fn format_once(&mut self, format: &str) {
+        let mut chars = format.chars().peekable();
+        while let Some(ch) = chars.next() {
+            match ch {
+                '\\' => push_escape(&mut chars, &mut self.output, false),
+                '%' => {
+                    if chars.peek() == Some(&'%') {
+                        chars.next();
+                        self.output.push('%');
+                        continue;
+                    }
+
+                    let spec = self.parse_spec(&mut chars);
+                    match chars.next() {
+                        Some(conversion) => self.convert(conversion, &spec),
+                        None => self.errors.push("missing format character".to_owned()),
+                    }
+
+                    if self.stopped {
+                        return;
+                    }
+                }
+                _ => self.output.push(ch),
+            }
+        }
+    }
+
+    fn parse_spec(&mut self, chars: &mut std::iter::Peekable<std::str::Chars>) -> Spec {
+        let mut spec = Spec::default();
+        while let Some(flag) = chars.next_if(|ch| "-+ #0".contains(*ch)) {
+            match flag {
+                '-' => spec.left = true,
+                '+' => spec.plus = true,
+                ' ' => spec.space = true,
+                '#' => spec.alternate = true,
+                _ => spec.zero = true,
+            }
+        }
+
+        // `*` takes the width or the precision from an argument.
+        if chars.next_if_eq(&'*').is_some() {
+            let width = self.next_arg().map_or(0, |arg| self.parse_int(arg));
+            spec.left |= width < 0;
+            spec.width = width.unsigned_abs() as usize;
+        } else {
+            spec.width = parse_digits(chars);
+        }
+
+        if chars.next_if_eq(&'.').is_some() {
+            spec.precision = Some(if chars.next_if_eq(&'*').is_some() {
+                let precision = self.next_arg().map_or(0, |arg| self.parse_int(arg));
+                precision.max(0) as usize
+            } else {
+                parse_digits(chars)
+            });
+        }
+
+        spec
+    }
+
+    fn convert(&mut self, conversion: char, spec: &Spec) {
+        let arg = self.next_arg();
+        let text = match conversion {
+            's' => {
+                let s = arg.unwrap_or_default();
+                match spec.precision {
+                    Some(precision) => s.chars().take(precision).collect(),
+                    None => s.to_owned(),
+                }
+            }
+            'b' => {
+                let mut expanded = String::new();
+                let mut chars = arg.unwrap_or_default().chars().peekable();
+                while let Some(ch) = chars.next() {
+                    if ch != '\\' {
+                        expanded.push(ch);
+                    } else if chars.next_if_eq(&'c').is_some() {
+                        self.stopped = true;
+                        break;
+                    } else {
+                        push_escape(&mut chars, &mut expanded, true);
+                    }
+                }
+                expanded
+            }
+            'c' => arg
+                .and_then(|arg| arg.chars().next())
+                .map(String::from)
+                .unwrap_or_default(),
+            'd' | 'i' => {
+                let n = arg.map_or(0, |arg| self.parse_int(arg));
+                format_int(n.unsigned_abs(), n < 0, 10, false, spec)
+            }
+            'o' | 'u' | 'x' | 'X' => {
+                let n = arg.map_or(0, |arg| self.parse_int(arg));
+                let radix = match conversion {
+                    'o' => 8,
+                    'u' => 10,
+                    _ => 16,
+                };
+                format_int(n as u64, false, radix, conversion == 'X', spec)
+            }
+            'q' => shell_quote(arg.unwrap_or_default()),
+            'f' | 'F' | 'e' | 'E' | 'g' | 'G' => {
+                let x = arg.map_or(0.0, |arg| self.parse_float(arg));
+                format_float(x, conversion, spec)
+            }
+            _ => {
+                self.errors
+                    .push(format!("%{}: invalid format character", conversion));
+                self.stopped = true;
+                return;
+            }
+        };
+
+        // Zeros are inserted by `format_int` and `format_float`.
+        pad(&mut self.output, &text, spec.width, spec.left);
+    }
+
+    /// Parses an integer as in C: `0x1f`, `017`, or `'c'` (the code of `c`).
+    fn parse_int(&mut self, arg: &str) -> i64 {
+        if let Some(quoted) = arg.strip_prefix(['\'', '"']) {
+            return quoted.chars().next().map_or(0, |ch| ch as i64);
+        }
+
+        let trimmed = arg.trim_start();
+        let (negative, digits) = match trimmed.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, trimmed.strip_prefix('+').unwrap_or(trimmed)),
+        };
+        let parsed = if let Some(hex) = digits
+            .strip_prefix("0x")
+            .or_else(|| digits.strip_prefix("0X"))
+        {
+            u64::from_str_radix(hex, 16)
+        } else if digits.len() > 1 && digits.starts_with('0') {
+            u64::from_str_radix(&digits[1..], 8)
+        } else {
+            digits.parse()
+        };
+
+        match parsed {
+            Ok(n) if negative => (n as i64).wrapping_neg(),
+            Ok(n) => n as i64,
+            Err(_) => {
+                self.errors.push(format!("{}: invalid number", arg));
+                0
+            }
+        }
+    }
+
+    fn parse_float(&mut self, arg: &str) -> f64 {
+        if arg.starts_with(['\'', '"']) {
+            return self.parse_int(arg) as f64;
+        }
+
+        match arg.trim().parse() {
+            Ok(x) => x,
+            Err(_) => {
+                self.errors.push(format!("{}: invalid number", arg));
+                0.0
+            }
+        }
+    }
+}
+
+fn parse_digits(chars: &mut std::iter::Peekable<std::str::Chars>) -> usize {
+    let mut n = 0usize;
+    while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+        n = n
+            .saturating_mul(10)
+            .saturating_add(digit.to_digit(10).unwrap() as usize);
+    }
+    n
+}
+
+/// Handles a backslash escape in the format (or in a `%b` argument if
+/// `in_arg`, where octal escapes are written as `\0NNN`).
+fn push_escape(
+    chars: &mut std::iter::Peekable<std::str::Chars>,
+    output: &mut String,
+    in_arg: bool,
+) {
+    let ch = match chars.next() {
+        Some(ch) => ch,
+        None => {
+            output.push('\\');
+            return;
+        }
+    };
+
+    let escaped = match ch {
+        'a' => '\x07',
+        'b' => '\x08',
+        'e' | 'E' => '\x1b',
+        'f' => '\x0c',
+        'n' => '\n',
+        'r' => '\r',
+        't' => '\t',
+        'v' => '\x0b',
+        '\\' => '\\',
+        '\'' | '"' if !in_arg => ch,
+        '0'..='7' => {
+            let max_digits = if in_arg && ch == '0' { 3 } else { 2 };
+            let mut code = if in_arg && ch == '0' {
+                0
+            } else {
+                ch.to_digit(8).unwrap()
+            };
+            for _ in 0..max_digits {
+                match chars.next_if(|ch| ch.is_digit(8)) {
+                    Some(digit) => code = code * 8 + digit.to_digit(8).unwrap(),
+                    None => break,
+                }
+            }
+            char::from_u32(code & 0xff).unwrap_or_default()
+        }
+        'x' => {
+            let mut code = 0;
+            let mut digits = 0;
+            while digits < 2 {
+                match chars.next_if(char::is_ascii_hexdigit) {
+                    Some(digit) => code = code * 16 + digit.to_digit(16).unwrap(),
+                    None => break,
+                }
+                digits += 1;
+            }
+            if digits == 0 {
+                output.push_str("\\x");
+                return;
+            }
+            char::from_u32(code).unwrap_or_default()
+        }
+        _ => {
+            output.push('\\');
+            ch
+        }
+    };
+
+    output.push(escaped);
+}
+
+fn pad(output: &mut String, text: &str, width: usize, left: bool) {
+    let padding = width.saturating_sub(text.chars().count());
+    if !left {
+        output.push_str(&" ".repeat(padding));
+    }
+    output.push_str(text);
+    if left {
+        output.push_str(&" ".repeat(padding));
+    }
+}
+
+/// Prepends the sign and zeros (the `0` flag) to `digits`.
+fn finish_number(digits: String, prefix: &str, spec: &Spec, zero_pad: bool) -> String {
+    let len = prefix.len() + digits.chars().count();
+    let zeros = if zero_pad && spec.zero && !spec.left {
+        spec.width.saturating_sub(len)
+    } else {
+        0
+    };
+    format!("{}{}{}", prefix, "0".repeat(zeros), digits)
+}
+
+fn sign(negative: bool, spec: &Spec) -> &'static str {
+    if negative {
+        "-"
+    } else if spec.plus {
+        "+"
+    } else if spec.space {
+        " "
+    } else {
+        ""
+    }
+}
+
+fn format_int(n: u64, negative: bool, radix: u32, upper: bool, spec: &Spec) -> String {
+    let mut digits = match radix {
+        8 => format!("{:o}", n),
+        16 if upper => format!("{:X}", n),
+        16 => format!("{:x}", n),
+        _ => n.to_string(),
+    };
+
+    // The precision is the minimum number of digits.
+    if let Some(precision) = spec.precision {
+        if precision == 0 && n == 0 {
+            digits.clear();
+        }
+        if digits.len() < precision {
+            digits = format!("{}{}", "0".repeat(precision - digits.len()), digits);
+        }
+    }
+
+    let mut prefix = sign(negative, spec).to_owned();
+    if spec.alternate && n != 0 {
+        match radix {
+            8 if !digits.starts_with('0') => prefix.push('0'),
+            16 if upper => prefix.push_str("0X"),
+            16 => prefix.push_str("0x"),
+            _ => (),
+        }
+    }
+
+    finish_number(digits, &prefix, spec, spec.precision.is_none())
+}
+
+fn format_float(x: f64, conversion: char, spec: &Spec) -> String {
+    let upper = conversion.is_ascii_uppercase();
+    if !x.is_finite() {
+        let text = if x.is_nan() { "nan" } else { "inf" };
+        let text = if upper {
+            text.to_uppercase()
+        } else {
+            text.to_owned()
+        };
+        return finish_number(text, sign(x < 0.0, spec), spec, false);
+    }
+
+    let precision = spec.precision.unwrap_or(6);
+    let digits = match conversion.to_ascii_lowercase() {
+        'f' => format!("{:.*}", precision, x.abs()),
+        'e' => format_exponent(x.abs(), precision),
+        _ => {
+            // `%g`: `%e` for a very small or large exponent, `%f` otherwise,
+            // without trailing zeros.
+            let precision = precision.max(1);
+            let exponent = if x == 0.0 {
+                0
+            } else {
+                let e = format_exponent(x.abs(), precision - 1);
+                e[e.find('e').unwrap() + 1..].parse::<i32>().unwrap()
+            };
+            let mut digits = if exponent < -4 || exponent >= precision as i32 {
+                format_exponent(x.abs(), precision - 1)
+            } else {
+                format!("{:.*}", (precision as i32 - 1 - exponent) as usize, x.abs())
+            };
+            if !spec.alternate {
+                let (mantissa, exponent) = match digits.find('e') {
+                    Some(i) => digits.split_at(i),
+                    None => (digits.as_str(), ""),
+                };
+                let mantissa = if mantissa.contains('.') {
+                    mantissa.trim_end_matches('0').trim_end_matches('.')
+                } else {
+                    mantissa
+                };
+                digits = format!("{}{}", mantissa, exponent);
+            }
+            digits
+        }
+    };
+
+    let digits = if upper { digits.to_uppercase() } else { digits };
+    finish_number(
+        digits,
+        sign(x.is_sign_negative() && x != 0.0, spec),
+        spec,
+        true,
+    )
+}
+
+/// Formats `x` like `1.500000e+03`.
+fn format_exponent(x: f64, precision: usize) -> String {
+    let formatted = format!("{:.*e}", precision, x);
+    let (mantissa, exponent) = formatted.split_once('e').unwrap();
+    let exponent: i32 = exponent.parse().unwrap();
+    format!(
+        "{}e{}{:02}",
+        mantissa,
+        if exponent < 0 { '-' } else { '+' },
+        exponent.abs()
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use crate::process::ExitStatus;
+    use crate::shell::{SharedBuffer, Shell};
+    use crate::variable::Value;
+
+    fn printf(script: &str) -> String {
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        shell.run_script(script);
+        stdout.contents()
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eq!(printf("printf '%s-%s\\n' a b c"), "a-b\nc-\n");
+        assert_eq!(
+            printf("printf '[%5s|%-5s|%.2s]' ab ab abc"),
+            "[   ab|ab   |ab]"
+        );
+        assert_eq!(
+            printf("printf '%d %i %+d %05d %x %X %#o' 42 -7 3 -42 255 255 8"),
+            "42 -7 +3 -0042 ff FF 010"
+        );
+        assert_eq!(printf("printf '%d %d' 0x10 \"'A\""), "16 65");
+        assert_eq!(
+            printf("printf '%.2f %e %g %g' 3.14159 1500 0.0001 1234567"),
+            "3.14 1.500000e+03 0.0001 1.23457e+06"
+        );
+        assert_eq!(printf("printf '%c%b%%' xyz 'a\\tb'"), "xa\tb%");
+        assert_eq!(printf("printf '%b' 'a\\cb'; printf '\\x41\\101'"), "aAA");
+        assert_eq!(printf("printf '%*d|%-*d|' 4 1 3 2"), "   1|2  |");
+    }
+
+    #[test]
+    fn assign_to_variable() {
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        assert_eq!(
+            shell.run_script("printf -v x '%05d' 42"),
+            ExitStatus::ExitedWith(0)
+        );
+        assert_eq!(shell.get("x").unwrap().as_str(), "00042");
+        assert_eq!(stdout.contents(), "");
+        assert_eq!(
+            shell.run_script("printf -v 1x foo"),
+            ExitStatus::ExitedWith(1)
+        );
+    }
+
+    #[test]
+    fn quote_arguments() {
+        assert_eq!(printf("printf '%q\\n' abc ''"), "abc\n''\n");
+        assert_eq!(printf("printf '%q' 'a b'"), "'a b'");
+        assert_eq!(printf("printf '%q' \"it's\""), "'it'\\''s'");
+        assert_eq!(printf("printf '%q' '$x;&|'"), "'$x;&|'");
+
+        // The quoted form is read back as the original string.
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        let original = "a 'b' \"c\" $d\te\\f";
+        shell.set("x", Value::String(original.to_owned()), false);
+        shell.run_script("printf -v q '%q' \"$x\"");
+        let quoted = shell.get("q").unwrap().as_str().to_owned();
+        shell.run_script(&format!("printf '%s' {}", quoted));
+        assert_eq!(stdout.contents(), original);
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        let mut shell = Shell::with_io(std::io::empty(), std::io::sink(), std::io::sink());
+        assert_eq!(
+            shell.run_script("printf '%d' abc"),
+            ExitStatus::ExitedWith(1)
+        );
+        assert_eq!(shell.run_script("printf"), ExitStatus::ExitedWith(2));
+    }
+}
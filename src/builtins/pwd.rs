@@ -0,0 +1,20 @@
+use super::{BuiltinCommand, BuiltinCommandContext};
+use crate::process::ExitStatus;
+
+pub struct Pwd;
+
+impl BuiltinCommand for Pwd {
+    fn name(&self) -> &'static str {
+        "pwd"
+    }
+
+    fn usage(&self) -> &'static str {
+        "pwd"
+    }
+
+    fn run(&self, ctx: &mut BuiltinCommandContext) -> anyhow::Result<ExitStatus> {
+        let current_dir = std::env::current_dir()?;
+        writeln!(ctx.stdout(), "{}", current_dir.display()).ok();
+        Ok(ExitStatus::ExitedWith(0))
+    }
+}
@@ -0,0 +1,369 @@
+use super::{BuiltinCommand, BuiltinCommandContext};
+use crate::expand::split_fields;
+use crate::process::ExitStatus;
+use crate::variable::{is_valid_name, Value};
+
+use nix::poll::{poll, PollFd, PollFlags};
+use nix::sys::termios::{tcgetattr, tcsetattr, LocalFlags, SetArg, SpecialCharacterIndices};
+use nix::unistd::isatty;
+use std::os::unix::io::RawFd;
+use std::time::{Duration, Instant};
+
+/// The exit status when `-t` times out.
+const TIMEOUT_STATUS: i32 = 142;
+
+pub struct Read;
+
+#[derive(Default)]
+struct ReadOptions {
+    /// `-r`: backslashes are not special.
+    raw: bool,
+    /// `-a NAME`: split the line into the elements of the array `NAME`.
+    array: Option<String>,
+    /// `-n N`: return after reading `N` characters.
+    nchars: Option<usize>,
+    /// `-t SECONDS`: give up if a line is not read in time.
+    timeout: Option<Duration>,
+}
+
+enum ReadResult {
+    Line(String),
+    Eof(String),
+    TimedOut(String),
+}
+
+impl BuiltinCommand for Read {
+    fn name(&self) -> &'static str {
+        "read"
+    }
+
+    fn usage(&self) -> &'static str {
+        "read [-r] [-a array] [-n nchars] [-t timeout] [name ...]"
+    }
+
+    fn run(&self, ctx: &mut BuiltinCommandContext) -> anyhow::Result<ExitStatus> {
+        let argv = ctx.argv;
+        let mut options = ReadOptions::default();
+        let mut args = argv.iter().skip(1).peekable();
+        while let Some(opt) = args.next_if(|arg| arg.starts_with('-') && arg.len() > 1) {
+            if opt == "-r" {
+                options.raw = true;
+                continue;
+            }
+
+            let flag = opt.get(..2).unwrap_or_default();
+            let value = match opt.as_str() {
+                "-a" | "-n" | "-t" => match args.next() {
+                    Some(value) => value.as_str(),
+                    None => {
+                        writeln!(
+                            ctx.stderr(),
+                            "smash: read: {}: option requires an argument",
+                            opt
+                        )
+                        .ok();
+                        return Ok(ExitStatus::ExitedWith(2));
+                    }
+                },
+                _ if matches!(flag, "-a" | "-n" | "-t") => &opt[2..],
+                _ => {
+                    writeln!(ctx.stderr(), "smash: read: {}: invalid option", opt).ok();
+                    writeln!(ctx.stderr(), "read: usage: {}", self.usage()).ok();
+                    return Ok(ExitStatus::ExitedWith(2));
+                }
+            };
+
+            if flag == "-a" {
+                options.array = Some(value.to_owned());
+            } else if flag == "-n" {
+                match value.parse() {
+                    Ok(nchars) => options.nchars = Some(nchars),
+                    Err(_) => {
+                        writeln!(ctx.stderr(), "smash: read: {}: invalid number", value).ok();
+                        return Ok(ExitStatus::ExitedWith(1));
+                    }
+                }
+            } else {
+                match value
+                    .parse::<f64>()
+                    .ok()
+                    .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                {
+                    Some(timeout) => options.timeout = Some(timeout),
+                    None => {
+                        writeln!(
+                            ctx.stderr(),
+                            "smash: read: {}: invalid timeout specification",
+                            value
+                        )
+                        .ok();
+                        return Ok(ExitStatus::ExitedWith(1));
+                    }
+                }
+            }
+        }
+
+        let names: Vec<&String> = args.collect();
+        let invalid_name = names
+            .iter()
+            .map(|name| name.as_str())
+            .chain(options.array.as_deref())
+            .find(|name| !is_valid_name(name));
+        if let Some(name) = invalid_name {
+            writeln!(
+                ctx.stderr(),
+                "smash: read: {}: not a valid identifier",
+                name
+            )
+            .ok();
+            return Ok(ExitStatus::ExitedWith(1));
+        }
+
+        // `-t 0` only checks if input is available.
+        if options.timeout == Some(Duration::ZERO) {
+            let ready = match ctx.stdin_fd() {
+                Some(fd) => wait_for_input(fd, Some(Duration::ZERO))?,
+                None => true,
+            };
+            return Ok(ExitStatus::ExitedWith(if ready { 0 } else { 1 }));
+        }
+
+        let (line, status) = match read_line(ctx, &options)? {
+            ReadResult::Line(line) => (line, 0),
+            ReadResult::Eof(line) => (line, 1),
+            ReadResult::TimedOut(line) => (line, TIMEOUT_STATUS),
+        };
+        let status = ExitStatus::ExitedWith(status);
+        if let Some(array) = &options.array {
+            let elems = split_fields(&line, &ctx.shell.ifs(), None);
+            ctx.shell.set(array, Value::Array(elems), false);
+            return Ok(status);
+        }
+
+        if names.is_empty() {
+            // The whole line is stored as it is, without field splitting.
+            ctx.shell.set("REPLY", Value::String(line), false);
+            return Ok(status);
+        }
+
+        // The last variable takes the rest of the line.
+        let mut fields = split_fields(&line, &ctx.shell.ifs(), Some(names.len())).into_iter();
+        for name in names {
+            let value = fields.next().unwrap_or_default();
+            ctx.shell.set(name, Value::String(value), false);
+        }
+
+        Ok(status)
+    }
+}
+
+/// Reads a line byte by byte so that no input after the newline is consumed.
+/// Unless `raw`, a backslash escapes the next character (a backslash-newline
+/// continues the line).
+fn read_line(ctx: &mut BuiltinCommandContext, options: &ReadOptions) -> anyhow::Result<ReadResult> {
+    let fd = ctx.stdin_fd();
+    // Read characters as they are typed instead of waiting for a newline.
+    let saved_termios = match (fd, options.nchars) {
+        (Some(fd), Some(_)) if isatty(fd).unwrap_or(false) => {
+            let saved = tcgetattr(fd)?;
+            let mut termios = saved.clone();
+            termios.local_flags.remove(LocalFlags::ICANON);
+            termios.control_chars[SpecialCharacterIndices::VMIN as usize] = 1;
+            termios.control_chars[SpecialCharacterIndices::VTIME as usize] = 0;
+            tcsetattr(fd, SetArg::TCSANOW, &termios)?;
+            Some((fd, saved))
+        }
+        _ => None,
+    };
+
+    let result = read_chars(ctx, fd, options);
+    if let Some((fd, saved)) = saved_termios {
+        tcsetattr(fd, SetArg::TCSANOW, &saved)?;
+    }
+
+    result
+}
+
+fn read_chars(
+    ctx: &mut BuiltinCommandContext,
+    fd: Option<RawFd>,
+    options: &ReadOptions,
+) -> anyhow::Result<ReadResult> {
+    let deadline = options.timeout.map(|timeout| Instant::now() + timeout);
+    let mut line = Vec::new();
+    let mut nchars = 0;
+    // The number of bytes left in the current UTF-8 sequence.
+    let mut remaining = 0u8;
+    let mut escaped = false;
+    let mut byte = [0; 1];
+    loop {
+        if options.nchars == Some(nchars) {
+            break;
+        }
+
+        if let (Some(fd), Some(deadline)) = (fd, deadline) {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if !wait_for_input(fd, Some(remaining))? {
+                return Ok(ReadResult::TimedOut(
+                    String::from_utf8_lossy(&line).into_owned(),
+                ));
+            }
+        }
+
+        if ctx.stdin().read(&mut byte)? == 0 {
+            return Ok(ReadResult::Eof(String::from_utf8_lossy(&line).into_owned()));
+        }
+
+        remaining = match byte[0] {
+            0x80..=0xbf => remaining.saturating_sub(1),
+            0xc0..=0xdf => 1,
+            0xe0..=0xef => 2,
+            0xf0..=0xf7 => 3,
+            _ => 0,
+        };
+        if remaining == 0 {
+            nchars += 1;
+        }
+
+        match byte[0] {
+            b'\n' if escaped => escaped = false,
+            b'\n' => break,
+            b'\\' if !options.raw && !escaped => escaped = true,
+            ch => {
+                escaped = false;
+                line.push(ch);
+            }
+        }
+    }
+
+    Ok(ReadResult::Line(
+        String::from_utf8_lossy(&line).into_owned(),
+    ))
+}
+
+/// Waits until `fd` becomes readable. Returns `false` on a timeout.
+fn wait_for_input(fd: RawFd, timeout: Option<Duration>) -> anyhow::Result<bool> {
+    let timeout = match timeout {
+        Some(timeout) => timeout.as_millis().min(i32::MAX as u128) as i32,
+        None => -1,
+    };
+
+    let mut fds = [PollFd::new(fd, PollFlags::POLLIN)];
+    loop {
+        match poll(&mut fds, timeout) {
+            Ok(n) => return Ok(n > 0),
+            Err(nix::errno::Errno::EINTR) => continue,
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::process::ExitStatus;
+    use crate::shell::{SharedBuffer, Shell, FD_LOCK};
+
+    fn read_from(input: &'static str, script: &str) -> Shell {
+        let mut shell = Shell::with_io(input.as_bytes(), SharedBuffer::new(), std::io::sink());
+        shell.run_script(script);
+        shell
+    }
+
+    #[test]
+    fn split_into_variables() {
+        let shell = read_from("  a b  c d \nnext\n", "read x y z");
+        assert_eq!(shell.get("x").unwrap().as_str(), "a");
+        assert_eq!(shell.get("y").unwrap().as_str(), "b");
+        assert_eq!(shell.get("z").unwrap().as_str(), "c d");
+    }
+
+    #[test]
+    fn reply() {
+        let _lock = FD_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let shell = read_from("  a  b \\n\nnext\n", "read");
+        assert_eq!(shell.get("REPLY").unwrap().as_str(), "  a  b n");
+
+        let mut shell = Shell::new();
+        shell.run_script("read -r <<< 'a\\b'");
+        assert_eq!(shell.get("REPLY").unwrap().as_str(), "a\\b");
+    }
+
+    #[test]
+    fn nchars() {
+        let shell = read_from("yes\n", "read -n 1 a; read b");
+        assert_eq!(shell.get("a").unwrap().as_str(), "y");
+        assert_eq!(shell.get("b").unwrap().as_str(), "es");
+
+        let shell = read_from("あい\n", "read -n1 a; read -n 5 b");
+        assert_eq!(shell.get("a").unwrap().as_str(), "あ");
+        assert_eq!(shell.get("b").unwrap().as_str(), "い");
+    }
+
+    #[test]
+    fn timeout() {
+        let _lock = FD_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let mut shell = Shell::new();
+        // The pipe stays open without input until `sleep` exits.
+        assert_eq!(
+            shell.run_script("read -t 0.05 x < <(/bin/sleep 0.3)"),
+            ExitStatus::ExitedWith(142)
+        );
+        assert_eq!(shell.get("x").unwrap().as_str(), "");
+
+        assert_eq!(
+            shell.run_script("read -t 1 x <<< 'in time'"),
+            ExitStatus::ExitedWith(0)
+        );
+        assert_eq!(shell.get("x").unwrap().as_str(), "in time");
+        assert_eq!(shell.run_script("read -t x y"), ExitStatus::ExitedWith(1));
+    }
+
+    #[test]
+    fn into_array() {
+        let _lock = FD_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        shell.run_script("arr=(old old old old); read -a arr <<< \"  a b  c \"");
+        shell.run_script("echo ${#arr[@]}: ${arr[@]}");
+        assert_eq!(stdout.contents(), "3: a b c\n");
+
+        stdout.clear();
+        shell.run_script("IFS=, read -r -a arr <<< 'x\\y,,z'; echo ${#arr[@]} ${arr[0]}");
+        assert_eq!(stdout.contents(), "3 x\\y\n");
+        assert_eq!(
+            shell.run_script("read -a 1x <<< a"),
+            ExitStatus::ExitedWith(1)
+        );
+    }
+
+    #[test]
+    fn backslashes() {
+        let shell = read_from("a\\ b\\\nc\n", "read x");
+        assert_eq!(shell.get("x").unwrap().as_str(), "a bc");
+
+        let shell = read_from("a\\ b\n", "read -r x");
+        assert_eq!(shell.get("x").unwrap().as_str(), "a\\ b");
+    }
+
+    #[test]
+    fn eof() {
+        let mut shell = Shell::with_io("a b".as_bytes(), SharedBuffer::new(), std::io::sink());
+        assert_eq!(shell.run_script("read x"), ExitStatus::ExitedWith(1));
+        assert_eq!(shell.get("x").unwrap().as_str(), "a b");
+    }
+
+    #[test]
+    fn custom_ifs() {
+        let _lock = FD_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let mut shell = Shell::new();
+        shell.run_script("IFS=: read a b c <<< \"x:y:z\"");
+        assert_eq!(shell.get("a").unwrap().as_str(), "x");
+        assert_eq!(shell.get("b").unwrap().as_str(), "y");
+        assert_eq!(shell.get("c").unwrap().as_str(), "z");
+        assert!(shell.get("IFS").is_none());
+
+        shell.run_script("IFS=': ' read a b <<< \" x :: y:z \"");
+        assert_eq!(shell.get("a").unwrap().as_str(), "x");
+        assert_eq!(shell.get("b").unwrap().as_str(), ": y:z");
+    }
+}
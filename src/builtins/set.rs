@@ -0,0 +1,207 @@
+use super::{BuiltinCommand, BuiltinCommandContext};
+use crate::process::ExitStatus;
+use crate::shell::SET_OPTIONS;
+
+pub struct Set;
+
+impl BuiltinCommand for Set {
+    fn name(&self) -> &'static str {
+        "set"
+    }
+
+    fn usage(&self) -> &'static str {
+        "set [-efux | +efux] [-o | +o] [option] [--] [arg ...]"
+    }
+
+    fn run(&self, ctx: &mut BuiltinCommandContext) -> anyhow::Result<ExitStatus> {
+        let argv = ctx.argv;
+        let mut args = argv.iter().skip(1);
+        while let Some(arg) = args.next() {
+            // The rest are the positional parameters.
+            if arg == "--" {
+                ctx.shell.set_args(args.cloned().collect());
+                break;
+            }
+
+            if !arg.starts_with(['-', '+']) {
+                let rest = std::iter::once(arg).chain(args).cloned().collect();
+                ctx.shell.set_args(rest);
+                break;
+            }
+
+            let value = arg.starts_with('-');
+            let flags = match arg.strip_prefix(['-', '+']) {
+                Some(flags) if !flags.is_empty() => flags,
+                _ => {
+                    smash_err!("set: {}: invalid option", arg);
+                    return Ok(ExitStatus::ExitedWith(1));
+                }
+            };
+
+            if flags == "o" {
+                match args.next() {
+                    Some(name) => {
+                        if !ctx.shell.set_option(name, value) {
+                            smash_err!("set: {}: invalid option name", name);
+                            return Ok(ExitStatus::ExitedWith(1));
+                        }
+                    }
+                    // `set -o` or `set +o`: list the options.
+                    None => list_options(ctx, value),
+                }
+
+                continue;
+            }
+
+            // `-e` is a shorthand for `-o errexit`, etc.
+            for flag in flags.chars() {
+                let name = SET_OPTIONS
+                    .iter()
+                    .find(|(_, option_flag)| *option_flag == Some(flag))
+                    .map(|(name, _)| name);
+                match name {
+                    Some(name) => {
+                        ctx.shell.set_option(name, value);
+                    }
+                    None => {
+                        smash_err!("set: {}{}: invalid option", &arg[..1], flag);
+                        return Ok(ExitStatus::ExitedWith(1));
+                    }
+                }
+            }
+        }
+
+        Ok(ExitStatus::ExitedWith(0))
+    }
+}
+
+/// Prints the state of each option: `name on` for `set -o`, or a command
+/// to restore it for `set +o`.
+fn list_options(ctx: &mut BuiltinCommandContext, human_readable: bool) {
+    for (name, _) in SET_OPTIONS {
+        let set = ctx.shell.is_option_set(name);
+        if human_readable {
+            writeln!(
+                ctx.stdout(),
+                "{:<15}\t{}",
+                name,
+                if set { "on" } else { "off" }
+            )
+            .ok();
+        } else {
+            writeln!(
+                ctx.stdout(),
+                "set {}o {}",
+                if set { '-' } else { '+' },
+                name
+            )
+            .ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::process::ExitStatus;
+    use crate::shell::{SharedBuffer, Shell};
+
+    #[test]
+    fn set_and_list_options() {
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        assert_eq!(
+            shell.run_script("set -eu -o pipefail"),
+            ExitStatus::ExitedWith(0)
+        );
+        assert!(shell.is_option_set("errexit"));
+        assert!(shell.is_option_set("nounset"));
+        assert!(shell.is_option_set("pipefail"));
+        assert!(!shell.is_option_set("xtrace"));
+
+        shell.run_script("set -o");
+        let listing = stdout.contents();
+        for line in [
+            "errexit        \ton",
+            "nounset        \ton",
+            "pipefail       \ton",
+            "xtrace         \toff",
+        ] {
+            assert!(listing.lines().any(|l| l == line), "{}", line);
+        }
+
+        stdout.clear();
+        shell.run_script("set +eu -x; set +o");
+        let listing = stdout.contents();
+        for line in [
+            "set +o errexit",
+            "set +o nounset",
+            "set -o pipefail",
+            "set -o xtrace",
+        ] {
+            assert!(listing.lines().any(|l| l == line), "{}", line);
+        }
+
+        assert_eq!(shell.run_script("set -q"), ExitStatus::ExitedWith(1));
+        assert_eq!(shell.run_script("set -o nosuch"), ExitStatus::ExitedWith(1));
+    }
+
+    #[test]
+    fn positional_parameters() {
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        shell.run_script("set -- a 'b c'; echo $# $2");
+        shell.run_script("set -u x; echo $# $1");
+        assert!(shell.is_option_set("nounset"));
+        assert_eq!(shell.run_script("set --"), ExitStatus::ExitedWith(0));
+        shell.run_script("echo $#");
+        assert_eq!(stdout.contents(), "2 b c\n1 x\n0\n");
+    }
+
+    #[test]
+    fn pipefail() {
+        let mut shell = Shell::with_io(std::io::empty(), std::io::sink(), std::io::sink());
+        let script = "/bin/sh -c 'exit 2' | /bin/false | /bin/true";
+        assert_eq!(shell.run_script(script), ExitStatus::ExitedWith(0));
+        shell.run_script("set -o pipefail");
+        assert_eq!(shell.run_script(script), ExitStatus::ExitedWith(1));
+        assert_eq!(
+            shell.run_script("/bin/sh -c 'exit 2' | /bin/true"),
+            ExitStatus::ExitedWith(2)
+        );
+    }
+
+    #[test]
+    fn nounset() {
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        // A non-interactive shell exits on the error, which is tested in
+        // tests/smash.rs.
+        shell.interactive = true;
+        shell.run_script("set -u; empty=");
+        assert_eq!(shell.run_script("echo $nosuch"), ExitStatus::ExitedWith(1));
+        assert_eq!(shell.run_script("echo $1"), ExitStatus::ExitedWith(1));
+        assert_eq!(stdout.contents(), "");
+
+        // Set but empty parameters and empty arrays are not errors.
+        assert_eq!(
+            shell.run_script("echo \"$empty\" $# ${nosuch[@]} \"$@\""),
+            ExitStatus::ExitedWith(0)
+        );
+        assert_eq!(stdout.contents(), " 0\n");
+    }
+
+    #[test]
+    fn xtrace() {
+        let stderr = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), std::io::sink(), stderr.clone());
+        shell.run_script("set -x; x='a b'; y=1 echo \"$x\" c; set +x; echo d");
+        assert_eq!(
+            stderr.contents(),
+            "+ x='a b'\n+ y=1 echo 'a b' c\n+ set +x\n"
+        );
+
+        stderr.clear();
+        shell.run_script("PS4='> '; set -x; echo e");
+        assert_eq!(stderr.contents(), "> echo e\n");
+    }
+}
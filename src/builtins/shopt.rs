@@ -0,0 +1,136 @@
+use super::{BuiltinCommand, BuiltinCommandContext};
+use crate::process::ExitStatus;
+use crate::shell::SHOPT_OPTIONS;
+
+pub struct Shopt;
+
+impl BuiltinCommand for Shopt {
+    fn name(&self) -> &'static str {
+        "shopt"
+    }
+
+    fn usage(&self) -> &'static str {
+        "shopt [-s | -u] [optname ...]"
+    }
+
+    fn run(&self, ctx: &mut BuiltinCommandContext) -> anyhow::Result<ExitStatus> {
+        let mut args = &ctx.argv[1..];
+        let value = match args.first().map(String::as_str) {
+            Some("-s") => Some(true),
+            Some("-u") => Some(false),
+            Some(arg) if arg.starts_with('-') => {
+                writeln!(ctx.stderr(), "smash: shopt: {}: invalid option", arg).ok();
+                writeln!(ctx.stderr(), "shopt: usage: {}", self.usage()).ok();
+                return Ok(ExitStatus::ExitedWith(2));
+            }
+            _ => None,
+        };
+
+        if value.is_some() {
+            args = &args[1..];
+        }
+
+        for name in args {
+            if !SHOPT_OPTIONS.contains(&name.as_str()) {
+                writeln!(
+                    ctx.stderr(),
+                    "smash: shopt: {}: invalid shell option name",
+                    name
+                )
+                .ok();
+                return Ok(ExitStatus::ExitedWith(1));
+            }
+        }
+
+        match value {
+            // `shopt -s name` or `shopt -u name`
+            Some(value) if !args.is_empty() => {
+                for name in args {
+                    ctx.shell.set_shopt(name, value);
+                }
+
+                Ok(ExitStatus::ExitedWith(0))
+            }
+            // `shopt -s` or `shopt -u`: list the options in the state.
+            Some(value) => {
+                for name in SHOPT_OPTIONS {
+                    if ctx.shell.is_shopt_set(name) == value {
+                        print_option(ctx, name);
+                    }
+                }
+
+                Ok(ExitStatus::ExitedWith(0))
+            }
+            // `shopt [name ...]`: succeeds only if all of them are set.
+            None => {
+                let names: Vec<&str> = if args.is_empty() {
+                    SHOPT_OPTIONS.to_vec()
+                } else {
+                    args.iter().map(String::as_str).collect()
+                };
+
+                let mut status = 0;
+                for name in names {
+                    print_option(ctx, name);
+                    if !ctx.shell.is_shopt_set(name) {
+                        status = 1;
+                    }
+                }
+
+                Ok(ExitStatus::ExitedWith(if args.is_empty() {
+                    0
+                } else {
+                    status
+                }))
+            }
+        }
+    }
+}
+
+fn print_option(ctx: &mut BuiltinCommandContext, name: &str) {
+    let state = if ctx.shell.is_shopt_set(name) {
+        "on"
+    } else {
+        "off"
+    };
+
+    writeln!(ctx.stdout(), "{:<15}\t{}", name, state).ok();
+}
+
+#[cfg(test)]
+mod test {
+    use crate::process::ExitStatus;
+    use crate::shell::{SharedBuffer, Shell, SHOPT_OPTIONS};
+
+    #[test]
+    fn set_and_query() {
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        assert_eq!(shell.run_script("shopt autocd"), ExitStatus::ExitedWith(1));
+        assert_eq!(
+            shell.run_script("shopt -s autocd histappend"),
+            ExitStatus::ExitedWith(0)
+        );
+        assert!(shell.is_shopt_set("autocd"));
+        assert_eq!(shell.run_script("shopt autocd"), ExitStatus::ExitedWith(0));
+        assert_eq!(
+            stdout.contents(),
+            "autocd         \toff\nautocd         \ton\n"
+        );
+
+        stdout.clear();
+        shell.run_script("shopt -u autocd");
+        shell.run_script("shopt -s");
+        assert_eq!(stdout.contents(), "histappend     \ton\n");
+
+        stdout.clear();
+        shell.run_script("shopt");
+        assert_eq!(stdout.contents().lines().count(), SHOPT_OPTIONS.len());
+        assert!(stdout.contents().contains("nullglob       \toff\n"));
+
+        assert_eq!(
+            shell.run_script("shopt -s no-such-option"),
+            ExitStatus::ExitedWith(1)
+        );
+    }
+}
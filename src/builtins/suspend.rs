@@ -0,0 +1,61 @@
+use super::{BuiltinCommand, BuiltinCommandContext};
+use crate::process::ExitStatus;
+
+use nix::sys::signal::{killpg, Signal};
+use nix::unistd::getpgrp;
+
+pub struct Suspend;
+
+impl BuiltinCommand for Suspend {
+    fn name(&self) -> &'static str {
+        "suspend"
+    }
+
+    fn usage(&self) -> &'static str {
+        "suspend [-f]"
+    }
+
+    /// Stops the shell until it receives SIGCONT (e.g. `fg` in the parent
+    /// shell).
+    fn run(&self, ctx: &mut BuiltinCommandContext) -> anyhow::Result<ExitStatus> {
+        let force = match ctx.argv.get(1).map(String::as_str) {
+            Some("-f") => true,
+            Some(arg) => {
+                writeln!(ctx.stderr(), "smash: suspend: {}: invalid option", arg).ok();
+                writeln!(ctx.stderr(), "suspend: usage: {}", self.usage()).ok();
+                return Ok(ExitStatus::ExitedWith(2));
+            }
+            None => false,
+        };
+
+        // Nothing would resume a login shell.
+        if ctx.shell.login() && !force {
+            writeln!(ctx.stderr(), "smash: suspend: cannot suspend a login shell").ok();
+            return Ok(ExitStatus::ExitedWith(1));
+        }
+
+        ctx.stdout().flush().ok();
+        if let Err(err) = killpg(getpgrp(), Signal::SIGSTOP) {
+            writeln!(ctx.stderr(), "smash: suspend: {}", err.desc()).ok();
+            return Ok(ExitStatus::ExitedWith(1));
+        }
+
+        Ok(ExitStatus::ExitedWith(0))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::process::ExitStatus;
+    use crate::shell::Shell;
+
+    // Stopping the shell is tested by running the binary in tests/smash.rs.
+
+    #[test]
+    fn refuse_in_login_shell() {
+        let mut shell = Shell::with_io(std::io::empty(), std::io::sink(), std::io::sink());
+        shell.set_login(true);
+        assert_eq!(shell.run_script("suspend"), ExitStatus::ExitedWith(1));
+        assert_eq!(shell.run_script("suspend -x"), ExitStatus::ExitedWith(2));
+    }
+}
@@ -0,0 +1,207 @@
+use super::{BuiltinCommand, BuiltinCommandContext};
+use crate::process::ExitStatus;
+use crate::quote::shell_quote;
+
+pub struct Trap;
+
+impl BuiltinCommand for Trap {
+    fn name(&self) -> &'static str {
+        "trap"
+    }
+
+    fn usage(&self) -> &'static str {
+        "trap [-p] [action condition ...]"
+    }
+
+    fn run(&self, ctx: &mut BuiltinCommandContext) -> anyhow::Result<ExitStatus> {
+        let argv = ctx.argv;
+        let args = match argv.get(1).map(String::as_str) {
+            Some("--") => &argv[2..],
+            // `trap -p [condition ...]`: print the traps to be reused as input.
+            Some("-p") => return Ok(print_traps(ctx, &argv[2..])),
+            _ => &argv[1..],
+        };
+
+        let (action, conditions) = match args.split_first() {
+            Some(split) => split,
+            None => return Ok(print_traps(ctx, &[])),
+        };
+
+        if conditions.is_empty() {
+            writeln!(ctx.stderr(), "trap: usage: {}", self.usage()).ok();
+            return Ok(ExitStatus::ExitedWith(2));
+        }
+
+        let mut status = 0;
+        for condition in conditions {
+            let condition = match parse_condition(condition) {
+                Some(condition) => condition,
+                None => {
+                    invalid_condition(ctx, condition);
+                    status = 1;
+                    continue;
+                }
+            };
+
+            if action == "-" {
+                ctx.shell.remove_trap(condition);
+            } else {
+                ctx.shell.set_trap(condition, action);
+            }
+        }
+
+        Ok(ExitStatus::ExitedWith(status))
+    }
+}
+
+/// Normalizes a trap condition. Only the pseudo-signals are supported for
+/// now: `EXIT` (or `0`), `DEBUG` (before each simple command), `ERR` (after
+/// a failed command), and `RETURN` (when a function returns).
+fn parse_condition(condition: &str) -> Option<&'static str> {
+    match condition {
+        "EXIT" | "0" => Some("EXIT"),
+        "DEBUG" => Some("DEBUG"),
+        "ERR" => Some("ERR"),
+        "RETURN" => Some("RETURN"),
+        _ => None,
+    }
+}
+
+fn invalid_condition(ctx: &mut BuiltinCommandContext, condition: &str) {
+    writeln!(
+        ctx.stderr(),
+        "smash: trap: {}: invalid signal specification",
+        condition
+    )
+    .ok();
+}
+
+/// Prints the traps on `conditions` (all traps if empty) as `trap` commands.
+fn print_traps(ctx: &mut BuiltinCommandContext, conditions: &[String]) -> ExitStatus {
+    let mut status = 0;
+    let mut selected = Vec::new();
+    for condition in conditions {
+        match parse_condition(condition) {
+            Some(condition) => selected.push(condition),
+            None => {
+                invalid_condition(ctx, condition);
+                status = 1;
+            }
+        }
+    }
+
+    let lines: Vec<String> = ctx
+        .shell
+        .traps()
+        .into_iter()
+        .filter(|(condition, _)| conditions.is_empty() || selected.contains(condition))
+        .map(|(condition, command)| format!("trap -- {} {}", shell_quote(command), condition))
+        .collect();
+    for line in lines {
+        writeln!(ctx.stdout(), "{}", line).ok();
+    }
+
+    ExitStatus::ExitedWith(status)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::process::ExitStatus;
+    use crate::shell::{SharedBuffer, Shell, FD_LOCK};
+
+    #[test]
+    fn exit_trap_preserves_status() {
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        shell.run_script("trap 'echo $?; true' EXIT; /bin/false");
+        assert_eq!(shell.run_exit_trap(shell.last_status()), 1);
+        assert_eq!(stdout.contents(), "1\n");
+        assert_eq!(shell.last_status(), 1);
+
+        // The trap runs only once.
+        stdout.clear();
+        assert_eq!(shell.run_exit_trap(0), 0);
+        assert_eq!(stdout.contents(), "");
+    }
+
+    #[test]
+    fn list_and_reset_traps() {
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        shell.run_script("trap 'echo '\\''bye'\\''' EXIT; trap");
+        assert_eq!(stdout.contents(), "trap -- 'echo '\\''bye'\\' EXIT\n");
+
+        stdout.clear();
+        shell.run_script("trap - 0; trap");
+        assert_eq!(stdout.contents(), "");
+        assert_eq!(
+            shell.run_script("trap 'echo' NOSUCHSIGNAL"),
+            ExitStatus::ExitedWith(1)
+        );
+    }
+
+    #[test]
+    fn print_traps_reusably() {
+        let _lock = FD_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        shell.run_script("trap 'echo \"it'\\''s $HOME\"' EXIT; trap -p EXIT");
+        let saved = stdout.contents();
+        assert_eq!(saved, "trap -- 'echo \"it'\\''s $HOME\"' EXIT\n");
+        assert_eq!(
+            shell.run_script("trap -p NOSUCHSIGNAL"),
+            ExitStatus::ExitedWith(1)
+        );
+
+        // Save the trap by a command substitution and restore it.
+        shell.run_script("saved=$(trap -p); trap - EXIT");
+        stdout.clear();
+        shell.run_script("trap -p");
+        assert_eq!(stdout.contents(), "");
+        shell.run_script("eval \"$saved\"; trap -p");
+        assert_eq!(stdout.contents(), saved);
+    }
+
+    #[test]
+    fn debug_trap() {
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        shell.run_script("trap 'echo debug' DEBUG");
+        shell.run_script("echo a; x=1; /bin/true | /bin/cat");
+        assert_eq!(stdout.contents(), "debug\na\ndebug\ndebug\ndebug\n");
+    }
+
+    #[test]
+    fn err_trap() {
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        shell.run_script("trap 'echo err $?' ERR");
+        shell.run_script("/bin/true; /bin/false; echo $?");
+        assert_eq!(stdout.contents(), "err 1\n1\n");
+
+        // Failures in conditions and before `&&` or `||` are not errors.
+        stdout.clear();
+        shell.run_script("/bin/false || /bin/true; /bin/false && /bin/true");
+        shell.run_script("if /bin/false; then echo; fi; while /bin/false; do echo; done");
+        assert_eq!(stdout.contents(), "");
+        shell.run_script("/bin/true && /bin/sh -c 'exit 3'");
+        assert_eq!(stdout.contents(), "err 3\n");
+    }
+
+    #[test]
+    fn return_trap() {
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        shell.run_script(
+            "f() { trap 'echo returned $1 $?' RETURN; echo body; return 3; echo unreachable; }",
+        );
+        assert_eq!(shell.run_script("f x"), ExitStatus::ExitedWith(3));
+        assert_eq!(stdout.contents(), "body\nreturned x 3\n");
+        assert_eq!(shell.last_status(), 3);
+
+        // Not run outside of functions.
+        stdout.clear();
+        shell.run_script("echo done");
+        assert_eq!(stdout.contents(), "done\n");
+    }
+}
@@ -0,0 +1,141 @@
+use super::{BuiltinCommand, BuiltinCommandContext};
+use crate::process::{
+    destroy_job, resolve_job_spec, wait_for_any_process, wait_for_job, ExitStatus, Job, JobState,
+    ProcessState,
+};
+use crate::shell::Shell;
+
+use std::rc::Rc;
+
+pub struct Wait;
+
+impl BuiltinCommand for Wait {
+    fn name(&self) -> &'static str {
+        "wait"
+    }
+
+    fn usage(&self) -> &'static str {
+        "wait [-n] [job_spec ...]"
+    }
+
+    fn run(&self, ctx: &mut BuiltinCommandContext) -> anyhow::Result<ExitStatus> {
+        let mut args = ctx.argv.iter().skip(1).peekable();
+        if args.next_if(|arg| *arg == "-n").is_some() {
+            return Ok(ExitStatus::ExitedWith(wait_for_next_job(ctx.shell)));
+        }
+
+        let specs: Vec<&str> = args.map(String::as_str).collect();
+        if specs.is_empty() {
+            let mut jobs: Vec<_> = ctx.shell.jobs().values().cloned().collect();
+            jobs.sort_by_key(|job| job.id());
+            for job in jobs {
+                wait_for_job(ctx.shell, &job);
+            }
+
+            return Ok(ExitStatus::ExitedWith(0));
+        }
+
+        let mut status = 0;
+        for spec in specs {
+            status = match resolve_job_spec(ctx.shell, spec) {
+                Some(job) => job_status(wait_for_job(ctx.shell, &job)),
+                None => {
+                    smash_err!("wait: {}: no such job", spec);
+                    127
+                }
+            };
+        }
+
+        Ok(ExitStatus::ExitedWith(status))
+    }
+}
+
+/// `wait -n`: waits until one of the jobs completes and returns its exit
+/// status, or 127 if there's no job to wait for.
+fn wait_for_next_job(shell: &mut Shell) -> i32 {
+    loop {
+        if let Some(job) = completed_job(shell) {
+            let state = job.state(shell);
+            destroy_job(shell, &job);
+            return match state {
+                JobState::Done(status) => status,
+                _ => 0,
+            };
+        }
+
+        if shell.jobs().is_empty() || wait_for_any_process(shell, false).is_none() {
+            return 127;
+        }
+    }
+}
+
+fn completed_job(shell: &Shell) -> Option<Rc<Job>> {
+    shell
+        .jobs()
+        .values()
+        .filter(|job| job.completed(shell))
+        .min_by_key(|job| job.id())
+        .cloned()
+}
+
+fn job_status(state: ProcessState) -> i32 {
+    match state {
+        ProcessState::Completed(status) => status,
+        ProcessState::Stopped(_) => 148,
+        ProcessState::Running => 0,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::process::ExitStatus;
+    use crate::shell::{Shell, FD_LOCK};
+
+    use nix::sys::signal::{kill, Signal};
+    use nix::sys::wait::{waitpid, WaitStatus};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn wait_for_shorter_job() {
+        let _lock = FD_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let mut shell = Shell::new();
+        shell.run_script("/bin/sleep 5 &");
+        let long_job = shell.current_job().unwrap();
+        shell.run_script("/bin/sleep 0.1 &");
+
+        let started_at = Instant::now();
+        assert_eq!(shell.run_script("wait -n"), ExitStatus::ExitedWith(0));
+        assert!(started_at.elapsed() < Duration::from_secs(3));
+        assert_eq!(shell.jobs().len(), 1);
+        assert!(shell.jobs().contains_key(&long_job.id()));
+
+        kill(long_job.pgid, Signal::SIGKILL).unwrap();
+        assert_eq!(
+            waitpid(long_job.pgid, None),
+            Ok(WaitStatus::Signaled(long_job.pgid, Signal::SIGKILL, false))
+        );
+    }
+
+    #[test]
+    fn exit_status_of_waited_job() {
+        let mut shell = Shell::with_io(std::io::empty(), std::io::sink(), std::io::sink());
+        shell.run_script("/bin/sh -c 'exit 3' &");
+        assert_eq!(shell.run_script("wait %1"), ExitStatus::ExitedWith(3));
+        assert_eq!(shell.last_status(), 3);
+        assert!(shell.jobs().is_empty());
+
+        assert_eq!(shell.run_script("wait %9"), ExitStatus::ExitedWith(127));
+        assert_eq!(shell.last_status(), 127);
+
+        // Killed by SIGTERM.
+        shell.run_script("/bin/sh -c 'kill $$' &");
+        assert_eq!(
+            shell.run_script("wait %1"),
+            ExitStatus::ExitedWith(128 + 15)
+        );
+
+        shell.run_script("/bin/sh -c 'exit 4' &");
+        assert_eq!(shell.run_script("wait"), ExitStatus::ExitedWith(0));
+        assert!(shell.jobs().is_empty());
+    }
+}
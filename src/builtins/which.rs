@@ -0,0 +1,91 @@
+use super::{BuiltinCommand, BuiltinCommandContext};
+use crate::process::ExitStatus;
+
+pub struct Which;
+
+impl BuiltinCommand for Which {
+    fn name(&self) -> &'static str {
+        "which"
+    }
+
+    fn usage(&self) -> &'static str {
+        "which [-a] name ..."
+    }
+
+    fn run(&self, ctx: &mut BuiltinCommandContext) -> anyhow::Result<ExitStatus> {
+        let mut names = &ctx.argv[1..];
+        let all = names.first().map(String::as_str) == Some("-a");
+        if all {
+            names = &names[1..];
+        }
+
+        let mut status = 0;
+        for name in names {
+            let paths = ctx.shell.path_table().search(name);
+            if paths.is_empty() {
+                status = 1;
+                continue;
+            }
+
+            let n = if all { paths.len() } else { 1 };
+            for path in &paths[..n] {
+                writeln!(ctx.stdout(), "{}", path).ok();
+            }
+        }
+
+        Ok(ExitStatus::ExitedWith(status))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::process::ExitStatus;
+    use crate::shell::{SharedBuffer, Shell};
+
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::PathBuf;
+
+    /// Creates a directory containing an executable named `cmd`.
+    fn bin_dir(name: &str, cmd: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("smash-which-{}-{}", std::process::id(), name));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(cmd);
+        std::fs::write(&path, "#!/bin/sh\n").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        dir
+    }
+
+    #[test]
+    fn which() {
+        let dir1 = bin_dir("1", "smash-which-test");
+        let dir2 = bin_dir("2", "smash-which-test");
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        shell.run_script(&format!("PATH={}:{}", dir1.display(), dir2.display()));
+
+        assert_eq!(
+            shell.run_script("which smash-which-test"),
+            ExitStatus::ExitedWith(0)
+        );
+        assert_eq!(
+            shell.run_script("which -a smash-which-test"),
+            ExitStatus::ExitedWith(0)
+        );
+        assert_eq!(
+            stdout.contents(),
+            format!(
+                "{0}/smash-which-test\n{0}/smash-which-test\n{1}/smash-which-test\n",
+                dir1.display(),
+                dir2.display()
+            )
+        );
+
+        assert_eq!(
+            shell.run_script("which smash-which-test no-such-command"),
+            ExitStatus::ExitedWith(1)
+        );
+
+        std::fs::remove_dir_all(dir1).ok();
+        std::fs::remove_dir_all(dir2).ok();
+    }
+}
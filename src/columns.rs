@@ -0,0 +1,70 @@
+//! Lists laid out in columns like `ls`.
+
+/// The gap between columns.
+const GAP: usize = 2;
+
+/// Formats `items` into lines, filling columns from top to bottom with as
+/// many columns as fit in `width`.
+pub fn format_columns(items: &[String], width: usize) -> Vec<String> {
+    let lens: Vec<usize> = items.iter().map(|item| item.chars().count()).collect();
+    let mut rows = 1;
+    let widths = loop {
+        let widths: Vec<usize> = lens
+            .chunks(rows)
+            .map(|column| column.iter().copied().max().unwrap_or(0))
+            .collect();
+        let total = widths.iter().sum::<usize>() + GAP * widths.len().saturating_sub(1);
+        if total <= width || rows >= items.len() {
+            break widths;
+        }
+
+        rows += 1;
+    };
+
+    (0..rows.min(items.len()))
+        .map(|row| {
+            let mut line = String::new();
+            for (col, col_width) in widths.iter().enumerate() {
+                let item = match items.get(col * rows + row) {
+                    Some(item) => item,
+                    None => break,
+                };
+
+                if !line.is_empty() {
+                    line.push_str(&" ".repeat(GAP));
+                }
+
+                line.push_str(item);
+                line.push_str(&" ".repeat(col_width - lens[col * rows + row]));
+            }
+
+            line.truncate(line.trim_end().len());
+            line
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn strings(items: &[&str]) -> Vec<String> {
+        items.iter().map(|item| item.to_string()).collect()
+    }
+
+    #[test]
+    fn pack_columns() {
+        let items = strings(&["a", "bbb", "cc", "dddd", "e"]);
+        assert_eq!(format_columns(&items, 80), vec!["a  bbb  cc  dddd  e"]);
+        assert_eq!(
+            format_columns(&items, 12),
+            vec!["a    cc    e", "bbb  dddd"]
+        );
+        assert_eq!(format_columns(&items, 9), vec!["a    dddd", "bbb  e", "cc"]);
+        assert_eq!(
+            format_columns(&items, 1),
+            strings(&["a", "bbb", "cc", "dddd", "e"])
+        );
+        assert!(format_columns(&[], 80).is_empty());
+    }
+}
@@ -0,0 +1,181 @@
+//! Tab completion. The first word completes to a command name and the rest
+//! to file names unless a function is registered by `complete -F`.
+use crate::builtins::builtin_names;
+use crate::eval::run_function;
+use crate::glob::{escape, glob};
+use crate::shell::Shell;
+use crate::variable::Value;
+
+use std::path::Path;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Completion {
+    /// The byte offset of the word being completed.
+    pub start: usize,
+    /// Sorted and deduplicated.
+    pub candidates: Vec<String>,
+}
+
+impl Completion {
+    /// The longest prefix shared by all candidates.
+    pub fn common_prefix(&self) -> &str {
+        let first = match self.candidates.first() {
+            Some(first) => first.as_str(),
+            None => return "",
+        };
+
+        let mut prefix = first;
+        for candidate in &self.candidates[1..] {
+            while !candidate.starts_with(prefix) {
+                let mut chars = prefix.chars();
+                chars.next_back();
+                prefix = chars.as_str();
+            }
+        }
+
+        prefix
+    }
+}
+
+/// Returns completions for the word before `cursor` (a byte offset).
+pub fn complete(shell: &mut Shell, line: &str, cursor: usize) -> Completion {
+    let start = line[..cursor]
+        .rfind(char::is_whitespace)
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    let end = line[cursor..]
+        .find(char::is_whitespace)
+        .map(|i| cursor + i)
+        .unwrap_or(line.len());
+    let current = &line[start..cursor];
+
+    // Words in the current simple command.
+    let command_start = line[..start]
+        .rfind([';', '|', '&', '(', '\n'])
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    let mut words: Vec<String> = line[command_start..start]
+        .split_whitespace()
+        .map(str::to_owned)
+        .collect();
+    let cword = words.len();
+    words.push(line[start..end].to_owned());
+    words.extend(line[end..].split_whitespace().map(str::to_owned));
+
+    let mut candidates = if cword == 0 && !current.contains('/') {
+        complete_command(shell, current)
+    } else {
+        match shell.completion(&words[0]).map(str::to_owned) {
+            Some(function) => {
+                let prev = if cword > 0 { &words[cword - 1] } else { "" };
+                let argv = vec![
+                    function.clone(),
+                    words[0].clone(),
+                    current.to_owned(),
+                    prev.to_owned(),
+                ];
+                call_completion_function(shell, &function, &argv, words, cword)
+            }
+            None => complete_file(shell, current),
+        }
+    };
+
+    candidates.sort();
+    candidates.dedup();
+    Completion { start, candidates }
+}
+
+fn complete_command(shell: &Shell, prefix: &str) -> Vec<String> {
+    builtin_names()
+        .iter()
+        .map(|name| name.to_string())
+        .chain(shell.function_names().map(str::to_owned))
+        .chain(shell.path_table().to_vec())
+        .filter(|name| name.starts_with(prefix))
+        .collect()
+}
+
+/// Directories end with `/`.
+fn complete_file(shell: &Shell, prefix: &str) -> Vec<String> {
+    let pattern = format!("{}*", escape(prefix));
+    glob(&pattern, &shell.glob_options())
+        .into_iter()
+        .map(|path| {
+            if Path::new(&path).is_dir() && !path.ends_with('/') {
+                format!("{}/", path)
+            } else {
+                path
+            }
+        })
+        .collect()
+}
+
+/// Calls a function registered by `complete -F` with `$COMP_WORDS` and
+/// `$COMP_CWORD` set, and returns `$COMPREPLY`.
+fn call_completion_function(
+    shell: &mut Shell,
+    function: &str,
+    argv: &[String],
+    words: Vec<String>,
+    cword: usize,
+) -> Vec<String> {
+    shell.set("COMP_WORDS", Value::Array(words), false);
+    shell.set("COMP_CWORD", Value::String(cword.to_string()), false);
+    shell.unset("COMPREPLY");
+    run_function(shell, function, argv);
+
+    let reply = match shell.get("COMPREPLY").and_then(|var| var.value().clone()) {
+        Some(Value::Array(elems)) => elems,
+        Some(Value::String(value)) => vec![value],
+        None => Vec::new(),
+    };
+
+    shell.unset("COMP_WORDS");
+    shell.unset("COMP_CWORD");
+    shell.unset("COMPREPLY");
+    reply
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::shell::FD_LOCK;
+
+    fn candidates(shell: &mut Shell, line: &str) -> Vec<String> {
+        complete(shell, line, line.len()).candidates
+    }
+
+    #[test]
+    fn complete_commands_and_files() {
+        let _lock = FD_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let mut shell = Shell::new();
+        shell.run_script("greet() { echo hello; }");
+        assert_eq!(candidates(&mut shell, "ech"), vec!["echo"]);
+        assert_eq!(candidates(&mut shell, "gre"), vec!["greet"]);
+        assert_eq!(candidates(&mut shell, "ls /bi"), vec!["/bin/"]);
+        assert_eq!(candidates(&mut shell, "true; ech"), vec!["echo"]);
+        assert_eq!(complete(&mut shell, "ls /bi", 6).start, 3);
+    }
+
+    #[test]
+    fn completion_function() {
+        let _lock = FD_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let mut shell = Shell::new();
+        shell.run_script("_words() { COMPREPLY=(${COMP_WORDS[2]}x ${2}y ${3}z $1 $COMP_CWORD); }");
+        shell.run_script("complete -F _words mycmd");
+        assert_eq!(
+            candidates(&mut shell, "mycmd first a"),
+            vec!["2", "ax", "ay", "firstz", "mycmd"]
+        );
+        assert!(shell.get("COMPREPLY").is_none());
+    }
+
+    #[test]
+    fn common_prefix() {
+        let completion = Completion {
+            start: 0,
+            candidates: vec!["foobar".to_owned(), "foobaz".to_owned(), "foo".to_owned()],
+        };
+        assert_eq!(completion.common_prefix(), "foo");
+    }
+}
@@ -1,38 +1,131 @@
-use crate::builtins::BuiltinCommandError;
-use crate::expand::expand_words;
-use crate::parser::{self, Ast, RunIf, Term};
+use crate::builtins::{builtin_command, BuiltinCommandError};
+use crate::expand::{eval_expr, expand_word_into_string, expand_words, UnboundVariable};
+use crate::parser::{
+    self, Ast, Index, Initializer, RedirectionDirection, RedirectionTarget, RunIf, Span, Term,
+};
 use crate::process::{
-    run_external_command, run_in_foreground, run_internal_command, wait_for_job, Context,
-    ExitStatus, ProcessState,
+    apply_redirections, exit_shell, join_job, run_external_command, run_in_foreground,
+    run_internal_command, wait_for_job, wait_for_process, with_redirections, Context, ExitStatus,
+    ProcessState, Redirection,
 };
-use crate::shell::Shell;
+use crate::quote::shell_quote;
+use crate::shell::{Function, LoopControl, Shell};
+use crate::variable::{is_valid_name, Value};
 
-use nix::unistd::{close, pipe, setpgid};
+use nix::fcntl::OFlag;
+use nix::sys::resource::{getrusage, UsageWho};
+use nix::sys::signal::Signal;
+use nix::sys::time::{TimeVal, TimeValLike};
+use nix::unistd::{close, dup2, fork, pipe2, setpgid, ForkResult, Pid};
+use std::collections::HashSet;
+use std::io::Write;
+use std::os::unix::io::RawFd;
+use std::path::Path;
+use std::time::{Duration, Instant};
 use tracing::debug;
 
 pub fn eval(shell: &mut Shell, ast: &Ast) -> ExitStatus {
     debug!("ast: {:#?}", ast);
+    shell.sync_pwd();
     run_terms(shell, &ast.terms)
 }
 
 pub fn run_terms(shell: &mut Shell, terms: &[Term]) -> ExitStatus {
     let mut last_status = ExitStatus::ExitedWith(0);
     for term in terms {
-        for pipeline in &term.pipelines {
+        shell.set_lineno(term.line);
+        for (i, pipeline) in term.pipelines.iter().enumerate() {
             match (last_status, &pipeline.run_if) {
                 (ExitStatus::ExitedWith(0), RunIf::Success) => (),
-                (ExitStatus::ExitedWith(_), RunIf::Failure) => (),
+                (ExitStatus::ExitedWith(status), RunIf::Failure) if status != 0 => (),
                 (_, RunIf::Always) => (),
                 _ => continue,
             }
 
+            let started_at = Instant::now();
+            let cpu_times_before = cpu_times();
             last_status = run_pipeline(shell, &term.code, pipeline, term.background);
+            shell.finish_process_substitutions(!term.background);
+            if pipeline.timed {
+                report_times(shell, started_at.elapsed(), cpu_times_before);
+            }
+
+            // A failure is not an error in a condition nor before `&&` or
+            // `||`.
+            let followed_by_list = term
+                .pipelines
+                .get(i + 1)
+                .is_some_and(|next| next.run_if != RunIf::Always);
+            if !matches!(
+                last_status,
+                ExitStatus::ExitedWith(0) | ExitStatus::Running(_)
+            ) && !followed_by_list
+                && !shell.in_condition()
+            {
+                shell.run_trap("ERR");
+                if shell.is_option_set("errexit") {
+                    if let ExitStatus::ExitedWith(status) = last_status {
+                        exit_shell(shell, status);
+                    }
+                }
+            }
+
+            if shell.interrupted() {
+                return last_status;
+            }
         }
     }
 
     last_status
 }
 
+/// Runs the condition of `if`, `elif`, or `while`. Returns whether it
+/// succeeded.
+fn run_condition(shell: &mut Shell, condition: &[Term]) -> bool {
+    shell.enter_condition();
+    let status = run_terms(shell, condition);
+    shell.leave_condition();
+    status == ExitStatus::ExitedWith(0)
+}
+
+/// The user and system CPU time consumed by the shell and its waited children.
+fn cpu_times() -> (Duration, Duration) {
+    let to_duration = |time: TimeVal| Duration::from_micros(time.num_microseconds() as u64);
+    let mut user = Duration::ZERO;
+    let mut sys = Duration::ZERO;
+    for who in [UsageWho::RUSAGE_SELF, UsageWho::RUSAGE_CHILDREN] {
+        if let Ok(usage) = getrusage(who) {
+            user += to_duration(usage.user_time());
+            sys += to_duration(usage.system_time());
+        }
+    }
+
+    (user, sys)
+}
+
+/// Prints the times taken by a pipeline prefixed with `time` in the same
+/// format as bash.
+fn report_times(shell: &mut Shell, real: Duration, cpu_times_before: (Duration, Duration)) {
+    let (user, sys) = cpu_times();
+    let format = |time: Duration| {
+        format!(
+            "{}m{}.{:03}s",
+            time.as_secs() / 60,
+            time.as_secs() % 60,
+            time.subsec_millis()
+        )
+    };
+
+    writeln!(
+        shell.stderr(),
+        "\nreal\t{}\nuser\t{}\nsys\t{}",
+        format(real),
+        format(user.saturating_sub(cpu_times_before.0)),
+        format(sys.saturating_sub(cpu_times_before.1))
+    )
+    .ok();
+}
+
 fn run_pipeline(
     shell: &mut Shell,
     code: &str,
@@ -40,37 +133,56 @@ fn run_pipeline(
     background: bool,
 ) -> ExitStatus {
     // Invoke commands in a pipeline.
-    let mut last_result = None;
+    let mut results = Vec::new();
     let mut iter = pipeline.commands.iter().peekable();
     let mut childs = Vec::new();
     let mut pgid = None;
+    let mut stdin = 0;
     while let Some(command) = iter.next() {
         let pipes = if iter.peek().is_some() {
             // There is a next command in the pipeline (e.g. date in
             // `date | hexdump`). Create and connect a pipe.
-            let (pipe_out, pipe_in) = pipe().expect("failed to create a pipe");
+            let (pipe_out, pipe_in) = pipe2(OFlag::O_CLOEXEC).expect("failed to create a pipe");
             Some((pipe_out, pipe_in))
         } else {
             // The last command in the pipeline.
             None
         };
 
-        let result = run_command(
-            shell,
-            command,
-            &Context {
-                pgid,
-                background,
-                interactive: shell.interactive(),
-            },
-        );
+        let ctx = Context {
+            pgid,
+            background,
+            interactive: shell.interactive(),
+            nohup: false,
+            stdin,
+            stdout: pipes.map(|(_, pipe_in)| pipe_in).unwrap_or(1),
+            pipe_out: pipes.map(|(pipe_out, _)| pipe_out),
+        };
 
-        if let Some((_, pipe_in)) = pipes {
-            // `pipe_in` is used by a child process and is no longer needed.
-            close(pipe_in).expect("failed to close pipe_in");
+        let in_subshell = matches!(command, parser::Command::Subshell { .. })
+            || (pipeline.commands.len() > 1 && is_compound(command));
+        let result = if in_subshell {
+            // Like other shells, a compound command in a pipeline runs in a
+            // subshell connected to the pipes.
+            run_in_subshell(shell, command, &ctx)
+        } else {
+            run_command(shell, command, &ctx)
+        };
+
+        // The pipe ends given to the command are no longer needed.
+        if stdin != 0 {
+            close(stdin).expect("failed to close pipe_out");
         }
 
-        last_result = match result {
+        stdin = match pipes {
+            Some((pipe_out, pipe_in)) => {
+                close(pipe_in).expect("failed to close pipe_in");
+                pipe_out
+            }
+            None => 0,
+        };
+
+        match result {
             Ok(ExitStatus::Running(pid)) => {
                 if pgid.is_none() {
                     // The first child (the process group leader) pid is used for pgid.
@@ -82,77 +194,585 @@ fn run_pipeline(
                 }
 
                 childs.push(pid);
-                Some(ExitStatus::Running(pid))
+                results.push(ExitStatus::Running(pid));
             }
-            Ok(ExitStatus::ExitedWith(status)) => Some(ExitStatus::ExitedWith(status)),
+            Ok(ExitStatus::ExitedWith(status)) => results.push(ExitStatus::ExitedWith(status)),
             Err(err) => {
-                unimplemented!("error: {}", err);
+                smash_err!("{}", err);
+                if err.is::<UnboundVariable>() && !shell.interactive() {
+                    exit_shell(shell, 1);
+                }
+
+                results.push(ExitStatus::ExitedWith(1));
             }
-        };
+        }
+    }
+
+    if results.is_empty() {
+        debug!("nothing to execute");
+        return ExitStatus::ExitedWith(0);
+    }
+
+    if childs.is_empty() {
+        // All commands are builtins and have already finished.
+        let status = set_pipe_status(shell, &results);
+        shell.set_last_status(status);
+        return ExitStatus::ExitedWith(status);
+    }
+
+    // Wait for the child processes in the pipeline.
+    let cmd_name = code.to_owned();
+    let job = shell.create_job(cmd_name, pgid.unwrap(), childs);
+
+    if background {
+        if shell.interactive {
+            eprintln!("[{}] {}", job.id(), job.pgid);
+        }
+
+        shell.set_last_status(0);
+        return ExitStatus::ExitedWith(0);
+    }
+
+    let state = if shell.interactive {
+        run_in_foreground(shell, &job)
+    } else {
+        wait_for_job(shell, &job)
+    };
+
+    let state = match state {
+        // The last command may be a builtin.
+        ProcessState::Completed(_) => ProcessState::Completed(set_pipe_status(shell, &results)),
+        state => state,
+    };
+
+    foreground_job_status(shell, state, pgid.unwrap())
+}
+
+/// Sets `PIPESTATUS` to the exit status of each command in a finished
+/// pipeline and returns the last one.
+fn set_pipe_status(shell: &mut Shell, results: &[ExitStatus]) -> i32 {
+    let statuses: Vec<i32> = results
+        .iter()
+        .map(|result| match result {
+            ExitStatus::ExitedWith(status) => *status,
+            ExitStatus::Running(pid) => match shell.get_process_state(*pid) {
+                Some(ProcessState::Completed(status)) => *status,
+                _ => 0,
+            },
+        })
+        .collect();
+
+    let value = Value::Array(statuses.iter().map(|status| status.to_string()).collect());
+    shell.set("PIPESTATUS", value, false);
+    // `set -o pipefail`: the last command which failed.
+    if shell.is_option_set("pipefail") {
+        return statuses
+            .iter()
+            .rev()
+            .find(|status| **status != 0)
+            .copied()
+            .unwrap_or(0);
     }
 
-    // Wait for the last command in the pipeline.
-    match last_result {
-        Some(ExitStatus::ExitedWith(status)) => {
+    *statuses.last().unwrap()
+}
+
+/// Updates `$?` from the state a foreground job left the wait in.
+pub fn foreground_job_status(shell: &mut Shell, state: ProcessState, pgid: Pid) -> ExitStatus {
+    match state {
+        ProcessState::Completed(status) => {
             shell.set_last_status(status);
             ExitStatus::ExitedWith(status)
         }
-        Some(ExitStatus::Running(_)) => {
-            let cmd_name = code.to_owned();
-            let job = shell.create_job(cmd_name, pgid.unwrap(), childs);
+        ProcessState::Stopped(_) => {
+            // 128 + SIGTSTP, as other shells report a suspended job.
+            shell.set_last_status(128 + Signal::SIGTSTP as i32);
+            ExitStatus::Running(pgid)
+        }
+        _ => unreachable!(),
+    }
+}
 
-            if !shell.interactive {
-                match wait_for_job(shell, &job) {
-                    ProcessState::Completed(status) => {
-                        shell.set_last_status(status);
-                        ExitStatus::ExitedWith(status)
-                    }
-                    ProcessState::Stopped(_) => ExitStatus::Running(pgid.unwrap()),
-                    _ => unreachable!(),
+fn run_command(
+    shell: &mut Shell,
+    command: &parser::Command,
+    ctx: &Context,
+) -> anyhow::Result<ExitStatus> {
+    debug!("run_command: {:?}", command);
+    let result = match command {
+        parser::Command::SimpleCommand {
+            argv,
+            redirects,
+            assignments,
+        } => {
+            shell.run_trap("DEBUG");
+            run_simple_command(ctx, shell, argv, redirects, assignments)?
+        }
+        parser::Command::Assignment { assignments } => {
+            shell.run_trap("DEBUG");
+            let assignments = expand_assignments(shell, assignments)?;
+            if shell.is_option_set("xtrace") {
+                trace_command(shell, &assignments, &[]);
+            }
+
+            for (name, value) in assignments {
+                shell.set(&name, value, false);
+            }
+
+            ExitStatus::ExitedWith(0)
+        }
+        parser::Command::If {
+            condition,
+            then_part,
+            elif_parts,
+            else_part,
+        } => {
+            if run_condition(shell, condition) {
+                return Ok(run_terms(shell, then_part));
+            }
+
+            for elif in elif_parts {
+                if run_condition(shell, &elif.condition) {
+                    return Ok(run_terms(shell, &elif.then_part));
                 }
-            } else {
-                match run_in_foreground(shell, &job) {
-                    ProcessState::Completed(status) => ExitStatus::ExitedWith(status),
-                    ProcessState::Stopped(_) => ExitStatus::Running(pgid.unwrap()),
-                    _ => unreachable!(),
+            }
+
+            match else_part {
+                Some(else_part) => run_terms(shell, else_part),
+                None => ExitStatus::ExitedWith(0),
+            }
+        }
+        parser::Command::While { condition, body } => {
+            let mut last_status = ExitStatus::ExitedWith(0);
+            shell.enter_loop();
+            while run_condition(shell, condition) {
+                last_status = run_terms(shell, body);
+                if loop_finished(shell) {
+                    break;
                 }
             }
+
+            shell.leave_loop();
+            last_status
+        }
+        parser::Command::Select { name, words, body } => run_select(shell, name, words, body)?,
+        parser::Command::Coproc { name, body, code } => {
+            let name = name.as_deref().unwrap_or("COPROC");
+            run_coproc(shell, name, body, code)?
         }
-        None => {
-            debug!("nothing to execute");
+        parser::Command::Group { terms } => run_terms(shell, terms),
+        // Only as a function body: pipelines fork subshells by themselves.
+        parser::Command::Subshell { .. } => run_in_subshell(shell, command, ctx)?,
+        parser::Command::FunctionDefinition { name, body, code } => {
+            shell.define_function(name, (**body).clone(), code.clone());
+            ExitStatus::ExitedWith(0)
+        }
+        parser::Command::Return { status } => {
+            if !shell.in_function() {
+                smash_err!("return: can only `return' from a function");
+                return Ok(ExitStatus::ExitedWith(1));
+            }
+
+            shell.set_returning();
+            ExitStatus::ExitedWith(status.unwrap_or_else(|| shell.last_status()))
+        }
+        parser::Command::Break | parser::Command::Continue => {
+            if !shell.in_loop() {
+                smash_err!("break: only meaningful in a loop");
+                return Ok(ExitStatus::ExitedWith(0));
+            }
+
+            shell.set_loop_control(Some(if *command == parser::Command::Break {
+                LoopControl::Break
+            } else {
+                LoopControl::Continue
+            }));
             ExitStatus::ExitedWith(0)
         }
+    };
+
+    Ok(result)
+}
+
+/// Starts `body` as a background job with its stdin and stdout connected to
+/// pipes. The other ends are set to `${name[1]}` and `${name[0]}`.
+fn run_coproc(
+    shell: &mut Shell,
+    name: &str,
+    body: &parser::Command,
+    code: &str,
+) -> anyhow::Result<ExitStatus> {
+    let (child_stdin, shell_write) = pipe2(OFlag::O_CLOEXEC)?;
+    let (shell_read, child_stdout) = pipe2(OFlag::O_CLOEXEC)?;
+    shell.stdout().flush().ok();
+    match unsafe { fork() }? {
+        ForkResult::Child => {
+            if shell.interactive() {
+                setpgid(Pid::from_raw(0), Pid::from_raw(0)).ok();
+            }
+
+            close(shell_write).ok();
+            close(shell_read).ok();
+            dup2(child_stdin, 0).ok();
+            dup2(child_stdout, 1).ok();
+            close(child_stdin).ok();
+            close(child_stdout).ok();
+            exit_with_subshell(shell, body);
+        }
+        ForkResult::Parent { child } => {
+            if shell.interactive() {
+                setpgid(child, child).ok();
+            }
+
+            close(child_stdin).ok();
+            close(child_stdout).ok();
+            let fds = vec![shell_read.to_string(), shell_write.to_string()];
+            shell.set(name, Value::Array(fds), false);
+            shell.set(
+                &format!("{}_PID", name),
+                Value::String(child.to_string()),
+                false,
+            );
+
+            let job = shell.create_job(format!("coproc {}", code), child, vec![child]);
+            if shell.interactive() {
+                eprintln!("[{}] {}", job.id(), job.pgid);
+            }
+
+            Ok(ExitStatus::ExitedWith(0))
+        }
     }
 }
 
-fn run_command(
+fn is_compound(command: &parser::Command) -> bool {
+    matches!(
+        command,
+        parser::Command::If { .. }
+            | parser::Command::While { .. }
+            | parser::Command::Select { .. }
+            | parser::Command::Group { .. }
+            | parser::Command::Subshell { .. }
+    )
+}
+
+/// Forks a child which runs `command` as a subshell with the stdin and
+/// stdout of `ctx` as a process of the job.
+fn run_in_subshell(
     shell: &mut Shell,
     command: &parser::Command,
     ctx: &Context,
 ) -> anyhow::Result<ExitStatus> {
-    debug!("run_command: {:?}", command);
-    let result = match command {
-        parser::Command::SimpleCommand { argv } => run_simple_command(ctx, shell, argv)?,
+    fork_into_job(shell, ctx, |shell, ctx| {
+        run_subshell_command(shell, command, ctx)
+    })
+}
+
+/// Forks a child which runs `f` in a subshell with the stdin and stdout of
+/// `ctx` as a process of the job.
+fn fork_into_job<F>(shell: &mut Shell, ctx: &Context, f: F) -> anyhow::Result<ExitStatus>
+where
+    F: FnOnce(&mut Shell, &Context) -> anyhow::Result<ExitStatus>,
+{
+    // Don't let the child print what we have buffered.
+    shell.stdout().flush().ok();
+    std::io::stdout().flush().ok();
+    match unsafe { fork() }? {
+        ForkResult::Child => {
+            join_job(shell, ctx);
+            // The reader must see EOF when the child exits, and the child
+            // must get EPIPE when the reader exits.
+            if let Some(fd) = ctx.pipe_out {
+                close(fd).ok();
+            }
+
+            if ctx.stdin != 0 {
+                dup2(ctx.stdin, 0).ok();
+                close(ctx.stdin).ok();
+            }
+
+            if ctx.stdout != 1 {
+                dup2(ctx.stdout, 1).ok();
+                close(ctx.stdout).ok();
+            }
+
+            exit_in_subshell(shell, f);
+        }
+        ForkResult::Parent { child } => Ok(ExitStatus::Running(child)),
+    }
+}
+
+/// Runs `command` in a forked child as a subshell and exits with its status.
+fn exit_with_subshell(shell: &mut Shell, command: &parser::Command) -> ! {
+    exit_in_subshell(shell, |shell, ctx| {
+        run_subshell_command(shell, command, ctx)
+    })
+}
+
+/// Runs `f` in a forked child as a subshell and exits with its status.
+fn exit_in_subshell<F>(shell: &mut Shell, f: F) -> !
+where
+    F: FnOnce(&mut Shell, &Context) -> anyhow::Result<ExitStatus>,
+{
+    shell.enter_subshell();
+    let ctx = Context {
+        pgid: None,
+        background: false,
+        interactive: false,
+        nohup: false,
+        stdin: 0,
+        stdout: 1,
+        pipe_out: None,
     };
 
-    Ok(result)
+    let status = match f(shell, &ctx) {
+        Ok(ExitStatus::ExitedWith(status)) => status,
+        // An external command run by a builtin (e.g. `command ls`).
+        Ok(ExitStatus::Running(pid)) => {
+            wait_for_process(shell, pid, false);
+            match shell.get_process_state(pid) {
+                Some(ProcessState::Completed(status)) => *status,
+                _ => 0,
+            }
+        }
+        Err(err) => {
+            smash_err!("{}", err);
+            1
+        }
+    };
+
+    shell.stdout().flush().ok();
+    std::process::exit(status);
+}
+
+/// Runs `command` in a subshell: the terms of `( ... )` are run directly
+/// instead of forking again.
+fn run_subshell_command(
+    shell: &mut Shell,
+    command: &parser::Command,
+    ctx: &Context,
+) -> anyhow::Result<ExitStatus> {
+    match command {
+        parser::Command::Subshell { terms } => Ok(run_terms(shell, terms)),
+        _ => run_command(shell, command, ctx),
+    }
+}
+
+/// Called after each iteration of a loop. Returns `true` if the loop should
+/// stop by `break` or `return`.
+fn loop_finished(shell: &mut Shell) -> bool {
+    if shell.returning()
+        || shell.aborting_functions()
+        || shell.loop_control() == Some(LoopControl::Break)
+    {
+        return true;
+    }
+
+    shell.set_loop_control(None);
+    false
+}
+
+/// Prints a numbered menu of `words` to stderr and runs `body` with `name`
+/// set to the selected word and `$REPLY` to the input until EOF.
+fn run_select(
+    shell: &mut Shell,
+    name: &str,
+    words: &Option<Vec<parser::Word>>,
+    body: &[Term],
+) -> anyhow::Result<ExitStatus> {
+    let words = match words {
+        Some(words) => expand_words(shell, words)?,
+        None => shell.args().to_vec(),
+    };
+
+    let mut last_status = ExitStatus::ExitedWith(0);
+    if words.is_empty() {
+        return Ok(last_status);
+    }
+
+    shell.enter_loop();
+    let mut show_menu = true;
+    loop {
+        if show_menu {
+            for (i, word) in words.iter().enumerate() {
+                writeln!(shell.stderr(), "{}) {}", i + 1, word).ok();
+            }
+        }
+
+        let prompt = match shell.get("PS3") {
+            Some(var) => var.as_str().to_owned(),
+            None => "#? ".to_owned(),
+        };
+        write!(shell.stderr(), "{}", prompt).ok();
+        shell.stderr().flush().ok();
+
+        let reply = match read_reply(shell) {
+            Some(reply) => reply,
+            None => {
+                writeln!(shell.stderr()).ok();
+                break;
+            }
+        };
+
+        // An empty line shows the menu again.
+        show_menu = reply.is_empty();
+        if show_menu {
+            continue;
+        }
+
+        let selected = reply
+            .trim()
+            .parse::<usize>()
+            .ok()
+            .and_then(|n| n.checked_sub(1))
+            .and_then(|i| words.get(i))
+            .cloned()
+            .unwrap_or_default();
+        shell.set("REPLY", Value::String(reply), false);
+        shell.set(name, Value::String(selected), false);
+        last_status = run_terms(shell, body);
+        if loop_finished(shell) {
+            break;
+        }
+    }
+
+    shell.leave_loop();
+    Ok(last_status)
+}
+
+/// Reads a line from the shell's stdin byte by byte. Returns `None` on EOF.
+fn read_reply(shell: &mut Shell) -> Option<String> {
+    let mut line = Vec::new();
+    let mut byte = [0; 1];
+    loop {
+        match shell.stdin().read(&mut byte) {
+            Ok(1) if byte[0] == b'\n' => break,
+            Ok(1) => line.push(byte[0]),
+            _ if line.is_empty() => return None,
+            _ => break,
+        }
+    }
+
+    Some(String::from_utf8_lossy(&line).into_owned())
 }
 
 fn run_simple_command(
     ctx: &Context,
     shell: &mut Shell,
     argv: &[parser::Word],
+    redirects: &[parser::Redirection],
+    assignments: &[parser::Assignment],
 ) -> anyhow::Result<ExitStatus> {
     debug!("run_simple_command");
-    let argv = expand_words(shell, argv)?;
+    let argv = expand_aliases(shell, argv);
+    let argv = expand_words(shell, &argv)?;
+    let assignments = expand_assignments(shell, assignments)?;
+    if shell.is_option_set("xtrace") {
+        trace_command(shell, &assignments, &argv);
+    }
+
     if argv.is_empty() {
+        // Nothing left to run (e.g. `a=b $empty`): assign to the shell.
+        for (name, value) in assignments {
+            shell.set(&name, value, false);
+        }
+
         return Ok(ExitStatus::ExitedWith(0));
     }
 
-    // TODO: support functions
+    // A builtin or a function runs in the shell process: writing to the pipe
+    // would block the shell before the next command is started to read it.
+    // Run it in a child like other commands.
+    if ctx.pipe_out.is_some() && runs_in_shell(shell, &argv[0]) {
+        return fork_into_job(shell, ctx, |shell, ctx| {
+            run_expanded_command(ctx, shell, argv, redirects, assignments)
+        });
+    }
+
+    run_expanded_command(ctx, shell, argv, redirects, assignments)
+}
+
+/// Returns whether the command `name` is run in the shell process: a
+/// function, a builtin, or a command not found (e.g. `autocd`).
+fn runs_in_shell(shell: &Shell, name: &str) -> bool {
+    let is_path = name.starts_with('/') || name.starts_with("./");
+    shell.get_function(name).is_some()
+        || builtin_command(name).is_some()
+        || (!is_path && shell.path_table().lookup(name).is_none())
+}
+
+fn run_expanded_command(
+    ctx: &Context,
+    shell: &mut Shell,
+    argv: Vec<String>,
+    redirects: &[parser::Redirection],
+    assignments: Vec<(String, Value)>,
+) -> anyhow::Result<ExitStatus> {
+    // Connect the pipeline first so that redirections can override it.
+    let mut pipe_redirects = Vec::new();
+    if ctx.stdin != 0 {
+        pipe_redirects.push(Redirection::Fd {
+            fd: 0,
+            target_fd: ctx.stdin,
+        });
+    }
+
+    if ctx.stdout != 1 {
+        pipe_redirects.push(Redirection::Fd {
+            fd: 1,
+            target_fd: ctx.stdout,
+        });
+    }
+
+    let redirects = match expand_redirections(shell, redirects) {
+        Ok(redirects) => [pipe_redirects, redirects].concat(),
+        Err(err) => {
+            smash_err!("{}", err);
+            return Ok(ExitStatus::ExitedWith(1));
+        }
+    };
+
+    // `exec` without a command: the redirections stay in effect in the shell
+    // and are inherited by commands run later.
+    if argv[0] == "exec" && argv.len() == 1 {
+        shell.stdout().flush().ok();
+        std::io::stderr().flush().ok();
+        if let Err(err) = apply_redirections(&redirects) {
+            smash_err!("{}", err);
+            return Ok(ExitStatus::ExitedWith(1));
+        }
+
+        return Ok(ExitStatus::ExitedWith(0));
+    }
+
+    // Functions and internal commands: assignments are visible (and
+    // exported to commands run from them) only during the command.
+    let saved_vars: Vec<_> = assignments
+        .iter()
+        .map(|(name, _)| {
+            let var = shell.get(name);
+            let value = var.as_ref().and_then(|var| var.value().clone());
+            (name, value, var.is_some_and(|var| var.is_exported()))
+        })
+        .collect();
+    for (name, value) in &assignments {
+        shell.set(name, value.clone(), false);
+        shell.export(name, true);
+    }
+
+    let result = match shell.get_function(&argv[0]) {
+        Some(function) => Ok(call_function(ctx, shell, &function, &argv, &redirects)),
+        None => run_internal_command(ctx, shell, &argv, &redirects),
+    };
+    for (name, value, exported) in saved_vars.into_iter().rev() {
+        match value {
+            Some(value) => {
+                shell.set(name, value, false);
+                shell.export(name, exported);
+            }
+            None => shell.unset(name),
+        }
+    }
 
-    // Internal commands
-    let result = run_internal_command(shell, &argv);
     match result {
         Ok(status) => return Ok(status),
         Err(err) => match err.downcast_ref::<BuiltinCommandError>() {
@@ -161,7 +781,623 @@ fn run_simple_command(
         },
     }
 
+    // `autocd`: a directory name alone which is not a command changes the
+    // current directory.
+    if shell.is_shopt_set("autocd")
+        && argv.len() == 1
+        && shell.path_table().lookup(&argv[0]).is_none()
+        && Path::new(&argv[0]).is_dir()
+    {
+        let cd_argv = ["cd".to_owned(), argv[0].clone()];
+        return run_internal_command(ctx, shell, &cd_argv, &redirects);
+    }
+
+    // A missing command is passed to `command_not_found_handle` if it's
+    // defined, instead of being reported.
+    let is_path = argv[0].starts_with('/') || argv[0].starts_with("./");
+    if !is_path && shell.path_table().lookup(&argv[0]).is_none() {
+        if let Some(handler) = shell.get_function("command_not_found_handle") {
+            let handler_argv = [vec!["command_not_found_handle".to_owned()], argv].concat();
+            return Ok(call_function(
+                ctx,
+                shell,
+                &handler,
+                &handler_argv,
+                &redirects,
+            ));
+        }
+    }
+
     debug!("argv: {:?}", argv);
-    // TODO: External commands
-    run_external_command(ctx, shell, argv)
+    // External commands: assignments are passed as environment variables.
+    let envs: Vec<(String, String)> = assignments
+        .into_iter()
+        .map(|(name, value)| match value {
+            Value::String(value) => (name, value),
+            Value::Array(elems) => (name, elems.into_iter().next().unwrap_or_default()),
+        })
+        .collect();
+    run_external_command(ctx, shell, argv, &redirects, &envs)
+}
+
+/// Replaces the command name with its alias. An alias is not expanded again
+/// in its own expansion so that `alias ls='ls -F'` runs the command `ls`.
+/// Prints a command to be run for `set -x`: `$PS4` (`+ ` by default)
+/// followed by the assignments and the words, quoted where needed.
+fn trace_command(shell: &mut Shell, assignments: &[(String, Value)], argv: &[String]) {
+    let quote_all = |words: &[String]| {
+        words
+            .iter()
+            .map(|word| shell_quote(word))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+
+    let mut words: Vec<String> = assignments
+        .iter()
+        .map(|(name, value)| match value {
+            Value::String(value) => format!("{}={}", name, shell_quote(value)),
+            Value::Array(elems) => format!("{}=({})", name, quote_all(elems)),
+        })
+        .collect();
+    words.extend(argv.iter().map(|arg| shell_quote(arg)));
+
+    let ps4 = match shell.get("PS4") {
+        Some(var) => var.as_str().to_owned(),
+        None => "+ ".to_owned(),
+    };
+    writeln!(shell.stderr(), "{}{}", ps4, words.join(" ")).ok();
+}
+
+fn expand_aliases(shell: &Shell, argv: &[parser::Word]) -> Vec<parser::Word> {
+    let mut argv = argv.to_vec();
+    let mut expanded = HashSet::new();
+    while let Some([Span::Literal(name)]) = argv.first().map(parser::Word::spans) {
+        let value = match shell.alias(name) {
+            Some(value) if !expanded.contains(name) => value,
+            _ => break,
+        };
+
+        // Only a simple command is supported for now.
+        let words = match parse_simple_command(value) {
+            Some(words) => words,
+            None => break,
+        };
+
+        expanded.insert(name.clone());
+        argv.splice(0..1, words);
+    }
+
+    argv
+}
+
+/// The words of `code` if it's a simple command without redirections nor
+/// assignments.
+fn parse_simple_command(code: &str) -> Option<Vec<parser::Word>> {
+    let ast = parser::parse(code).ok()?;
+    match ast.terms.as_slice() {
+        [] => Some(Vec::new()),
+        [term] if !term.background => match term.pipelines.as_slice() {
+            [pipeline] => match pipeline.commands.as_slice() {
+                [parser::Command::SimpleCommand {
+                    argv,
+                    redirects,
+                    assignments,
+                }] if redirects.is_empty() && assignments.is_empty() => Some(argv.clone()),
+                _ => None,
+            },
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Calls the function `name` from outside of a script (e.g. a completion
+/// function). Returns `None` if it's not defined.
+pub fn run_function(shell: &mut Shell, name: &str, argv: &[String]) -> Option<ExitStatus> {
+    let function = shell.get_function(name)?;
+    let ctx = Context {
+        pgid: None,
+        background: false,
+        interactive: shell.interactive(),
+        nohup: false,
+        stdin: 0,
+        stdout: 1,
+        pipe_out: None,
+    };
+
+    Some(call_function(&ctx, shell, &function, argv, &[]))
+}
+
+fn call_function(
+    ctx: &Context,
+    shell: &mut Shell,
+    function: &Function,
+    argv: &[String],
+    redirects: &[Redirection],
+) -> ExitStatus {
+    if shell.function_depth() >= shell.max_function_depth() {
+        smash_err!(
+            "{}: maximum function nesting level exceeded ({})",
+            argv[0],
+            shell.max_function_depth()
+        );
+        shell.abort_functions();
+        return ExitStatus::ExitedWith(1);
+    }
+
+    let saved_args = shell.enter_function(&argv[0], argv[1..].to_vec());
+    let status = with_redirections(redirects, || {
+        let status = match run_command(shell, &function.body, ctx) {
+            Ok(status) => status,
+            Err(err) => {
+                smash_err!("{}", err);
+                ExitStatus::ExitedWith(1)
+            }
+        };
+
+        shell.stdout().flush().ok();
+        status
+    });
+
+    shell.leave_function(saved_args);
+    status
+}
+
+fn expand_assignments(
+    shell: &mut Shell,
+    assignments: &[parser::Assignment],
+) -> anyhow::Result<Vec<(String, Value)>> {
+    let mut expanded = Vec::new();
+    for assignment in assignments {
+        if !is_valid_name(&assignment.name) {
+            anyhow::bail!("{}: not a valid identifier", assignment.name);
+        }
+
+        let value = match &assignment.initializer {
+            Initializer::String(word) => Value::String(expand_word_into_string(shell, word)?),
+            Initializer::Array(words) => Value::Array(expand_words(shell, words)?),
+        };
+
+        let value = match (&assignment.index, value) {
+            (None, value) => value,
+            (Some(index), Value::String(elem)) => {
+                assign_element(shell, &assignment.name, index, elem)?
+            }
+            (Some(_), Value::Array(_)) => {
+                anyhow::bail!(
+                    "{}: cannot assign a list to an array element",
+                    assignment.name
+                )
+            }
+        };
+
+        expanded.push((assignment.name.clone(), value));
+    }
+
+    Ok(expanded)
+}
+
+/// Returns the array `name` with the element at `index` replaced by `elem`
+/// for `name[index]=elem`. Arrays are not sparse: the elements before it are
+/// filled with empty strings.
+fn assign_element(
+    shell: &mut Shell,
+    name: &str,
+    index: &Index,
+    elem: String,
+) -> anyhow::Result<Value> {
+    let mut elems = match shell.get(name).and_then(|var| var.value().clone()) {
+        Some(Value::String(value)) => vec![value],
+        Some(Value::Array(elems)) => elems,
+        None => Vec::new(),
+    };
+
+    let n = match index {
+        Index::Number(n) => *n as i64,
+        Index::Expr(expr) => eval_expr(shell, expr)?,
+        Index::All | Index::AllJoined => anyhow::bail!("{}: bad array subscript", name),
+    };
+
+    // A negative index counts from the end.
+    let i = if n < 0 { elems.len() as i64 + n } else { n };
+    let i = match usize::try_from(i) {
+        Ok(i) => i,
+        Err(_) => anyhow::bail!("{}[{}]: bad array subscript", name, n),
+    };
+
+    if elems.len() <= i {
+        elems.resize(i + 1, String::new());
+    }
+
+    elems[i] = elem;
+    Ok(Value::Array(elems))
+}
+
+fn expand_redirections(
+    shell: &mut Shell,
+    redirects: &[parser::Redirection],
+) -> anyhow::Result<Vec<Redirection>> {
+    let mut expanded = Vec::new();
+    for redirect in redirects {
+        let target = match &redirect.target {
+            RedirectionTarget::Fd(target_fd) => {
+                expanded.push(Redirection::Fd {
+                    fd: redirect.fd,
+                    target_fd: *target_fd,
+                });
+                continue;
+            }
+            RedirectionTarget::FdWord(word) => {
+                let target = expand_word_into_string(shell, word)?;
+                let target_fd = match target.parse() {
+                    Ok(target_fd) => target_fd,
+                    Err(_) => anyhow::bail!("{}: ambiguous redirect", target),
+                };
+
+                expanded.push(Redirection::Fd {
+                    fd: redirect.fd,
+                    target_fd,
+                });
+                continue;
+            }
+            RedirectionTarget::Close => {
+                expanded.push(Redirection::Close { fd: redirect.fd });
+                continue;
+            }
+            RedirectionTarget::File(word) => word,
+        };
+
+        if redirect.direction == RedirectionDirection::HereString {
+            let mut body = expand_word_into_string(shell, target)?;
+            body.push('\n');
+            expanded.push(Redirection::HereString {
+                fd: redirect.fd,
+                body,
+            });
+            continue;
+        }
+
+        let mut words = expand_words(shell, std::slice::from_ref(target))?;
+        if words.len() != 1 {
+            anyhow::bail!("ambiguous redirect");
+        }
+
+        let flags = match redirect.direction {
+            RedirectionDirection::Input => OFlag::O_RDONLY,
+            RedirectionDirection::Output => OFlag::O_WRONLY | OFlag::O_CREAT | OFlag::O_TRUNC,
+            RedirectionDirection::Append => OFlag::O_WRONLY | OFlag::O_CREAT | OFlag::O_APPEND,
+            RedirectionDirection::HereString => unreachable!(),
+        };
+
+        let path = words.remove(0);
+        match special_fd(&path) {
+            Some(target_fd) => expanded.push(Redirection::Fd {
+                fd: redirect.fd,
+                target_fd,
+            }),
+            None => expanded.push(Redirection::File {
+                fd: redirect.fd,
+                path,
+                flags,
+            }),
+        }
+    }
+
+    Ok(expanded)
+}
+
+/// Returns the descriptor `/dev/stdin`, `/dev/stdout`, `/dev/stderr`, or
+/// `/dev/fd/N` refers to. They are duplicated instead of being opened so
+/// that they work even if the system lacks them.
+fn special_fd(path: &str) -> Option<RawFd> {
+    match path {
+        "/dev/stdin" => Some(0),
+        "/dev/stdout" => Some(1),
+        "/dev/stderr" => Some(2),
+        _ => path.strip_prefix("/dev/fd/")?.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::process::ExitStatus;
+    use crate::shell::{SharedBuffer, Shell, CWD_LOCK, FD_LOCK};
+    use crate::variable::Value;
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("smash-eval-{}-{}", std::process::id(), name))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn append_stderr_and_both() {
+        let _lock = FD_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let path = temp_path("append-both");
+        let mut shell = Shell::new();
+        shell.run_script(&format!("/bin/sh -c 'echo e1 >&2' 2>> {}", path));
+        shell.run_script(&format!("/bin/sh -c 'echo e2 >&2' 2>>{}", path));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "e1\ne2\n");
+
+        shell.run_script(&format!("/bin/sh -c 'echo out; echo err >&2' &>> {}", path));
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "e1\ne2\nout\nerr\n"
+        );
+
+        shell.run_script(&format!("/bin/sh -c 'echo err >&2' &>{}", path));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "err\n");
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn redirect_external_command_output() {
+        let _lock = FD_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let path = temp_path("external");
+        let mut shell = Shell::new();
+        shell.run_script(&format!("/bin/echo hello > {}", path));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "hello\n");
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn redirect_read_input() {
+        let _lock = FD_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let path = temp_path("read");
+        std::fs::write(&path, "first line\nsecond line\n").unwrap();
+        let mut shell = Shell::new();
+        shell.run_script(&format!("read a b < {}", path));
+        assert_eq!(shell.get("a").unwrap().as_str(), "first");
+        assert_eq!(shell.get("b").unwrap().as_str(), "line");
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn pipelines() {
+        let _lock = FD_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let path = temp_path("pipeline");
+        let mut shell = Shell::new();
+        shell.run_script(&format!("echo hello | /bin/cat | /bin/cat > {}", path));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "hello\n");
+
+        // Builtins and functions don't block the shell on a full pipe.
+        shell.run_script("x=$(printf %070000d 0); f() { echo \"$x\"; }");
+        let script = format!(
+            "echo \"$x\" | /usr/bin/wc -c > {0}; f | /usr/bin/wc -c >> {0}",
+            path
+        );
+        shell.run_script(&script);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "70001\n70001\n");
+        shell.run_script("/bin/echo a b | read x y");
+        assert_eq!(shell.get("y").unwrap().as_str(), "b");
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn assign_to_array_elements() {
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        shell.run_script("a=(x y z); a[1]=Y; i=2; a[i]=Z; a[-3]=X; echo ${a[@]}");
+        shell.run_script("s=str; s[1]=two; b[2]=c; echo ${s[@]} ${#b[@]} \"[${b[0]}]\"");
+        assert_eq!(stdout.contents(), "X Y Z\nstr two 3 []\n");
+
+        assert_eq!(shell.run_script("a[-4]=w"), ExitStatus::ExitedWith(1));
+        assert_eq!(shell.run_script("a[@]=w"), ExitStatus::ExitedWith(1));
+        assert_eq!(shell.run_script("a[0]=(w)"), ExitStatus::ExitedWith(1));
+    }
+
+    #[test]
+    fn pipe_status() {
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        shell.run_script("/bin/true | /bin/false | /bin/true");
+        assert_eq!(shell.last_status(), 0);
+        shell.run_script("echo ${PIPESTATUS[@]}");
+        assert_eq!(stdout.contents(), "0 1 0\n");
+    }
+
+    #[test]
+    fn and_or_lists() {
+        let cases = [
+            ("/bin/true && echo a || echo b", "a\n", 0),
+            ("/bin/false && echo a || echo b", "b\n", 0),
+            ("/bin/true || echo a && echo b", "b\n", 0),
+            ("/bin/false || echo a && echo b", "a\nb\n", 0),
+            ("/bin/false || /bin/false || echo a", "a\n", 0),
+            ("/bin/true && /bin/false && echo a || echo b", "b\n", 0),
+            ("/bin/true || /bin/false || echo a", "", 0),
+            ("/bin/true && /bin/false", "", 1),
+            ("/bin/false || /bin/sh -c 'exit 3'", "", 3),
+        ];
+
+        for (script, expected, status) in cases {
+            let stdout = SharedBuffer::new();
+            let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+            shell.run_script(script);
+            assert_eq!(stdout.contents(), expected, "{}", script);
+            assert_eq!(shell.last_status(), status, "{}", script);
+        }
+    }
+
+    #[test]
+    fn time_pipeline() {
+        let stderr = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), std::io::sink(), stderr.clone());
+        shell.run_script("time /bin/sleep 0.05");
+        let report = stderr.contents();
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines.len(), 4, "{}", report);
+        assert_eq!(lines[0], "");
+        assert!(lines[1].starts_with("real\t0m0."));
+        assert!(lines[2].starts_with("user\t0m"));
+        assert!(lines[3].starts_with("sys\t0m"));
+        assert!(lines[1] >= "real\t0m0.050s");
+    }
+
+    #[test]
+    fn autocd() {
+        let _lock = CWD_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let saved_cwd = std::env::current_dir().unwrap();
+        let dir = std::path::PathBuf::from(temp_path("autocd"));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::create_dir_all(dir.join("echo")).unwrap();
+        std::env::set_current_dir(&dir).unwrap();
+
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        assert_ne!(shell.run_script("sub"), ExitStatus::ExitedWith(0));
+        assert_eq!(std::env::current_dir().unwrap(), dir);
+
+        shell.run_script("shopt -s autocd");
+        assert_eq!(shell.run_script("sub"), ExitStatus::ExitedWith(0));
+        assert_eq!(std::env::current_dir().unwrap(), dir.join("sub"));
+
+        // A command precedes a directory of the same name.
+        shell.run_script("cd ..");
+        shell.run_script("echo");
+        assert_eq!(std::env::current_dir().unwrap(), dir);
+        assert_eq!(stdout.contents(), "\n");
+
+        std::env::set_current_dir(saved_cwd).unwrap();
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn environment_of_child_processes() {
+        let mut shell = Shell::with_io(std::io::empty(), std::io::sink(), std::io::sink());
+        shell.run_script("export FOO=bar; x=$(/usr/bin/printenv FOO)");
+        assert_eq!(shell.get("x").unwrap().as_str(), "bar");
+
+        // Assignments to a builtin or a function are passed to commands run
+        // from it, and only to them.
+        shell.run_script("FOO=baz eval 'y=$(/usr/bin/printenv FOO)'");
+        assert_eq!(shell.get("y").unwrap().as_str(), "baz");
+        shell.run_script("f() { z=$(/usr/bin/printenv BAR); }; BAR=qux f");
+        assert_eq!(shell.get("z").unwrap().as_str(), "qux");
+        assert_eq!(shell.get("FOO").unwrap().as_str(), "bar");
+        assert!(shell.get("BAR").is_none());
+        assert!(!shell.exported_vars().iter().any(|(name, _)| name == "BAR"));
+    }
+
+    #[test]
+    fn function_nesting_limit() {
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        shell.run_script("f() { echo x; f; echo unreachable; }");
+        assert_eq!(shell.run_script("FUNCNEST=3; f"), ExitStatus::ExitedWith(1));
+        assert_eq!(stdout.contents(), "x\nx\nx\n");
+        assert_eq!(shell.function_depth(), 0);
+
+        // Loops in the functions are stopped too.
+        stdout.clear();
+        shell.run_script("g() { while /bin/true; do g; done; }");
+        assert_eq!(shell.run_script("g"), ExitStatus::ExitedWith(1));
+
+        // The shell keeps working.
+        stdout.clear();
+        shell.run_script("echo ok");
+        assert_eq!(stdout.contents(), "ok\n");
+    }
+
+    #[test]
+    fn aliases() {
+        let _lock = FD_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        shell.run_script("alias greet='echo hello' e=greet");
+        shell.run_script("e world");
+        assert_eq!(stdout.contents(), "hello world\n");
+
+        // A self-referential alias is expanded only once.
+        stdout.clear();
+        shell.run_script("alias echo='echo echo'; echo x");
+        assert_eq!(stdout.contents(), "echo x\n");
+
+        let path = std::env::temp_dir().join(format!("smash-alias-{}", std::process::id()));
+        shell.run_script("PATH=/bin:/usr/bin; alias seq='seq -s,'");
+        shell.run_script(&format!("seq 3 > {}", path.display()));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "1,2,3\n");
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn command_not_found_handle() {
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        shell.run_script("command_not_found_handle() { echo \"missing: $1 ($#)\"; return 127; }");
+        assert_eq!(
+            shell.run_script("no_such_command_xyz a b"),
+            ExitStatus::ExitedWith(127)
+        );
+        assert_eq!(stdout.contents(), "missing: no_such_command_xyz (3)\n");
+
+        // Existing commands are not affected.
+        stdout.clear();
+        shell.run_script("echo found");
+        assert_eq!(stdout.contents(), "found\n");
+    }
+
+    #[test]
+    fn functions() {
+        let _lock = FD_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        shell.run_script("f() { echo $# \"$1\"; for_each \"$@\"; return 3; echo unreachable; }");
+        shell.run_script("for_each() { echo \"<$1>\" \"<$2>\"; }");
+        assert_eq!(shell.run_script("f 'a b' c"), ExitStatus::ExitedWith(3));
+        assert_eq!(stdout.contents(), "2 a b\n<a b> <c>\n");
+        // The positional parameters are restored.
+        assert!(shell.args().is_empty());
+
+        assert_eq!(shell.run_script("return 1"), ExitStatus::ExitedWith(1));
+        assert_eq!(
+            shell.run_script("g() { /bin/false; return; }; g"),
+            ExitStatus::ExitedWith(1)
+        );
+    }
+
+    #[test]
+    fn coproc() {
+        let _lock = FD_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        shell.run_script("coproc { read line; echo \"got $line\"; }");
+        shell.run_script("/bin/echo hello >&${COPROC[1]}; read reply <&${COPROC[0]}");
+        assert_eq!(shell.get("reply").unwrap().as_str(), "got hello");
+        assert!(shell.get("COPROC_PID").is_some());
+
+        shell.run_script("coproc UPPER { /bin/cat; }");
+        shell.run_script("/bin/echo abc >&${UPPER[1]}; read reply <&${UPPER[0]}");
+        assert_eq!(shell.get("reply").unwrap().as_str(), "abc");
+
+        // Let `cat` exit by EOF.
+        for name in ["COPROC", "UPPER"] {
+            if let Some(Value::Array(fds)) = shell.get(name).and_then(|var| var.value().clone()) {
+                for fd in fds {
+                    nix::unistd::close(fd.parse::<i32>().unwrap()).ok();
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn select() {
+        let _lock = FD_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let stdout = SharedBuffer::new();
+        let stderr = SharedBuffer::new();
+        let mut shell = Shell::with_io("2\n\n9\n1\n".as_bytes(), stdout.clone(), stderr.clone());
+        shell.run_script("PS3='> '; select fruit in apple banana; do echo \"$REPLY:$fruit\"; done");
+        assert_eq!(stdout.contents(), "2:banana\n9:\n1:apple\n");
+        assert_eq!(
+            stderr.contents(),
+            "1) apple\n2) banana\n> > 1) apple\n2) banana\n> > > \n"
+        );
+
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io("1\n2\n".as_bytes(), stdout.clone(), std::io::sink());
+        shell.run_script("select x in a b; do echo $x; break; echo unreachable; done; echo done");
+        assert_eq!(stdout.contents(), "a\ndone\n");
+    }
 }
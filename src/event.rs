@@ -6,21 +6,57 @@ use crossterm::{execute, queue};
 use nix::sys::signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal};
 use std::cmp::min;
 use std::io::Write;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tracing::debug;
 
+use crate::columns::format_columns;
+use crate::completion::complete;
+use crate::parser::{self, ParseError};
+use crate::path::abbreviate_home;
+use crate::process::{collect_finished_jobs, hangup_jobs};
+use crate::prompt::expand_prompt;
 use crate::shell::Shell;
+use crate::variable::Value;
 
 pub struct SmashState {
     shell: Shell,
     columns: usize,
     lines: usize,
-    prompt_len: usize,
+    /// The width of the last line of the prompt.
+    prompt_width: usize,
     input: UserInput,
     clear_above: usize,
     clear_below: usize,
+    /// Set by EOF (Ctrl-D) at an empty prompt.
+    exit_requested: bool,
+    /// `$PROMPT_COMMAND` is being run.
+    in_prompt_command: bool,
+    /// Text killed by Ctrl-K, Ctrl-U, and Ctrl-W. The last one is the most
+    /// recent.
+    kill_ring: Vec<String>,
+    /// The last key killed text: the next kill is appended to the same entry.
+    killing: bool,
+    /// The position, length, and kill ring index of the text inserted by the
+    /// last key if it was Ctrl-Y or Alt-Y.
+    last_yank: Option<(usize, usize, usize)>,
+    /// How long the last command took (`\E` in `$PS1`).
+    last_elapsed: Option<Duration>,
+    /// In the command mode of `set -o vi` (entered by Esc).
+    vi_command_mode: bool,
+    /// The first key of a two-key vi command (`dd`).
+    vi_pending: Option<char>,
+    /// Where the prompt and the input are drawn and the bell is rung.
+    terminal: Box<dyn Write>,
+    /// The screen is flashed by the visible bell until then.
+    flash_until: Option<Instant>,
+    /// The number of the history entry being shown by Up and Down, and the
+    /// input before it.
+    history_position: Option<(usize, String)>,
 }
 
+/// The maximum number of entries in the kill ring.
+const KILL_RING_MAX: usize = 16;
+
 #[derive(Clone, Debug)]
 struct UserInput {
     input: String,
@@ -39,10 +75,12 @@ impl UserInput {
         }
     }
 
+    /// The number of characters.
     pub fn len(&self) -> usize {
-        self.input.len()
+        self.indices.len()
     }
 
+    #[cfg(test)]
     pub fn cursor(&self) -> usize {
         self.cursor
     }
@@ -95,6 +133,45 @@ impl UserInput {
         }
     }
 
+    pub fn insert_str(&mut self, text: &str) {
+        self.input.insert_str(self.byte_index(), text);
+        self.update_indices();
+        self.cursor += text.chars().count();
+    }
+
+    /// Removes the characters from `start` to `end` and returns them. The
+    /// cursor moves to `start`.
+    pub fn remove_range(&mut self, start: usize, end: usize) -> String {
+        let byte_at = |i: usize| self.indices.get(i).copied().unwrap_or(self.input.len());
+        let removed = self.input.drain(byte_at(start)..byte_at(end)).collect();
+        self.update_indices();
+        self.cursor = start;
+        removed
+    }
+
+    /// The start of the whitespace-delimited word before the cursor.
+    pub fn prev_word_start(&self) -> usize {
+        let chars: Vec<char> = self.input.chars().take(self.cursor).collect();
+        let mut start = chars.len();
+        while start > 0 && chars[start - 1].is_whitespace() {
+            start -= 1;
+        }
+
+        while start > 0 && !chars[start - 1].is_whitespace() {
+            start -= 1;
+        }
+
+        start
+    }
+
+    /// Replaces the text from `start` (a byte offset) to the cursor.
+    pub fn replace_before_cursor(&mut self, start: usize, text: &str) {
+        let end = self.byte_index();
+        self.input.replace_range(start..end, text);
+        self.update_indices();
+        self.cursor = self.input[..start + text.len()].chars().count();
+    }
+
     pub fn clear(&mut self) {
         self.cursor = 0;
         self.input.clear();
@@ -140,12 +217,8 @@ impl UserInput {
 
     pub fn move_to_next_word(&mut self) {
         // Skip the whitespace at the current position.
-        self.cursor += 1;
-        if self.cursor > self.input.len() {
-            self.cursor = self.input.len();
-        }
-
-        while self.cursor < self.input.len() {
+        self.cursor = min(self.len(), self.cursor + 1);
+        while self.cursor < self.len() {
             if let Some(prev_ch) = self.nth(self.cursor.saturating_sub(1)) {
                 if let Some(ch) = self.nth(self.cursor) {
                     if self.word_split.contains(prev_ch) && !self.word_split.contains(ch) {
@@ -169,45 +242,201 @@ impl Drop for SmashState {
     }
 }
 
+/// The number of columns `ch` occupies in a terminal: zero for combining
+/// marks and zero-width characters, and two for East Asian wide characters.
+fn char_width(ch: char) -> usize {
+    match ch as u32 {
+        0x0300..=0x036f | 0x200b..=0x200f | 0x2060 | 0xfe00..=0xfe0f | 0xfeff => 0,
+        0x1100..=0x115f
+        | 0x2e80..=0x303e
+        | 0x3041..=0x33ff
+        | 0x3400..=0x4dbf
+        | 0x4e00..=0x9fff
+        | 0xa000..=0xa4cf
+        | 0xac00..=0xd7a3
+        | 0xf900..=0xfaff
+        | 0xfe30..=0xfe4f
+        | 0xff00..=0xff60
+        | 0xffe0..=0xffe6
+        | 0x1f300..=0x1f64f
+        | 0x1f900..=0x1f9ff
+        | 0x20000..=0x3fffd => 2,
+        _ if ch.is_control() => 0,
+        _ => 1,
+    }
+}
+
+fn str_width(s: &str) -> usize {
+    s.chars().map(char_width).sum()
+}
+
+/// Returns the row and column where the cursor ends up after printing
+/// `text` from the column `start` in a terminal `columns` wide. Characters
+/// which don't fit in the rest of a row wrap to the next one, and a row
+/// filled up to the last column moves the cursor to the next row.
+fn cursor_position(start: usize, text: &str, columns: usize) -> (usize, usize) {
+    let (mut y, mut x) = (start / columns, start % columns);
+    for ch in text.chars() {
+        if ch == '\n' {
+            y += 1;
+            x = 0;
+            continue;
+        }
+
+        let width = char_width(ch);
+        if x + width > columns {
+            y += 1;
+            x = 0;
+        }
+
+        x += width;
+        if x == columns {
+            y += 1;
+            x = 0;
+        }
+    }
+
+    (y, x)
+}
+
 impl SmashState {
     pub fn new(shell: Shell) -> Self {
         Self {
             shell,
-            columns: 0,
-            lines: 0,
-            prompt_len: 0,
+            // Until the terminal size is known.
+            columns: 80,
+            lines: 24,
+            prompt_width: 0,
             input: UserInput::new(),
             clear_above: 0,
             clear_below: 0,
+            exit_requested: false,
+            in_prompt_command: false,
+            kill_ring: Vec::new(),
+            killing: false,
+            last_yank: None,
+            last_elapsed: None,
+            vi_command_mode: false,
+            vi_pending: None,
+            terminal: Box::new(std::io::stdout()),
+            flash_until: None,
+            history_position: None,
+        }
+    }
+
+    /// Notifies the user that a key did nothing (e.g. no completions) as
+    /// `$BELL_STYLE` says: `audible` (default), `visible`, or `none`.
+    fn ring_bell(&mut self) {
+        let style = self
+            .shell
+            .get("BELL_STYLE")
+            .map(|var| var.as_str().to_owned())
+            .unwrap_or_default();
+        match style.as_str() {
+            "none" => (),
+            "visible" => {
+                // Flash the screen in reverse video. It's restored by
+                // `end_flash` so that keys are not blocked meanwhile.
+                self.terminal.write_all(b"\x1b[?5h").ok();
+                self.flash_until = Some(Instant::now() + Duration::from_millis(100));
+            }
+            _ => {
+                self.terminal.write_all(b"\x07").ok();
+            }
+        }
+
+        self.terminal.flush().ok();
+    }
+
+    /// Restores the screen flashed by the visible bell if it's time (or
+    /// `now`, e.g. before running a command).
+    fn end_flash(&mut self, now: bool) {
+        if self
+            .flash_until
+            .is_some_and(|until| now || Instant::now() >= until)
+        {
+            self.flash_until = None;
+            self.terminal.write_all(b"\x1b[?5l").ok();
+            self.terminal.flush().ok();
         }
     }
 
+    /// Runs `$PROMPT_COMMAND` before displaying a prompt. `$?` is left as is.
+    fn run_prompt_command(&mut self) {
+        let command = match self.shell.get("PROMPT_COMMAND") {
+            Some(var) if !var.as_str().is_empty() => var.as_str().to_owned(),
+            _ => return,
+        };
+
+        if self.in_prompt_command {
+            return;
+        }
+
+        self.in_prompt_command = true;
+        let last_status = self.shell.last_status();
+        disable_raw_mode().ok();
+        self.shell.run_script(&command);
+        enable_raw_mode().ok();
+        self.shell.set_last_status(last_status);
+        self.in_prompt_command = false;
+    }
+
     fn run_command(&mut self) {
+        self.end_flash(true);
+        // Leave the cursor below the whole input.
+        self.input.move_to_end();
         self.print_user_input();
 
-        execute!(std::io::stdout(), Print("\r\n")).ok();
+        execute!(self.terminal, Print("\r\n")).ok();
         disable_raw_mode().ok();
-        self.shell.run_script(self.input.as_str());
+        match self.shell.history().expand(self.input.as_str()) {
+            Ok(expanded) => {
+                let line = match expanded {
+                    Some(expanded) => {
+                        // Show the command to be run.
+                        writeln!(self.terminal, "{}", expanded).ok();
+                        expanded
+                    }
+                    None => self.input.as_str().to_owned(),
+                };
+
+                self.shell.history_mut().push(&line);
+                let started = Instant::now();
+                self.shell.run_script(&line);
+                self.last_elapsed = Some(started.elapsed());
+            }
+            Err(err) => {
+                writeln!(self.shell.stderr(), "smash: {}", err).ok();
+            }
+        }
+
+        // Background jobs finished while the command was running.
+        for job in collect_finished_jobs(&mut self.shell) {
+            writeln!(self.shell.stderr(), "[{}] Done: {}", job.id(), job.cmd).ok();
+        }
         enable_raw_mode().ok();
 
         self.input.clear();
-        self.clear_above = 0;
-        self.clear_below = 0;
-
+        self.vi_command_mode = false;
+        self.history_position = None;
+        self.run_prompt_command();
         self.render_prompt();
         self.print_user_input();
     }
 
     pub fn render_prompt(&mut self) {
-        let screen_size = terminal::size().unwrap();
-        self.columns = screen_size.0 as usize;
-        self.lines = screen_size.1 as usize;
+        // The input starts on the new prompt's line.
+        self.clear_above = 0;
+        self.clear_below = 0;
+        if let Ok((columns, lines)) = terminal::size() {
+            self.columns = columns as usize;
+            self.lines = lines as usize;
+        }
 
         debug!(?self.columns);
 
-        let mut stdout = std::io::stdout();
         queue!(
-            stdout,
+            self.terminal,
             SetAttribute(Attribute::Bold),
             SetAttribute(Attribute::Reverse),
             Print("$"),
@@ -215,93 +444,289 @@ impl SmashState {
             Print(&format!(
                 "{space:>width$}\r",
                 space = " ",
-                width = self.columns - 1
+                width = self.columns.saturating_sub(1)
             ))
         )
         .ok();
 
-        let (mut prompt_str, mut prompt_len) = (String::new(), 0);
-        if let Ok(current_dir) = std::env::current_dir() {
-            let mut path = current_dir.to_str().unwrap().to_string();
-
-            // "/Users/username/path/to" -> "~/path/to"
-            if let Some(home_dir) = dirs::home_dir() {
-                let home_dir = home_dir.to_str().unwrap();
-                if path.starts_with(&home_dir) {
-                    path = path.replace(home_dir, "~");
+        let prompt_str = match self.shell.get("PS1") {
+            Some(ps1) => expand_prompt(&self.shell, ps1.as_str(), self.last_elapsed),
+            None => {
+                let mut prompt_str = String::new();
+                if let Ok(current_dir) = std::env::current_dir() {
+                    // "/Users/username/path/to" -> "~/path/to"
+                    prompt_str.push_str(&abbreviate_home(&current_dir.to_string_lossy()));
                 }
-            }
 
-            prompt_str.push_str(&path);
-        }
-
-        prompt_str.push_str(" $ ");
-        queue!(stdout, Print(prompt_str.replace('\n', "\r\n"))).ok();
-        prompt_len += prompt_str.len();
-        stdout.flush().unwrap();
+                prompt_str.push_str(" $ ");
+                prompt_str
+            }
+        };
+        queue!(self.terminal, Print(prompt_str.replace('\n', "\r\n"))).ok();
+        self.terminal.flush().ok();
 
-        self.prompt_len = prompt_len;
+        self.prompt_width = str_width(prompt_str.rsplit('\n').next().unwrap_or_default());
     }
 
     fn print_user_input(&mut self) {
-        if cfg!(test) {
-            return;
-        }
+        let columns = self.columns.max(1);
+        let input = self.input.as_str();
+        let terminal = &mut self.terminal;
 
-        let mut stdout = std::io::stdout();
+        queue!(terminal, cursor::Hide).ok();
+
+        // Redraw from the first line of the input.
+        if self.clear_above > 0 {
+            queue!(terminal, cursor::MoveUp(self.clear_above as u16)).ok();
+        }
 
-        queue!(stdout, cursor::Hide).ok();
+        queue!(terminal, Print("\r")).ok();
+        if self.prompt_width > 0 {
+            queue!(terminal, cursor::MoveRight(self.prompt_width as u16)).ok();
+        }
 
         queue!(
-            stdout,
-            Print("\r"),
-            cursor::MoveRight(self.prompt_len as u16),
-            Clear(ClearType::UntilNewLine),
-            Print(self.input.input.replace('\n', "\r\n"))
+            terminal,
+            Clear(ClearType::FromCursorDown),
+            Print(input.replace('\n', "\r\n"))
         )
         .ok();
 
-        let current_x = self.prompt_len + self.input.len();
-        if current_x % self.columns == 0 {
-            queue!(stdout, Print("\r\n")).ok();
+        let (end_y, end_x) = cursor_position(self.prompt_width, input, columns);
+        if end_x == 0 && end_y > 0 && !input.ends_with('\n') {
+            // The terminal doesn't move the cursor to the next line until
+            // the next character is printed.
+            queue!(terminal, Print("\r\n")).ok();
         }
 
-        let input_height = current_x / self.columns;
-
-        let cursor_y = (self.prompt_len + self.input.cursor()) / self.columns;
-        let cursor_x = (self.prompt_len + self.input.cursor()) % self.columns;
-        let cursor_y_diff = input_height - cursor_y;
-        if cursor_y_diff > 0 {
-            queue!(stdout, cursor::MoveUp(cursor_y_diff as u16),).ok();
+        let before_cursor = &input[..self.input.byte_index()];
+        let (cursor_y, cursor_x) = cursor_position(self.prompt_width, before_cursor, columns);
+        if end_y > cursor_y {
+            queue!(terminal, cursor::MoveUp((end_y - cursor_y) as u16)).ok();
         }
 
-        queue!(stdout, Print("\r")).ok();
+        queue!(terminal, Print("\r")).ok();
         if cursor_x > 0 {
-            queue!(stdout, cursor::MoveRight(cursor_x as u16),).ok();
+            queue!(terminal, cursor::MoveRight(cursor_x as u16)).ok();
         }
 
-        queue!(stdout, cursor::Show).ok();
+        queue!(terminal, cursor::Show).ok();
 
         self.clear_above = cursor_y;
-        self.clear_below = input_height - cursor_y;
-        stdout.flush().ok();
+        self.clear_below = end_y - cursor_y;
+        terminal.flush().ok();
+    }
+
+    /// Kills the characters from `start` to `end`. Consecutive kills are
+    /// accumulated into one kill ring entry.
+    fn kill(&mut self, start: usize, end: usize, was_killing: bool) {
+        self.killing = true;
+        if start >= end {
+            return;
+        }
+
+        let killed = self.input.remove_range(start, end);
+        match self.kill_ring.last_mut() {
+            // Killed backwards (e.g. Ctrl-W) if it ended at the cursor.
+            Some(last) if was_killing && end > self.input.cursor => last.insert_str(0, &killed),
+            Some(last) if was_killing => last.push_str(&killed),
+            _ => {
+                if self.kill_ring.len() == KILL_RING_MAX {
+                    self.kill_ring.remove(0);
+                }
+
+                self.kill_ring.push(killed);
+            }
+        }
+    }
+
+    /// Inserts the kill ring entry at `index` and remembers it for Alt-Y.
+    fn yank(&mut self, index: usize) {
+        let text = self.kill_ring[index].clone();
+        let start = self.input.cursor;
+        self.input.insert_str(&text);
+        self.last_yank = Some((start, text.chars().count(), index));
+    }
+
+    /// Updates the terminal size on SIGWINCH (reported by crossterm as a
+    /// resize event) and redraws the input in the new width.
+    fn resize(&mut self, columns: u16, lines: u16) {
+        self.columns = columns as usize;
+        self.lines = lines as usize;
+        self.shell
+            .set("COLUMNS", Value::String(columns.to_string()), false);
+        self.shell
+            .set("LINES", Value::String(lines.to_string()), false);
+        self.print_user_input();
+    }
+
+    /// Completes the word before the cursor, or lists the candidates if it
+    /// can't be extended.
+    fn complete(&mut self) {
+        let cursor = self.input.byte_index();
+        let completion = complete(&mut self.shell, self.input.as_str(), cursor);
+        let current = &self.input.as_str()[completion.start..cursor];
+        match completion.candidates.as_slice() {
+            [] => self.ring_bell(),
+            [candidate] => {
+                let mut text = candidate.clone();
+                if !text.ends_with('/') {
+                    text.push(' ');
+                }
+
+                self.input.replace_before_cursor(completion.start, &text);
+            }
+            candidates => {
+                let prefix = completion.common_prefix();
+                if prefix.len() > current.len() {
+                    let prefix = prefix.to_owned();
+                    self.input.replace_before_cursor(completion.start, &prefix);
+                } else {
+                    queue!(self.terminal, Print("\r\n")).ok();
+                    for line in format_columns(candidates, self.columns) {
+                        queue!(self.terminal, Print(line), Print("\r\n")).ok();
+                    }
+
+                    self.render_prompt();
+                }
+            }
+        }
+    }
+
+    /// Replaces the input with the previous (`older`) or next history entry.
+    /// With `shopt -s dirhistory`, only the entries entered in the current
+    /// directory are recalled.
+    fn recall_history(&mut self, older: bool) {
+        let history = self.shell.history();
+        let numbers: Vec<usize> = if self.shell.is_shopt_set("dirhistory") {
+            match std::env::current_dir() {
+                Ok(dir) => history.numbers_in_dir(&dir.to_string_lossy()),
+                Err(_) => Vec::new(),
+            }
+        } else {
+            (1..=history.len()).collect()
+        };
+
+        let current = self
+            .history_position
+            .as_ref()
+            .map_or(usize::MAX, |(n, _)| *n);
+        let next = if older {
+            numbers.iter().rev().find(|n| **n < current)
+        } else {
+            numbers.iter().find(|n| **n > current)
+        };
+
+        let next = next.map(|n| (*n, history.get(*n).unwrap_or_default().to_owned()));
+        let line = match (next, self.history_position.take()) {
+            (Some((n, line)), position) => {
+                let saved = match position {
+                    Some((_, saved)) => saved,
+                    None => self.input.as_str().to_owned(),
+                };
+                self.history_position = Some((n, saved));
+                line
+            }
+            // Back to the line being edited.
+            (None, Some((_, saved))) if !older => saved,
+            (None, position) => {
+                self.history_position = position;
+                self.ring_bell();
+                return;
+            }
+        };
+
+        self.input.clear();
+        self.input.insert_str(&line);
+    }
+
+    /// Handles a key in the vi command mode. Returns `false` if it's not a
+    /// vi command.
+    fn handle_vi_command(&mut self, ev: &KeyEvent) -> bool {
+        let ch = match (ev.code, ev.modifiers) {
+            (KeyCode::Char(ch), KeyModifiers::NONE) => ch,
+            (KeyCode::Esc, KeyModifiers::NONE) => {
+                self.vi_pending = None;
+                return true;
+            }
+            _ => return false,
+        };
+
+        match (self.vi_pending.take(), ch) {
+            (Some('d'), 'd') => self.input.clear(),
+            (Some(_), _) => (),
+            (None, 'h') => self.input.move_by(-1),
+            (None, 'l') => self.input.move_by(1),
+            (None, 'w') => self.input.move_to_next_word(),
+            (None, 'b') => self.input.move_to_prev_word(),
+            (None, '0') => self.input.move_to_begin(),
+            (None, '$') => self.input.move_to_end(),
+            (None, 'x') => self.input.delete(),
+            (None, 'd') => self.vi_pending = Some('d'),
+            (None, 'i') => self.vi_command_mode = false,
+            (None, 'a') => {
+                self.input.move_by(1);
+                self.vi_command_mode = false;
+            }
+            _ => self.ring_bell(),
+        }
+
+        // The cursor is on a character in the command mode.
+        if self.vi_command_mode && self.input.cursor >= self.input.len() {
+            self.input.move_to_end();
+            self.input.move_by(-1);
+        }
+
+        true
     }
 
     pub fn handle_key_event(&mut self, ev: &KeyEvent) {
+        if self.shell.is_option_set("vi") {
+            if self.vi_command_mode {
+                if self.handle_vi_command(ev) {
+                    self.print_user_input();
+                    return;
+                }
+            } else if (ev.code, ev.modifiers) == (KeyCode::Esc, KeyModifiers::NONE) {
+                self.vi_command_mode = true;
+                self.input.move_by(-1);
+                self.print_user_input();
+                return;
+            }
+        }
+
         let mut needs_redraw = true;
+        let was_killing = std::mem::take(&mut self.killing);
+        let last_yank = self.last_yank.take();
         match (ev.code, ev.modifiers) {
             // cursor
             (KeyCode::Char('c'), KeyModifiers::CONTROL) => {
-                let mut stdout = std::io::stdout();
-                execute!(stdout, Print("\r\n")).ok();
+                // Discard the current line like an interrupted command.
+                execute!(self.terminal, Print("^C\r\n")).ok();
+
+                self.shell.set_last_status(130);
+                self.run_prompt_command();
                 self.render_prompt();
                 self.input.clear();
+                self.vi_command_mode = false;
+                self.history_position = None;
             }
             (KeyCode::Char('d'), KeyModifiers::CONTROL) => {
-                if self.input.is_empty() {
-                    unreachable!();
-                } else {
+                if !self.input.is_empty() {
                     self.input.delete();
+                } else if self.shell.is_option_set("ignoreeof") {
+                    execute!(
+                        self.terminal,
+                        Print("\r\nUse \"exit\" to leave the shell.\r\n")
+                    )
+                    .ok();
+
+                    self.render_prompt();
+                } else {
+                    execute!(self.terminal, Print("\r\n")).ok();
+
+                    self.exit_requested = true;
+                    needs_redraw = false;
                 }
             }
             (KeyCode::Char('a'), KeyModifiers::CONTROL) => {
@@ -316,19 +741,69 @@ impl SmashState {
             (KeyCode::Char('b'), KeyModifiers::ALT) => {
                 self.input.move_to_prev_word();
             }
+            // kill and yank
+            (KeyCode::Char('k'), KeyModifiers::CONTROL) => {
+                self.kill(self.input.cursor, self.input.len(), was_killing);
+            }
+            (KeyCode::Char('u'), KeyModifiers::CONTROL) => {
+                self.kill(0, self.input.cursor, was_killing);
+            }
+            (KeyCode::Char('w'), KeyModifiers::CONTROL) => {
+                self.kill(self.input.prev_word_start(), self.input.cursor, was_killing);
+            }
+            (KeyCode::Char('y'), KeyModifiers::CONTROL) if !self.kill_ring.is_empty() => {
+                self.yank(self.kill_ring.len() - 1);
+            }
+            (KeyCode::Char('y'), KeyModifiers::ALT) => {
+                // Replace the text just yanked with the previous entry.
+                if let Some((start, len, index)) = last_yank {
+                    self.input.remove_range(start, start + len);
+                    let ring_len = self.kill_ring.len();
+                    self.yank((index + ring_len - 1) % ring_len);
+                }
+            }
+            (KeyCode::Char('l'), KeyModifiers::CONTROL) => {
+                execute!(self.terminal, Clear(ClearType::All), cursor::MoveTo(0, 0)).ok();
+
+                self.render_prompt();
+            }
+            (KeyCode::Up, KeyModifiers::NONE) => {
+                self.recall_history(true);
+            }
+            (KeyCode::Down, KeyModifiers::NONE) => {
+                self.recall_history(false);
+            }
+            (KeyCode::Left, KeyModifiers::NONE) if self.input.cursor == 0 => {
+                self.ring_bell();
+            }
             (KeyCode::Left, KeyModifiers::NONE) => {
                 self.input.move_by(-1);
             }
+            (KeyCode::Right, KeyModifiers::NONE) if self.input.cursor == self.input.len() => {
+                self.ring_bell();
+            }
             (KeyCode::Right, KeyModifiers::NONE) => {
                 self.input.move_by(1);
             }
             // misc
+            (KeyCode::Backspace, KeyModifiers::NONE) if self.input.cursor == 0 => {
+                self.ring_bell();
+            }
             (KeyCode::Backspace, KeyModifiers::NONE) => {
                 self.input.backspace();
             }
             (KeyCode::Enter, KeyModifiers::NONE) => {
-                self.run_command();
-                needs_redraw = false;
+                if parser::parse_incremental(self.input.as_str()) == Err(ParseError::Incomplete) {
+                    // Continue the command on the next line.
+                    self.input.move_to_end();
+                    self.input.insert('\n');
+                } else {
+                    self.run_command();
+                    needs_redraw = false;
+                }
+            }
+            (KeyCode::Tab, KeyModifiers::NONE) => {
+                self.complete();
             }
             (KeyCode::Esc, KeyModifiers::NONE) => {
                 disable_raw_mode().ok();
@@ -346,35 +821,46 @@ impl SmashState {
         }
     }
 
-    pub fn run(&mut self) {
+    /// Reads and runs commands until EOF. Returns the exit status of the shell.
+    pub fn run(&mut self) -> i32 {
+        ignore_job_control_signals();
         enable_raw_mode().ok();
+        self.run_prompt_command();
         self.render_prompt();
 
-        let action = SigAction::new(SigHandler::SigIgn, SaFlags::empty(), SigSet::empty());
-        unsafe {
-            sigaction(Signal::SIGINT, &action).expect("failed to sigaction");
-            sigaction(Signal::SIGQUIT, &action).expect("failed to sigaction");
-            sigaction(Signal::SIGTSTP, &action).expect("failed to sigaction");
-            sigaction(Signal::SIGTTIN, &action).expect("failed to sigaction");
-            sigaction(Signal::SIGTTOU, &action).expect("failed to sigaction");
-        }
-
         debug!("start");
-        loop {
-            match crossterm::event::poll(Duration::from_millis(100)) {
-                Ok(true) => loop {
-                    if let Ok(TermEvent::Key(ev)) = crossterm::event::read() {
-                        self.handle_key_event(&ev)
+        while !self.exit_requested {
+            let timeout = match self.flash_until {
+                Some(until) => until.saturating_duration_since(Instant::now()),
+                None => Duration::from_millis(100),
+            };
+
+            if let Ok(true) = crossterm::event::poll(timeout) {
+                loop {
+                    match crossterm::event::read() {
+                        Ok(TermEvent::Key(ev)) => self.handle_key_event(&ev),
+                        Ok(TermEvent::Resize(columns, lines)) => self.resize(columns, lines),
+                        _ => (),
+                    }
+
+                    if self.exit_requested {
+                        break;
                     }
 
                     match crossterm::event::poll(Duration::from_millis(0)) {
                         Ok(true) => (),
                         _ => break,
                     }
-                },
-                _ => (),
+                }
             }
+
+            self.end_flash(false);
         }
+
+        disable_raw_mode().ok();
+        let status = self.shell.run_exit_trap(self.shell.last_status());
+        hangup_jobs(&self.shell);
+        status
     }
 
     #[cfg(test)]
@@ -391,13 +877,31 @@ impl SmashState {
     }
 }
 
+/// The shell itself must survive keyboard-generated signals: they are
+/// delivered to the foreground job, which restores the default dispositions
+/// after fork. At the prompt, Ctrl-C and Ctrl-Z are read as key events.
+fn ignore_job_control_signals() {
+    let action = SigAction::new(SigHandler::SigIgn, SaFlags::empty(), SigSet::empty());
+    unsafe {
+        sigaction(Signal::SIGINT, &action).expect("failed to sigaction");
+        sigaction(Signal::SIGQUIT, &action).expect("failed to sigaction");
+        sigaction(Signal::SIGTSTP, &action).expect("failed to sigaction");
+        sigaction(Signal::SIGTTIN, &action).expect("failed to sigaction");
+        sigaction(Signal::SIGTTOU, &action).expect("failed to sigaction");
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::shell::{SharedBuffer, CWD_LOCK};
+    use nix::libc;
 
     fn create_smash_state() -> SmashState {
         let shell = Shell::new();
-        SmashState::new(shell)
+        let mut m = SmashState::new(shell);
+        m.terminal = Box::new(std::io::sink());
+        m
     }
 
     macro_rules! key_event {
@@ -471,4 +975,346 @@ mod tests {
         m.handle_key_event(&key_event!(KeyCode::Char('b'), KeyModifiers::ALT));
         assert_eq!(m.input.cursor(), 0);
     }
+
+    #[test]
+    fn kill_and_yank() {
+        let mut m = create_smash_state();
+        let ctrl = |ch| key_event!(KeyCode::Char(ch), KeyModifiers::CONTROL);
+        m.input_str("echo foo bar");
+        m.handle_key_event(&ctrl('w'));
+        assert_eq!(m.input.as_str(), "echo foo ");
+        // Consecutive kills are yanked at once.
+        m.handle_key_event(&ctrl('w'));
+        assert_eq!(m.input.as_str(), "echo ");
+        m.handle_key_event(&ctrl('y'));
+        assert_eq!(m.input.as_str(), "echo foo bar");
+        assert_eq!(m.input.cursor(), 12);
+
+        m.handle_key_event(&ctrl('a'));
+        m.handle_key_event(&key_event!(KeyCode::Right, KeyModifiers::NONE));
+        m.handle_key_event(&ctrl('k'));
+        assert_eq!(m.input.as_str(), "e");
+        m.handle_key_event(&ctrl('u'));
+        assert_eq!(m.input.as_str(), "");
+
+        // Alt-Y rotates the kill ring.
+        m.handle_key_event(&ctrl('y'));
+        assert_eq!(m.input.as_str(), "echo foo bar");
+        m.handle_key_event(&key_event!(KeyCode::Char('y'), KeyModifiers::ALT));
+        assert_eq!(m.input.as_str(), "foo bar");
+        m.handle_key_event(&key_event!(KeyCode::Char('y'), KeyModifiers::ALT));
+        assert_eq!(m.input.as_str(), "echo foo bar");
+
+        // Alt-Y does nothing unless the last key yanked.
+        m.handle_key_event(&key_event!(KeyCode::Left, KeyModifiers::NONE));
+        m.handle_key_event(&key_event!(KeyCode::Char('y'), KeyModifiers::ALT));
+        assert_eq!(m.input.as_str(), "echo foo bar");
+    }
+
+    #[test]
+    fn kill_multibyte_text() {
+        let mut m = create_smash_state();
+        m.input_str("echo あい う");
+        m.handle_key_event(&key_event!(KeyCode::Left, KeyModifiers::NONE));
+        m.handle_key_event(&key_event!(KeyCode::Char('w'), KeyModifiers::CONTROL));
+        assert_eq!(m.input.as_str(), "echo う");
+        m.handle_key_event(&key_event!(KeyCode::Char('e'), KeyModifiers::CONTROL));
+        assert_eq!(m.input.cursor(), 6);
+    }
+
+    #[test]
+    fn tab_completion() {
+        let mut m = create_smash_state();
+        m.input_str("ech\t");
+        assert_eq!(m.input.as_str(), "echo ");
+
+        // The cursor is in the middle of the line.
+        m.input.clear();
+        m.input_str("ec x");
+        m.input.move_by(-2);
+        m.input_str("\t");
+        assert_eq!(m.input.as_str(), "echo  x");
+        assert_eq!(m.input.cursor(), 5);
+    }
+
+    #[test]
+    fn complete_with_function() {
+        let terminal = SharedBuffer::new();
+        let mut m = create_smash_state();
+        m.terminal = Box::new(terminal.clone());
+        m.shell
+            .run_script("_fruits() { COMPREPLY=(apple apricot); }; complete -F _fruits eat");
+        m.input_str("eat a\t");
+        assert_eq!(m.input.as_str(), "eat ap");
+
+        // The candidates are listed if the word can't be extended.
+        terminal.clear();
+        m.input_str("\t");
+        assert_eq!(m.input.as_str(), "eat ap");
+        assert!(terminal.contents().contains("apple"));
+        assert!(terminal.contents().contains("apricot"));
+
+        // The function sees the words and the index of the one completed.
+        m.input.clear();
+        m.shell.run_script(
+            "_cword() { COMPREPLY=(\"${COMP_WORDS[0]}-$COMP_CWORD\"); }; complete -F _cword eat",
+        );
+        m.input_str("eat x y\t");
+        assert_eq!(m.input.as_str(), "eat x eat-2 ");
+    }
+
+    #[test]
+    fn prompt_command() {
+        let mut m = create_smash_state();
+        m.shell.run_script("PROMPT_COMMAND='prompts=x$prompts'");
+        m.input_str("/bin/false\n");
+        assert_eq!(m.shell.get("prompts").unwrap().as_str(), "x");
+        assert_eq!(m.shell.last_status(), 1);
+
+        m.input_str("/bin/true\n");
+        m.handle_key_event(&key_event!(KeyCode::Char('c'), KeyModifiers::CONTROL));
+        assert_eq!(m.shell.get("prompts").unwrap().as_str(), "xxx");
+    }
+
+    #[test]
+    fn history_expansion() {
+        let stderr = SharedBuffer::new();
+        let shell = Shell::with_io(std::io::empty(), std::io::sink(), stderr.clone());
+        let mut m = SmashState::new(shell);
+        m.terminal = Box::new(std::io::sink());
+        m.input_str("history_test=one\n");
+        m.input_str("!!x\n");
+        assert_eq!(m.shell.get("history_test").unwrap().as_str(), "onex");
+        assert_eq!(m.shell.history().get(2), Some("history_test=onex"));
+
+        // The line is discarded if the event is not found.
+        m.input_str("!nothing\n");
+        assert_eq!(stderr.contents(), "smash: !nothing: event not found\n");
+        assert_eq!(m.shell.history().len(), 2);
+        assert!(m.input.is_empty());
+    }
+
+    #[test]
+    fn report_finished_background_jobs() {
+        let stderr = SharedBuffer::new();
+        let shell = Shell::with_io(std::io::empty(), std::io::sink(), stderr.clone());
+        let mut m = SmashState::new(shell);
+        m.terminal = Box::new(std::io::sink());
+        m.input_str("/bin/true &\n");
+
+        // Wait until the background job exits, leaving it to the shell to reap.
+        // It has been reported already if it exited before the prompt.
+        if let Some(job) = m.shell.current_job() {
+            // SAFETY: `waitid` fills the zeroed `siginfo_t`.
+            unsafe {
+                let mut info: libc::siginfo_t = std::mem::zeroed();
+                let options = libc::WEXITED | libc::WNOWAIT;
+                let id = job.pgid.as_raw() as libc::id_t;
+                assert_eq!(libc::waitid(libc::P_PID, id, &mut info, options), 0);
+            }
+        }
+
+        m.input_str("/bin/true\n");
+        assert_eq!(stderr.contents(), "[1] Done: /bin/true\n");
+        assert!(m.shell.jobs().is_empty());
+    }
+
+    #[test]
+    fn cursor_position_math() {
+        // A prompt 2 columns wide in a terminal 10 columns wide.
+        assert_eq!(cursor_position(2, "", 10), (0, 2));
+        assert_eq!(cursor_position(2, "abcdefg", 10), (0, 9));
+        assert_eq!(cursor_position(2, "abcdefgh", 10), (1, 0));
+        assert_eq!(cursor_position(2, "abcdefghijklm", 10), (1, 5));
+        assert_eq!(cursor_position(10, "", 10), (1, 0));
+
+        // Continuation lines start at the left edge.
+        assert_eq!(cursor_position(2, "if true\nthen", 10), (1, 4));
+        assert_eq!(cursor_position(2, "ab\n", 10), (1, 0));
+
+        // A wide character which doesn't fit wraps as a whole.
+        assert_eq!(cursor_position(2, "あいう", 10), (0, 8));
+        assert_eq!(cursor_position(2, "あいうえ", 10), (1, 0));
+        assert_eq!(cursor_position(3, "あいうえ", 10), (1, 2));
+
+        // Combining marks take no space.
+        assert_eq!(cursor_position(0, "e\u{301}", 10), (0, 1));
+        assert_eq!(str_width("~/src $ "), 8);
+    }
+
+    #[test]
+    fn resize_updates_width() {
+        let mut m = create_smash_state();
+        m.resize(40, 10);
+        assert_eq!((m.columns, m.lines), (40, 10));
+        assert_eq!(m.shell.get("COLUMNS").unwrap().as_str(), "40");
+
+        m.resize(120, 30);
+        assert_eq!(m.columns, 120);
+        assert_eq!(m.shell.get("LINES").unwrap().as_str(), "30");
+    }
+
+    #[test]
+    fn interrupt_discards_input() {
+        let mut m = create_smash_state();
+        m.handle_key_event(&key_event!(KeyCode::Char('c'), KeyModifiers::CONTROL));
+        assert!(m.input.is_empty());
+        assert_eq!(m.shell.last_status(), 130);
+
+        m.input_str("sleep 1");
+        m.handle_key_event(&key_event!(KeyCode::Char('c'), KeyModifiers::CONTROL));
+        assert!(m.input.is_empty());
+        assert_eq!(m.input.cursor(), 0);
+
+        // The reader keeps working after interrupts.
+        m.input_str("ls");
+        assert_eq!(m.input.as_str(), "ls");
+    }
+
+    #[test]
+    fn eof() {
+        let mut m = create_smash_state();
+        m.input_str("abc");
+        m.handle_key_event(&key_event!(KeyCode::Left, KeyModifiers::NONE));
+        m.handle_key_event(&key_event!(KeyCode::Char('d'), KeyModifiers::CONTROL));
+        assert!(!m.exit_requested);
+        assert_eq!(m.input.as_str(), "ab");
+
+        m.input.clear();
+        m.shell.set_option("ignoreeof", true);
+        m.handle_key_event(&key_event!(KeyCode::Char('d'), KeyModifiers::CONTROL));
+        assert!(!m.exit_requested);
+
+        m.shell.set_option("ignoreeof", false);
+        m.handle_key_event(&key_event!(KeyCode::Char('d'), KeyModifiers::CONTROL));
+        assert!(m.exit_requested);
+    }
+
+    #[test]
+    fn vi_mode() {
+        let mut m = create_smash_state();
+        assert!(m.shell.is_option_set("emacs"));
+        m.shell.set_option("vi", true);
+        assert!(!m.shell.is_option_set("emacs"));
+        let esc = key_event!(KeyCode::Esc, KeyModifiers::NONE);
+
+        m.input_str("echo hello world");
+        m.handle_key_event(&esc);
+        assert_eq!(m.input.cursor(), 15);
+        m.input_str("0w");
+        assert_eq!(m.input.cursor(), 5);
+        m.input_str("x");
+        assert_eq!(m.input.as_str(), "echo ello world");
+        m.input_str("iH");
+        assert_eq!(m.input.as_str(), "echo Hello world");
+
+        m.handle_key_event(&esc);
+        assert_eq!(m.input.cursor(), 5);
+        m.input_str("hhl");
+        assert_eq!(m.input.cursor(), 4);
+        m.input_str("$a!");
+        assert_eq!(m.input.as_str(), "echo Hello world!");
+
+        m.handle_key_event(&esc);
+        m.input_str("bx");
+        assert_eq!(m.input.as_str(), "echo Hello orld!");
+        m.input_str("$x");
+        assert_eq!(m.input.as_str(), "echo Hello orld");
+        assert_eq!(m.input.cursor(), 14);
+        m.input_str("dd");
+        assert_eq!(m.input.as_str(), "");
+
+        // Back to the insert mode.
+        m.input_str("iabc");
+        assert_eq!(m.input.as_str(), "abc");
+    }
+
+    #[test]
+    fn bell_style() {
+        let terminal = SharedBuffer::new();
+        let mut m = create_smash_state();
+        m.terminal = Box::new(terminal.clone());
+
+        // The input is redrawn after the bell.
+        let bell = |terminal: &SharedBuffer| {
+            let contents = terminal.contents();
+            contents[..contents.find("\x1b[?25l").unwrap()].to_owned()
+        };
+
+        m.input_str("no_such_command_xyz");
+        terminal.clear();
+        m.input_str("\t");
+        assert_eq!(m.input.as_str(), "no_such_command_xyz");
+        assert_eq!(bell(&terminal), "\x07");
+
+        terminal.clear();
+        m.shell.run_script("BELL_STYLE=visible");
+        m.input_str("\t");
+        assert_eq!(bell(&terminal), "\x1b[?5h");
+        m.flash_until = Some(Instant::now());
+        m.end_flash(false);
+        assert!(terminal.contents().ends_with("\x1b[?5l"));
+
+        terminal.clear();
+        m.shell.run_script("BELL_STYLE=none");
+        m.input_str("\t");
+        assert_eq!(bell(&terminal), "");
+
+        // Editing errors.
+        m.shell.run_script("BELL_STYLE=audible");
+        m.input.clear();
+        terminal.clear();
+        m.handle_key_event(&key_event!(KeyCode::Backspace, KeyModifiers::NONE));
+        assert_eq!(bell(&terminal), "\x07");
+    }
+
+    #[test]
+    fn draw_to_terminal() {
+        let terminal = SharedBuffer::new();
+        let mut m = create_smash_state();
+        m.terminal = Box::new(terminal.clone());
+        m.shell.run_script("PS1='> '");
+        m.render_prompt();
+        assert!(terminal.contents().ends_with("> "));
+        m.input_str("ab");
+        assert!(terminal.contents().contains("ab"));
+
+        terminal.clear();
+        m.handle_key_event(&key_event!(KeyCode::Char('c'), KeyModifiers::CONTROL));
+        assert!(terminal.contents().starts_with("^C\r\n"));
+    }
+
+    #[test]
+    fn recall_history() {
+        let _lock = CWD_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let mut m = create_smash_state();
+        let up = key_event!(KeyCode::Up, KeyModifiers::NONE);
+        let down = key_event!(KeyCode::Down, KeyModifiers::NONE);
+        let cwd = std::env::current_dir().unwrap();
+        let here = cwd.to_str().unwrap();
+        let history = m.shell.history_mut();
+        history.push_in_dir("make", Some(here));
+        history.push_in_dir("ls elsewhere", Some("/nonexistent/dir"));
+        history.push_in_dir("make test", Some(here));
+
+        m.input_str("ec");
+        m.handle_key_event(&up);
+        assert_eq!(m.input.as_str(), "make test");
+        m.handle_key_event(&up);
+        assert_eq!(m.input.as_str(), "ls elsewhere");
+        m.handle_key_event(&down);
+        m.handle_key_event(&down);
+        assert_eq!(m.input.as_str(), "ec");
+
+        // Only the commands entered in the current directory.
+        m.shell.run_script("shopt -s dirhistory");
+        m.handle_key_event(&up);
+        assert_eq!(m.input.as_str(), "make test");
+        m.handle_key_event(&up);
+        assert_eq!(m.input.as_str(), "make");
+        m.handle_key_event(&up);
+        assert_eq!(m.input.as_str(), "make");
+        m.handle_key_event(&down);
+        assert_eq!(m.input.as_str(), "make test");
+    }
 }
@@ -1,70 +1,1096 @@
-use crate::parser::Span;
-use crate::parser::Word;
+use crate::eval::run_terms;
+use crate::glob::{escape, glob, is_pattern, matches, GlobOptions};
+use crate::parser::{BinaryOp, Expr, Index, ParamOp, Span, Term, Word};
+use crate::process::{ExitStatus, FdReader};
 use crate::shell::Shell;
+use crate::variable::{is_valid_name, Value};
 
+use nix::fcntl::{fcntl, FcntlArg, FdFlag, OFlag};
+use nix::sys::wait::{waitpid, WaitStatus};
+use nix::unistd::{close, dup2, fork, pipe2, ForkResult, User};
+use std::io::{Read, Write};
+use thiserror::Error;
 use tracing::debug;
 
+/// Expanding an unset parameter with `set -u`: a non-interactive shell exits
+/// on it.
+#[derive(Debug, Error)]
+#[error("{0}: unbound variable")]
+pub struct UnboundVariable(String);
+
+/// Expands words into fields in the order below:
+///
+/// 1. brace expansion
+/// 2. tilde expansion, parameter expansion, command substitution, and
+///    arithmetic expansion
+/// 3. field splitting
+/// 4. pathname expansion
+/// 5. quote removal
+///
+/// The results of an expansion are not subject to the earlier ones: e.g.
+/// `x='{a,b}'; echo $x` prints `{a,b}`.
 pub fn expand_words(shell: &mut Shell, words: &[Word]) -> anyhow::Result<Vec<String>> {
     debug!("expand_words: {:?}", words);
     let mut evaluated = Vec::new();
     for word in words {
-        let mut ws = Vec::new();
-        for w in expand_word_into_vec(shell, word, &shell.ifs())? {
-            debug!("w: {:?}", w);
-            ws.push(w);
+        for word in expand_braces(word) {
+            let fields = expand_word_into_fields(shell, &word, &shell.ifs())?;
+            evaluated.extend(expand_pathnames(shell, fields));
         }
-
-        evaluated.extend(ws);
     }
 
     debug!("expand_words: {:?}", evaluated);
     Ok(evaluated)
 }
 
+/// Brace expansion: `a{b,c}d` becomes `abd` and `acd`. Braces in
+/// alternatives are expanded as well.
+fn expand_braces(word: &Word) -> Vec<Word> {
+    let mut words = vec![Vec::new()];
+    for span in word.spans() {
+        match span {
+            Span::Brace { alternatives, .. } => {
+                let alternatives: Vec<Word> = alternatives.iter().flat_map(expand_braces).collect();
+                words = words
+                    .iter()
+                    .flat_map(|prefix: &Vec<Span>| {
+                        alternatives.iter().map(move |alternative| {
+                            [prefix.as_slice(), alternative.spans()].concat()
+                        })
+                    })
+                    .collect();
+            }
+            _ => {
+                for word in &mut words {
+                    word.push(span.clone());
+                }
+            }
+        }
+    }
+
+    words.into_iter().map(Word).collect()
+}
+
+/// Pathname expansion and quote removal.
+fn expand_pathnames(shell: &Shell, fields: Vec<Field>) -> Vec<String> {
+    let options = shell.glob_options();
+    let mut expanded = Vec::new();
+    for field in fields {
+        debug!("field: {:?}", field);
+        if field.has_glob && !shell.is_option_set("noglob") {
+            let paths = glob(&field.pattern, &options);
+            if !paths.is_empty() {
+                expanded.extend(paths);
+                continue;
+            }
+
+            // No matches: the pattern is left as it is unless `nullglob`.
+            if shell.is_shopt_set("nullglob") {
+                continue;
+            }
+        }
+
+        expanded.push(field.text);
+    }
+
+    expanded
+}
+
+/// An expanded word.
+#[derive(Debug, Default)]
+struct Field {
+    text: String,
+    /// `text` as a pathname pattern where quoted characters are escaped.
+    pattern: String,
+    /// Whether `pattern` contains unquoted special characters.
+    has_glob: bool,
+}
+
+impl Field {
+    fn push_quoted(&mut self, s: &str) {
+        self.text.push_str(s);
+        self.pattern.push_str(&escape(s));
+    }
+
+    fn push_unquoted(&mut self, s: &str) {
+        self.text.push_str(s);
+        self.pattern.push_str(s);
+        self.has_glob |= is_pattern(s);
+    }
+}
+
+/// Expands a word without pathname expansion.
 pub fn expand_word_into_vec(
-    _shell: &mut Shell,
+    shell: &mut Shell,
     word: &Word,
     ifs: &str,
 ) -> anyhow::Result<Vec<String>> {
+    let fields = expand_word_into_fields(shell, word, ifs)?;
+    Ok(fields.into_iter().map(|field| field.text).collect())
+}
+
+fn expand_word_into_fields(
+    shell: &mut Shell,
+    word: &Word,
+    ifs: &str,
+) -> anyhow::Result<Vec<Field>> {
     let mut words = Vec::new();
-    let mut current_word = Vec::new();
+    let mut current_word = Field::default();
+    // Whether `current_word` is a word even if it's empty (e.g. `""`).
+    let mut has_word = false;
     for span in word.spans() {
-        let (frags, expand) = match span {
+        let (values, quoted) = match span {
             Span::LiteralChars(..) => {
                 unreachable!()
             }
-            Span::Literal(s) => (vec![s.clone()], false),
-        };
+            Span::Literal(s) => {
+                current_word.push_quoted(s);
+                has_word = true;
+                continue;
+            }
+            Span::Tilde(user) => {
+                current_word.push_quoted(&expand_tilde(shell, user.as_deref()));
+                has_word = true;
+                continue;
+            }
+            Span::Brace { code, .. } => {
+                current_word.push_quoted(code);
+                has_word = true;
+                continue;
+            }
+            Span::AnyString | Span::AnyChar => {
+                let s = if *span == Span::AnyString { "*" } else { "?" };
+                current_word.push_unquoted(s);
+                has_word = true;
+                continue;
+            }
+            Span::Bracket(s) => {
+                current_word.push_unquoted(s);
+                has_word = true;
+                continue;
+            }
+            Span::ExtGlob(s) => {
+                if shell.is_shopt_set("extglob") {
+                    current_word.push_unquoted(s);
+                } else {
+                    current_word.push_quoted(s);
+                }
+                has_word = true;
+                continue;
+            }
+            Span::Parameter {
+                name,
+                index,
+                indirect,
+                op,
+                quoted,
+            } => {
+                let target;
+                let (name, index) = if *indirect {
+                    // `${!name}`: the value is the name of the parameter to expand.
+                    target = expand_param(shell, name, index, ifs)?.join(" ");
+                    (target.as_str(), &None)
+                } else {
+                    (name.as_str(), index)
+                };
 
-        let frags_len = frags.len();
-        for frag in frags {
-            if expand {
-                if !current_word.is_empty() {
-                    words.push(current_word.into_iter().collect::<String>());
-                    current_word = Vec::new();
+                let mut values = match op {
+                    Some(ParamOp::Default {
+                        word,
+                        colon,
+                        assign,
+                    }) => expand_default(shell, name, index, word, *colon, *assign, ifs)?,
+                    _ if name.is_empty() => vec![String::new()],
+                    _ => expand_param(shell, name, index, ifs)?,
+                };
+
+                match op {
+                    Some(ParamOp::Length)
+                        if matches!(index, Some(Index::All | Index::AllJoined)) =>
+                    {
+                        values = vec![values.len().to_string()];
+                    }
+                    Some(ParamOp::Length) => {
+                        values = vec![values.join(" ").chars().count().to_string()];
+                    }
+                    Some(ParamOp::Default { .. }) => (),
+                    Some(op) => values = apply_param_op(shell, op, values)?,
+                    None => (),
                 }
 
-                for word in frag.split(|c| ifs.contains(c)) {
-                    words.push(word.to_string());
+                (values, *quoted)
+            }
+            Span::ProcessSubstitution { body, output } => (
+                vec![expand_process_substitution(shell, body, *output)?],
+                true,
+            ),
+            Span::Command { body, quoted } => {
+                (vec![expand_command_substitution(shell, body)?], *quoted)
+            }
+            Span::ReadFile { path, quoted } => (vec![read_file(shell, path)?], *quoted),
+        };
+
+        for (i, value) in values.iter().enumerate() {
+            // Each element of `${name[@]}` is a separate word.
+            if i > 0 && (has_word || quoted) {
+                words.push(std::mem::take(&mut current_word));
+                has_word = false;
+            }
+
+            if quoted {
+                current_word.push_quoted(value);
+                has_word = true;
+                continue;
+            }
+
+            // Split the unquoted expansion into fields by IFS.
+            if value.starts_with(|c| is_ifs_whitespace(c, ifs)) && has_word {
+                words.push(std::mem::take(&mut current_word));
+                has_word = false;
+            }
+
+            for (j, field) in split_fields(value, ifs, None).iter().enumerate() {
+                if j > 0 {
+                    words.push(std::mem::take(&mut current_word));
                 }
-            } else {
-                current_word.push(frag);
+
+                current_word.push_unquoted(field);
+                has_word = true;
             }
 
-            if frags_len > 1 && !current_word.is_empty() {
-                words.push(current_word.into_iter().collect::<String>());
-                current_word = Vec::new();
+            if value.ends_with(|c| ifs.contains(c)) && has_word {
+                words.push(std::mem::take(&mut current_word));
+                has_word = false;
             }
         }
     }
 
-    if !current_word.is_empty() {
-        words.push(current_word.into_iter().collect::<String>());
+    if has_word {
+        words.push(current_word);
     }
 
-    if words.is_empty() {
-        Ok(vec![String::new()])
+    Ok(words)
+}
+
+/// Expands a word into a pathname pattern where quoted characters match
+/// themselves.
+fn expand_word_into_pattern(shell: &mut Shell, word: &Word) -> anyhow::Result<String> {
+    let fields = expand_word_into_fields(shell, word, "")?;
+    Ok(fields
+        .into_iter()
+        .map(|field| field.pattern)
+        .collect::<Vec<_>>()
+        .join(" "))
+}
+
+/// Expands a word into a string without field splitting (e.g. the value of
+/// an assignment).
+pub fn expand_word_into_string(shell: &mut Shell, word: &Word) -> anyhow::Result<String> {
+    Ok(expand_word_into_vec(shell, word, "")?.join(" "))
+}
+
+fn is_ifs_whitespace(c: char, ifs: &str) -> bool {
+    c.is_ascii_whitespace() && ifs.contains(c)
+}
+
+/// Splits `s` into fields by `ifs`. Consecutive IFS whitespaces are a
+/// separator and so is any other IFS character with whitespaces around it
+/// (i.e. `a::b` contains an empty field). If `max_fields` is given, the last
+/// field contains the rest of the string as in `read`.
+pub fn split_fields(s: &str, ifs: &str, max_fields: Option<usize>) -> Vec<String> {
+    let is_whitespace = |c| is_ifs_whitespace(c, ifs);
+    let mut fields = Vec::new();
+    let mut rest = s.trim_start_matches(is_whitespace);
+    while !rest.is_empty() {
+        if max_fields == Some(fields.len() + 1) {
+            fields.push(rest.trim_end_matches(is_whitespace).to_owned());
+            break;
+        }
+
+        let end = rest.find(|c| ifs.contains(c)).unwrap_or(rest.len());
+        fields.push(rest[..end].to_owned());
+        rest = rest[end..].trim_start_matches(is_whitespace);
+        if let Some(remaining) = rest.strip_prefix(|c| ifs.contains(c) && !is_whitespace(c)) {
+            rest = remaining.trim_start_matches(is_whitespace);
+        }
+    }
+
+    fields
+}
+
+/// Runs `$(...)` in a subshell and returns its output without trailing
+/// newlines.
+fn expand_command_substitution(shell: &mut Shell, body: &[Term]) -> anyhow::Result<String> {
+    let (pipe_out, pipe_in) = pipe2(OFlag::O_CLOEXEC)?;
+    // Don't let the child print what we have buffered.
+    shell.stdout().flush().ok();
+    std::io::stdout().flush().ok();
+    match unsafe { fork() }? {
+        ForkResult::Child => {
+            close(pipe_out).ok();
+            dup2(pipe_in, 1).ok();
+            close(pipe_in).ok();
+            shell.enter_subshell();
+            let status = match run_terms(shell, body) {
+                ExitStatus::ExitedWith(status) => status,
+                ExitStatus::Running(_) => 0,
+            };
+
+            shell.stdout().flush().ok();
+            std::process::exit(status);
+        }
+        ForkResult::Parent { child } => {
+            close(pipe_in).ok();
+            let mut output = Vec::new();
+            let result = FdReader(pipe_out).read_to_end(&mut output);
+            close(pipe_out).ok();
+            if let Ok(WaitStatus::Exited(_, status)) = waitpid(child, None) {
+                shell.set_last_status(status);
+            }
+
+            shell.sync_pwd();
+
+            result?;
+            let output = String::from_utf8_lossy(&output);
+            Ok(output.trim_end_matches('\n').to_owned())
+        }
+    }
+}
+
+/// Reads the file in `$(<file)`. Like a failed command, a file which can't
+/// be read expands to an empty string.
+fn read_file(shell: &mut Shell, path: &Word) -> anyhow::Result<String> {
+    let path = expand_word_into_string(shell, path)?;
+    match std::fs::read(&path) {
+        Ok(contents) => {
+            shell.set_last_status(0);
+            let contents = String::from_utf8_lossy(&contents);
+            Ok(contents.trim_end_matches('\n').to_owned())
+        }
+        Err(err) => {
+            writeln!(shell.stderr(), "smash: {}: {}", path, err).ok();
+            shell.set_last_status(1);
+            Ok(String::new())
+        }
+    }
+}
+
+/// Runs `<(...)` or `>(...)` in a subshell connected to a pipe and returns
+/// the path to the other end. The shell closes it after the command (see
+/// `Shell::finish_process_substitutions`).
+fn expand_process_substitution(
+    shell: &mut Shell,
+    body: &[Term],
+    output: bool,
+) -> anyhow::Result<String> {
+    let (pipe_out, pipe_in) = pipe2(OFlag::O_CLOEXEC)?;
+    let (shell_end, child_end, child_fd) = if output {
+        (pipe_in, pipe_out, 0)
     } else {
-        Ok(words)
+        (pipe_out, pipe_in, 1)
+    };
+
+    shell.stdout().flush().ok();
+    std::io::stdout().flush().ok();
+    match unsafe { fork() }? {
+        ForkResult::Child => {
+            // Other substitutions in the command must see EOF without us.
+            close(shell_end).ok();
+            for fd in shell.process_substitution_fds() {
+                close(fd).ok();
+            }
+
+            dup2(child_end, child_fd).ok();
+            close(child_end).ok();
+            shell.enter_subshell();
+            let status = match run_terms(shell, body) {
+                ExitStatus::ExitedWith(status) => status,
+                ExitStatus::Running(_) => 0,
+            };
+
+            shell.stdout().flush().ok();
+            std::process::exit(status);
+        }
+        ForkResult::Parent { child } => {
+            close(child_end).ok();
+            // Inherited by the command.
+            fcntl(shell_end, FcntlArg::F_SETFD(FdFlag::empty()))?;
+            shell.add_process_substitution(shell_end, child);
+            Ok(format!("/dev/fd/{}", shell_end))
+        }
+    }
+}
+
+/// Expands `~` into `$HOME` and `~user` into the user's home directory. It's
+/// left as it is if the user does not exist.
+fn expand_tilde(shell: &Shell, user: Option<&str>) -> String {
+    let home_dir = match user {
+        None => shell.home_dir(),
+        Some(name) => User::from_name(name)
+            .ok()
+            .flatten()
+            .map(|user| user.dir.to_string_lossy().into_owned()),
+    };
+
+    home_dir.unwrap_or_else(|| format!("~{}", user.unwrap_or_default()))
+}
+
+/// Returns the value of a parameter computed on every reference.
+fn dynamic_param(shell: &mut Shell, name: &str) -> Option<String> {
+    match name {
+        "?" => Some(shell.last_status().to_string()),
+        "RANDOM" => Some(shell.random().to_string()),
+        "SECONDS" => Some(shell.seconds().to_string()),
+        "LINENO" => Some(shell.lineno().to_string()),
+        "#" => Some(shell.args().len().to_string()),
+        "0" => Some("smash".to_owned()),
+        // An unset one is left to `expand_param` as an unset variable.
+        _ if name.bytes().all(|b| b.is_ascii_digit()) => {
+            let n: usize = name.parse().ok()?;
+            n.checked_sub(1).and_then(|i| shell.args().get(i)).cloned()
+        }
+        _ => None,
+    }
+}
+
+fn apply_param_op(
+    shell: &mut Shell,
+    op: &ParamOp,
+    values: Vec<String>,
+) -> anyhow::Result<Vec<String>> {
+    let (upper, all, pattern) = match op {
+        ParamOp::Upper { all, pattern } => (true, *all, pattern),
+        ParamOp::Lower { all, pattern } => (false, *all, pattern),
+        ParamOp::Replace {
+            pattern,
+            replacement,
+            all,
+        } => {
+            let pattern = expand_word_into_pattern(shell, pattern)?;
+            let replacement = expand_word_into_string(shell, replacement)?;
+            return Ok(values
+                .iter()
+                .map(|value| replace_pattern(value, &pattern, &replacement, *all))
+                .collect());
+        }
+        ParamOp::Length | ParamOp::Default { .. } => unreachable!(),
+    };
+
+    let pattern = match pattern {
+        Some(word) => expand_word_into_pattern(shell, word)?,
+        None => "?".to_owned(),
+    };
+
+    let options = GlobOptions {
+        dotglob: true,
+        ..Default::default()
+    };
+    let convert = |value: &String| {
+        let mut converted = String::with_capacity(value.len());
+        for (i, ch) in value.chars().enumerate() {
+            if (i > 0 && !all) || !matches(&pattern, &ch.to_string(), &options) {
+                converted.push(ch);
+            } else if upper {
+                converted.extend(ch.to_uppercase());
+            } else {
+                converted.extend(ch.to_lowercase());
+            }
+        }
+
+        converted
+    };
+
+    Ok(values.iter().map(convert).collect())
+}
+
+/// Replaces the longest match of `pattern` (the first one or `all`) in
+/// `value` with `replacement`.
+fn replace_pattern(value: &str, pattern: &str, replacement: &str, all: bool) -> String {
+    let options = GlobOptions {
+        dotglob: true,
+        ..Default::default()
+    };
+    let bounds: Vec<usize> = value
+        .char_indices()
+        .map(|(i, _)| i)
+        .chain(std::iter::once(value.len()))
+        .collect();
+
+    let mut replaced = String::with_capacity(value.len());
+    let mut start = 0;
+    let mut k = 0;
+    while k < bounds.len() {
+        let end = bounds[k + 1..]
+            .iter()
+            .rposition(|&end| matches(pattern, &value[bounds[k]..end], &options));
+        match end {
+            Some(i) => {
+                replaced.push_str(&value[start..bounds[k]]);
+                replaced.push_str(replacement);
+                k += i + 1;
+                start = bounds[k];
+                if !all {
+                    break;
+                }
+            }
+            None => k += 1,
+        }
+    }
+
+    replaced.push_str(&value[start..]);
+    replaced
+}
+
+/// Expands `${name-word}` and the like: the values of the parameter or `word`
+/// if it's unset (or empty with `colon`). `word` is assigned to the parameter
+/// if `assign` is set.
+fn expand_default(
+    shell: &mut Shell,
+    name: &str,
+    index: &Option<Index>,
+    word: &Word,
+    colon: bool,
+    assign: bool,
+    ifs: &str,
+) -> anyhow::Result<Vec<String>> {
+    if is_param_set(shell, name) {
+        let values = expand_param(shell, name, index, ifs)?;
+        if !colon || !values.iter().all(String::is_empty) {
+            return Ok(values);
+        }
+    }
+
+    let value = expand_word_into_string(shell, word)?;
+    if assign {
+        if !is_valid_name(name) || index.is_some() {
+            anyhow::bail!("${}: cannot assign in this way", name);
+        }
+
+        shell.set(name, Value::String(value.clone()), false);
+    }
+
+    Ok(vec![value])
+}
+
+/// Returns whether a parameter is set, i.e. `${name-word}` expands to its
+/// value instead of `word`.
+fn is_param_set(shell: &Shell, name: &str) -> bool {
+    match name {
+        "@" | "*" => !shell.args().is_empty(),
+        "?" | "#" | "0" | "RANDOM" | "SECONDS" | "LINENO" => true,
+        _ if name.bytes().all(|b| b.is_ascii_digit()) => {
+            name.parse::<usize>().is_ok_and(|n| n <= shell.args().len())
+        }
+        _ => shell.get(name).is_some_and(|var| var.value().is_some()),
+    }
+}
+
+/// Returns the values of a parameter: `${name[@]}` expands to each element of
+/// the array and others expand to a value. An unset one is an error with
+/// `set -u`.
+fn expand_param(
+    shell: &mut Shell,
+    name: &str,
+    index: &Option<Index>,
+    ifs: &str,
+) -> anyhow::Result<Vec<String>> {
+    if let Some(value) = dynamic_param(shell, name) {
+        return Ok(vec![value]);
+    }
+
+    // `"$@"` expands to each positional parameter like `"${name[@]}"`.
+    match name {
+        "@" => return Ok(shell.args().to_vec()),
+        "*" => {
+            let sep = ifs.chars().next().map(String::from).unwrap_or_default();
+            return Ok(vec![shell.args().join(&sep)]);
+        }
+        _ => (),
+    }
+
+    let var = match shell.get(name) {
+        Some(var) => var,
+        None if *index == Some(Index::All) => return Ok(Vec::new()),
+        None if shell.is_option_set("nounset") => {
+            return Err(UnboundVariable(name.to_owned()).into())
+        }
+        None => return Ok(vec![String::new()]),
+    };
+
+    let elems = match var.value() {
+        Some(Value::String(value)) => vec![value.clone()],
+        Some(Value::Array(elems)) => elems.clone(),
+        None => Vec::new(),
+    };
+
+    let values = match index {
+        None => vec![var.as_str().to_owned()],
+        Some(Index::Number(n)) => vec![elems.get(*n).cloned().unwrap_or_default()],
+        Some(Index::Expr(expr)) => {
+            let n = eval_expr(shell, expr)?;
+            let i = if n < 0 { elems.len() as i64 + n } else { n };
+            let elem = usize::try_from(i).ok().and_then(|i| elems.get(i));
+            vec![elem.cloned().unwrap_or_default()]
+        }
+        Some(Index::All) => elems,
+        Some(Index::AllJoined) => {
+            let sep = ifs.chars().next().map(String::from).unwrap_or_default();
+            vec![elems.join(&sep)]
+        }
+    };
+
+    Ok(values)
+}
+
+/// Evaluates an arithmetic expression.
+pub fn eval_expr(shell: &mut Shell, expr: &Expr) -> anyhow::Result<i64> {
+    let value = match expr {
+        Expr::Number(n) => *n,
+        Expr::Variable(name) => variable_number(shell, name)?,
+        Expr::Parameter(word) => {
+            let value = expand_word_into_string(shell, word)?;
+            parse_number(&value)?
+        }
+        Expr::Assign(name, expr) => {
+            let value = eval_expr(shell, expr)?;
+            shell.set(name, Value::String(value.to_string()), false);
+            value
+        }
+        Expr::PostIncrement { name, increment } => {
+            let value = variable_number(shell, name)?;
+            let new_value = if *increment { value + 1 } else { value - 1 };
+            shell.set(name, Value::String(new_value.to_string()), false);
+            value
+        }
+        Expr::Negate(expr) => eval_expr(shell, expr)?.wrapping_neg(),
+        Expr::Binary(op, lhs, rhs) => {
+            let lhs = eval_expr(shell, lhs)?;
+            let rhs = eval_expr(shell, rhs)?;
+            match op {
+                BinaryOp::Add => lhs.wrapping_add(rhs),
+                BinaryOp::Sub => lhs.wrapping_sub(rhs),
+                BinaryOp::Mul => lhs.wrapping_mul(rhs),
+                BinaryOp::Div if rhs == 0 => anyhow::bail!("division by 0"),
+                BinaryOp::Div => lhs.wrapping_div(rhs),
+                BinaryOp::Eq => (lhs == rhs) as i64,
+                BinaryOp::Ne => (lhs != rhs) as i64,
+                BinaryOp::Ge => (lhs >= rhs) as i64,
+                BinaryOp::Gt => (lhs > rhs) as i64,
+                BinaryOp::Le => (lhs <= rhs) as i64,
+                BinaryOp::Lt => (lhs < rhs) as i64,
+            }
+        }
+    };
+
+    Ok(value)
+}
+
+/// The value of a variable in an arithmetic expression. Unset and empty ones
+/// are 0.
+fn variable_number(shell: &mut Shell, name: &str) -> anyhow::Result<i64> {
+    match shell.get(name) {
+        Some(var) => parse_number(var.as_str()),
+        None if shell.is_option_set("nounset") => Err(UnboundVariable(name.to_owned()).into()),
+        None => Ok(0),
+    }
+}
+
+fn parse_number(value: &str) -> anyhow::Result<i64> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(0);
+    }
+
+    value
+        .parse()
+        .map_err(|_| anyhow::anyhow!("{}: not a number", value))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::shell::{SharedBuffer, FD_LOCK};
+
+    fn expand(shell: &mut Shell, word: Vec<Span>) -> Vec<String> {
+        expand_word_into_vec(shell, &Word(word), &shell.ifs()).unwrap()
+    }
+
+    fn param(name: &str, quoted: bool) -> Span {
+        Span::Parameter {
+            name: name.to_owned(),
+            index: None,
+            indirect: false,
+            op: None,
+            quoted,
+        }
+    }
+
+    fn elems(name: &str, index: Index, quoted: bool) -> Span {
+        Span::Parameter {
+            name: name.to_owned(),
+            index: Some(index),
+            indirect: false,
+            op: None,
+            quoted,
+        }
+    }
+
+    #[test]
+    fn parameters() {
+        let _lock = FD_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let mut shell = Shell::new();
+        shell.set("x", Value::String(" a  b ".into()), false);
+        shell.set("y", Value::String("c".into()), false);
+
+        assert_eq!(expand(&mut shell, vec![param("x", false)]), vec!["a", "b"]);
+        assert_eq!(expand(&mut shell, vec![param("x", true)]), vec![" a  b "]);
+        assert_eq!(
+            expand(
+                &mut shell,
+                vec![
+                    Span::Literal("1".into()),
+                    param("y", false),
+                    param("x", false),
+                    param("y", false)
+                ]
+            ),
+            vec!["1c", "a", "b", "c"]
+        );
+        assert!(expand(&mut shell, vec![param("unset", false)]).is_empty());
+        assert_eq!(expand(&mut shell, vec![param("unset", true)]), vec![""]);
+    }
+
+    #[test]
+    fn arrays() {
+        let _lock = FD_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let mut shell = Shell::new();
+        shell.set(
+            "a",
+            Value::Array(vec!["x y".into(), "".into(), "z".into()]),
+            false,
+        );
+
+        assert_eq!(
+            expand(&mut shell, vec![elems("a", Index::All, true)]),
+            vec!["x y", "", "z"]
+        );
+        assert_eq!(
+            expand(&mut shell, vec![elems("a", Index::All, false)]),
+            vec!["x", "y", "z"]
+        );
+        assert_eq!(
+            expand(
+                &mut shell,
+                vec![
+                    Span::Literal("<".into()),
+                    elems("a", Index::All, true),
+                    Span::Literal(">".into())
+                ]
+            ),
+            vec!["<x y", "", "z>"]
+        );
+        assert_eq!(
+            expand(&mut shell, vec![elems("a", Index::AllJoined, true)]),
+            vec!["x y  z"]
+        );
+        assert_eq!(
+            expand(&mut shell, vec![elems("a", Index::Number(2), false)]),
+            vec!["z"]
+        );
+        assert_eq!(expand(&mut shell, vec![param("a", true)]), vec!["x y"]);
+        assert!(expand(&mut shell, vec![elems("unset", Index::All, true)]).is_empty());
+    }
+
+    #[test]
+    fn arithmetic_indexes() {
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        shell.run_script("a=(x y z); i=1; n='-1'");
+        shell.run_script("echo ${a[$i]} ${a[i + 1]} ${a[-1]} ${a[n]} ${a[3 - 1 - 1]}");
+        shell.run_script("echo ${a[i * 2 / 2 - 1]} ${a[${i} + 1]} \"[${a[i > 5]}]\"");
+        assert_eq!(stdout.contents(), "y z z z y\nx z [x]\n");
+
+        stdout.clear();
+        shell.run_script("echo ${a[i++]} ${a[i]} ${a[j = 2]} $j ${#a[-3]}");
+        assert_eq!(stdout.contents(), "y z z 2 1\n");
+
+        // Out of range.
+        stdout.clear();
+        shell.run_script("echo \"[${a[5]}]\" \"[${a[-5]}]\"");
+        assert_eq!(stdout.contents(), "[] []\n");
+
+        assert_eq!(
+            shell.run_script("echo ${a[1 / 0]}"),
+            ExitStatus::ExitedWith(1)
+        );
+        assert_eq!(
+            shell.run_script("s=abc; echo ${a[s]}"),
+            ExitStatus::ExitedWith(1)
+        );
+    }
+
+    #[test]
+    fn indirect_expansion() {
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        shell.run_script("x=y; y=5; echo ${!x} \"${!x}\"");
+        assert_eq!(stdout.contents(), "5 5\n");
+
+        stdout.clear();
+        shell.run_script("x=unset_target; echo \"[${!x}]\" \"[${!no_such_var}]\"");
+        assert_eq!(stdout.contents(), "[] []\n");
+
+        // `${!}` is the last background process.
+        stdout.clear();
+        shell.run_script("echo \"[${!}]\" \"[$!]\"");
+        let output = stdout.contents();
+        let (braced, plain) = output.trim_end().split_once(' ').unwrap();
+        assert_eq!(braced, plain);
+    }
+
+    #[test]
+    fn case_conversion() {
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        shell.run_script("x='hELLo wörld'");
+        shell.run_script("echo \"${x^^}\" \"${x,,}\" \"${x^}\" \"${x,}\"");
+        assert_eq!(
+            stdout.contents(),
+            "HELLO WÖRLD hello wörld HELLo wörld hELLo wörld\n"
+        );
+
+        stdout.clear();
+        shell.run_script("y=Abc; echo ${y,} ${x^^o} ${x^^'?'} ${x,,L}");
+        assert_eq!(
+            stdout.contents(),
+            "abc hELLO wörld hELLo wörld hEllo wörld\n"
+        );
+    }
+
+    #[test]
+    fn default_values() {
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        shell.run_script("e=; echo ${u-a} ${u:-b} \"${e-c}\" ${e:-d} ${1:-e} ${u:-}");
+        assert_eq!(stdout.contents(), "a b  d e\n");
+
+        stdout.clear();
+        shell.run_script("echo ${u=f} ${e:=g} ${x:-$e}; echo $u $e");
+        assert_eq!(stdout.contents(), "f g g\nf g\n");
+
+        stdout.clear();
+        shell.run_script("set -u; echo ${v:-h}; set +u");
+        assert_eq!(stdout.contents(), "h\n");
+        assert_eq!(shell.run_script("echo ${1:=i}"), ExitStatus::ExitedWith(1));
+    }
+
+    #[test]
+    fn pattern_replacement() {
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        shell.run_script("x=aXbXc; echo ${x/X/-} ${x//X/-} ${x//X} ${x/b*/Y} ${x//?/.}");
+        assert_eq!(stdout.contents(), "a-bXc a-b-c abc aXY .....\n");
+
+        stdout.clear();
+        shell.run_script("a=(foo bar); echo \"${a[@]/o/0}\" ${a[@]//[ab]/_}");
+        assert_eq!(stdout.contents(), "f0o bar foo __r\n");
+    }
+
+    #[test]
+    fn process_substitution() {
+        let _lock = FD_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let mut shell = Shell::new();
+        shell.run_script("x=$(/bin/cat <(echo hi) <(echo there))");
+        assert_eq!(shell.get("x").unwrap().as_str(), "hi\nthere");
+
+        shell.run_script("read y < <(echo from pipe)");
+        assert_eq!(shell.get("y").unwrap().as_str(), "from pipe");
+
+        let path = std::env::temp_dir().join(format!("smash-procsubst-{}", std::process::id()));
+        shell.run_script(&format!(
+            "/bin/echo written > >(/bin/cat > {})",
+            path.display()
+        ));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "written\n");
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn read_file_substitution() {
+        let stderr = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), std::io::sink(), stderr.clone());
+        let path = std::env::temp_dir().join(format!("smash-readfile-{}", std::process::id()));
+        std::fs::write(&path, "first line\nsecond  line\n\n").unwrap();
+        shell.run_script(&format!("f={}; x=$(<$f); y=\"$( < $f )\"", path.display()));
+        assert_eq!(shell.get("x").unwrap().as_str(), "first line\nsecond  line");
+        assert_eq!(shell.get("y").unwrap().as_str(), "first line\nsecond  line");
+        std::fs::remove_file(&path).ok();
+
+        shell.run_script("z=$(</no/such/file)");
+        assert_eq!(shell.get("z").unwrap().as_str(), "");
+        assert!(stderr.contents().starts_with("smash: /no/such/file: "));
+    }
+
+    #[test]
+    fn field_splitting() {
+        assert_eq!(split_fields(" a  b ", " \t\n", None), vec!["a", "b"]);
+        assert_eq!(split_fields("a::b:", ":", None), vec!["a", "", "b"]);
+        assert_eq!(split_fields(" a : b ", " :", None), vec!["a", "b"]);
+        assert_eq!(split_fields("a b  c ", " ", Some(2)), vec!["a", "b  c"]);
+        assert_eq!(split_fields("a b", "", None), vec!["a b"]);
+        assert!(split_fields("  ", " ", None).is_empty());
+    }
+
+    #[test]
+    fn command_substitution() {
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        shell.run_script("x=$(echo a b)");
+        assert_eq!(shell.get("x").unwrap().as_str(), "a b");
+
+        shell.run_script("IFS=:");
+        shell.run_script("echo $(echo a:b::c) \"$(echo a:b)\"");
+        assert_eq!(stdout.contents(), "a b  c a:b\n");
+    }
+
+    #[test]
+    fn dynamic_parameters() {
+        let _lock = FD_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let mut shell = Shell::new();
+        let randoms: Vec<u16> = (0..8)
+            .map(|_| {
+                expand(&mut shell, vec![param("RANDOM", false)])[0]
+                    .parse()
+                    .unwrap()
+            })
+            .collect();
+        assert!(randoms.iter().all(|n| *n <= 32767));
+        assert!(randoms.iter().any(|n| *n != randoms[0]));
+
+        shell.run_script("SECONDS=10");
+        assert_eq!(
+            expand(&mut shell, vec![param("SECONDS", false)]),
+            vec!["10"]
+        );
+        std::thread::sleep(std::time::Duration::from_millis(1100));
+        assert_eq!(
+            expand(&mut shell, vec![param("SECONDS", false)]),
+            vec!["11"]
+        );
+    }
+
+    #[test]
+    fn pathname_expansion() {
+        let dir = std::env::temp_dir().join(format!("smash-glob-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        for name in ["a.rs", "B.RS", "c.txt", ".hidden.rs", "sub/d.rs"] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        let glob = |shell: &mut Shell, pattern: &str| {
+            shell.run_script(&format!("echo {}/{}", dir.display(), pattern));
+            let output = stdout.contents();
+            stdout.clear();
+            output
+                .trim_end()
+                .replace(&format!("{}/", dir.display()), "")
+        };
+
+        assert_eq!(glob(&mut shell, "*.rs"), "a.rs");
+        assert_eq!(glob(&mut shell, "?.*"), "B.RS a.rs c.txt");
+        assert_eq!(glob(&mut shell, "*/*.rs"), "sub/d.rs");
+        assert_eq!(glob(&mut shell, "\\*.rs"), "*.rs");
+        assert_eq!(glob(&mut shell, "'*'.rs"), "*.rs");
+        assert_eq!(glob(&mut shell, "*.none"), "*.none");
+        assert_eq!(glob(&mut shell, "[a-c].*"), "a.rs c.txt");
+        assert_eq!(glob(&mut shell, "[![:lower:]]*"), "B.RS");
+        assert_eq!(glob(&mut shell, "'[a-c]'.*"), "[a-c].*");
+
+        shell.run_script("shopt -s nullglob");
+        assert_eq!(glob(&mut shell, "*.none"), "");
+        shell.run_script("shopt -s dotglob");
+        assert_eq!(glob(&mut shell, "*.rs"), ".hidden.rs a.rs");
+        shell.run_script("shopt -u dotglob");
+        shell.run_script("shopt -s nocaseglob");
+        assert_eq!(glob(&mut shell, "*.rs"), "B.RS a.rs");
+        shell.run_script("shopt -u nocaseglob nullglob");
+        assert_eq!(glob(&mut shell, "@(a|c).*"), "@(a|c).*");
+        shell.run_script("shopt -s extglob");
+        assert_eq!(glob(&mut shell, "@(a|c).*"), "a.rs c.txt");
+        assert_eq!(glob(&mut shell, "!(*.txt)"), "B.RS a.rs sub");
+        assert_eq!(glob(&mut shell, "+([a-c]).rs"), "a.rs");
+        shell.run_script("shopt -u extglob");
+
+        // `set -f` leaves patterns as they are.
+        shell.run_script("set -f");
+        assert_eq!(glob(&mut shell, "*.rs"), "*.rs");
+        shell.run_script("echo *");
+        assert_eq!(stdout.contents(), "*\n");
+        stdout.clear();
+        shell.run_script("set +f");
+        assert_eq!(glob(&mut shell, "*.rs"), "a.rs");
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn tilde_expansion() {
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        shell.set("HOME", Value::String("/home/smash".into()), false);
+        shell.run_script("X=~; Y=~/sub; P=~/a:~/b:/c; Q=a~:\"~\"/b");
+        let value = |shell: &Shell, name: &str| shell.get(name).unwrap().as_str().to_owned();
+        assert_eq!(value(&shell, "X"), "/home/smash");
+        assert_eq!(value(&shell, "Y"), "/home/smash/sub");
+        assert_eq!(value(&shell, "P"), "/home/smash/a:/home/smash/b:/c");
+        assert_eq!(value(&shell, "Q"), "a~:~/b");
+
+        shell.run_script("echo ~/x '~' ~root ~no-such-user a~ --prefix=~/usr");
+        assert_eq!(
+            stdout.contents(),
+            "/home/smash/x ~ /root ~no-such-user a~ --prefix=/home/smash/usr\n"
+        );
+    }
+
+    #[test]
+    fn expansion_order() {
+        let _lock = FD_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let dir = std::env::temp_dir().join(format!("smash-order-{}", std::process::id()));
+        let dir = dir.to_string_lossy().into_owned();
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["a1", "a2", "b1"] {
+            std::fs::write(format!("{}/{}", dir, name), "").unwrap();
+        }
+
+        let mut shell = Shell::new();
+        shell.set("HOME", Value::String("/home/smash".into()), false);
+        shell.set("d", Value::String(dir.clone()), false);
+        shell.set("x", Value::String(format!("{}/a* ~ {{a,b}}", dir)), false);
+        // Returns the words in `words=(...)` with `$d` replaced with `D`.
+        let mut words = |words: &str| -> Vec<String> {
+            shell.run_script(&format!("words=({})", words));
+            match shell.get("words").and_then(|var| var.value().clone()) {
+                Some(Value::Array(elems)) => elems
+                    .into_iter()
+                    .map(|elem| elem.replace(&dir, "D"))
+                    .collect(),
+                _ => unreachable!(),
+            }
+        };
+
+        // Brace expansion comes first and its results are not split.
+        assert_eq!(words("x{1..3}y"), vec!["x1y", "x2y", "x3y"]);
+        assert_eq!(words("{\"a b\",c}"), vec!["a b", "c"]);
+        assert_eq!(words("~/{a,b}"), vec!["/home/smash/a", "/home/smash/b"]);
+        assert_eq!(words("$d/{b,a}*"), vec!["D/b1", "D/a1", "D/a2"]);
+        assert_eq!(words("'{a,b}' \\{a,b\\}"), vec!["{a,b}", "{a,b}"]);
+
+        // Expanded values are split and globbed but not brace- or
+        // tilde-expanded.
+        assert_eq!(words("$x"), vec!["D/a1", "D/a2", "~", "{a,b}"]);
+        assert_eq!(words("\"$x\""), vec!["D/a* ~ {a,b}"]);
+        std::fs::remove_dir_all(&dir).ok();
     }
 }
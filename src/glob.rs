@@ -0,0 +1,575 @@
+//! Pathname expansion (`*.rs`, `src/?/mod.rs`, `[a-z]*`). In a pattern, a
+//! character preceded by a backslash matches itself (see `escape`).
+use std::fs::read_dir;
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct GlobOptions {
+    /// `*` and `?` match a leading `.`.
+    pub dotglob: bool,
+    /// `**` as a whole component matches any number of directories.
+    pub globstar: bool,
+    /// Match regardless of case.
+    pub nocaseglob: bool,
+    /// Recognize `?(...)`, `*(...)`, `+(...)`, `@(...)`, and `!(...)`.
+    pub extglob: bool,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum Token {
+    Char(char),
+    /// `?`
+    AnyChar,
+    /// `*`
+    AnyString,
+    /// `[...]` or `[!...]`
+    Bracket {
+        negated: bool,
+        items: Vec<BracketItem>,
+    },
+    /// `op(a|b)` where `op` is one of `?*+@!`.
+    Extended {
+        op: char,
+        alternatives: Vec<Vec<Token>>,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum BracketItem {
+    Char(char),
+    /// `a-z`
+    Range(char, char),
+    /// `[:alpha:]`
+    Class(String),
+}
+
+fn tokenize(pattern: &str, extglob: bool) -> Vec<Token> {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let token = match chars[i] {
+            op @ ('?' | '*' | '+' | '@' | '!') if extglob && chars.get(i + 1) == Some(&'(') => {
+                match parse_extended(&chars[i + 2..]) {
+                    Some((alternatives, len)) => {
+                        i += 1 + len;
+                        Token::Extended {
+                            op,
+                            alternatives: alternatives
+                                .iter()
+                                .map(|alternative| tokenize(alternative, extglob))
+                                .collect(),
+                        }
+                    }
+                    None if op == '?' => Token::AnyChar,
+                    None if op == '*' => Token::AnyString,
+                    None => Token::Char(op),
+                }
+            }
+            '\\' => {
+                i += 1;
+                Token::Char(chars.get(i).copied().unwrap_or('\\'))
+            }
+            '?' => Token::AnyChar,
+            '*' => Token::AnyString,
+            '[' => match parse_bracket(&chars[i + 1..]) {
+                Some((token, len)) => {
+                    i += len;
+                    token
+                }
+                // An unterminated `[` matches itself.
+                None => Token::Char('['),
+            },
+            c => Token::Char(c),
+        };
+
+        tokens.push(token);
+        i += 1;
+    }
+
+    tokens
+}
+
+/// Parses a bracket expression after `[`. Returns the token and the number
+/// of characters consumed including the closing `]`.
+fn parse_bracket(chars: &[char]) -> Option<(Token, usize)> {
+    let mut i = 0;
+    let negated = matches!(chars.first(), Some('!' | '^'));
+    if negated {
+        i += 1;
+    }
+
+    let mut items = Vec::new();
+    // `]` right after `[` or `[!` is a literal.
+    let start = i;
+    loop {
+        let c = *chars.get(i)?;
+        match c {
+            ']' if i > start => return Some((Token::Bracket { negated, items }, i + 1)),
+            '[' if chars.get(i + 1) == Some(&':') => {
+                let rest: String = chars[i + 2..].iter().collect();
+                let end = rest.find(":]")?;
+                let name = &rest[..end];
+                items.push(BracketItem::Class(name.to_owned()));
+                i += 2 + name.chars().count() + 2;
+                continue;
+            }
+            _ => {}
+        }
+
+        let c = if c == '\\' {
+            i += 1;
+            *chars.get(i)?
+        } else {
+            c
+        };
+
+        if chars.get(i + 1) == Some(&'-') && !matches!(chars.get(i + 2), Some(']') | None) {
+            let end = match chars[i + 2] {
+                '\\' => {
+                    i += 1;
+                    *chars.get(i + 2)?
+                }
+                end => end,
+            };
+            items.push(BracketItem::Range(c, end));
+            i += 3;
+        } else {
+            items.push(BracketItem::Char(c));
+            i += 1;
+        }
+    }
+}
+
+/// Splits the body of an extended pattern after `(` by top-level `|`s.
+/// Returns the alternatives and the number of characters consumed
+/// including the closing `)`.
+fn parse_extended(chars: &[char]) -> Option<(Vec<String>, usize)> {
+    let mut alternatives = vec![String::new()];
+    let mut depth = 0;
+    let mut i = 0;
+    loop {
+        let c = *chars.get(i)?;
+        match c {
+            ')' if depth == 0 => {
+                return Some((alternatives, i + 1));
+            }
+            '|' if depth == 0 => {
+                alternatives.push(String::new());
+                i += 1;
+                continue;
+            }
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            '\\' => {
+                alternatives.last_mut().unwrap().push(c);
+                i += 1;
+            }
+            _ => {}
+        }
+
+        alternatives.last_mut().unwrap().push(*chars.get(i)?);
+        i += 1;
+    }
+}
+
+fn class_matches(name: &str, c: char) -> bool {
+    match name {
+        "alnum" => c.is_alphanumeric(),
+        "alpha" => c.is_alphabetic(),
+        "blank" => c == ' ' || c == '\t',
+        "cntrl" => c.is_control(),
+        "digit" => c.is_ascii_digit(),
+        "graph" => c.is_ascii_graphic(),
+        "lower" => c.is_lowercase(),
+        "print" => c.is_ascii_graphic() || c == ' ',
+        "punct" => c.is_ascii_punctuation(),
+        "space" => c.is_whitespace(),
+        "upper" => c.is_uppercase(),
+        "xdigit" => c.is_ascii_hexdigit(),
+        _ => false,
+    }
+}
+
+fn bracket_matches(negated: bool, items: &[BracketItem], c: char, nocase: bool) -> bool {
+    let candidates: Vec<char> = if nocase {
+        c.to_lowercase()
+            .chain(c.to_uppercase())
+            .chain([c])
+            .collect()
+    } else {
+        vec![c]
+    };
+
+    let found = candidates.iter().any(|&c| {
+        items.iter().any(|item| match item {
+            BracketItem::Char(ch) => *ch == c,
+            BracketItem::Range(start, end) => (*start..=*end).contains(&c),
+            BracketItem::Class(name) => class_matches(name, c),
+        })
+    });
+
+    found != negated
+}
+
+/// Escapes characters in `s` so that it matches itself.
+pub fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '\\' | '*' | '?' | '[' | '(' | ')' | '|') {
+            escaped.push('\\');
+        }
+
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+/// Returns `true` if `pattern` contains an unescaped special character.
+/// Extended patterns are counted regardless of `extglob`.
+pub fn is_pattern(pattern: &str) -> bool {
+    tokenize(pattern, true)
+        .iter()
+        .any(|token| !matches!(token, Token::Char(_)))
+}
+
+/// Returns `true` if `name` matches `pattern` as a whole.
+pub fn matches(pattern: &str, name: &str, options: &GlobOptions) -> bool {
+    let tokens = tokenize(pattern, options.extglob);
+    let chars: Vec<char> = name.chars().collect();
+    if chars.first() == Some(&'.') && !options.dotglob && tokens.first() != Some(&Token::Char('.'))
+    {
+        return false;
+    }
+
+    if tokens
+        .iter()
+        .any(|token| matches!(token, Token::Extended { .. }))
+    {
+        return match_extended(&tokens, &chars, options);
+    }
+
+    // Backtrack to the last `*` on a mismatch.
+    let (mut t, mut c) = (0, 0);
+    let mut last_star = None;
+    while c < chars.len() {
+        match tokens.get(t) {
+            Some(Token::AnyString) => {
+                last_star = Some((t, c));
+                t += 1;
+            }
+            Some(token) if char_matches(token, chars[c], options) => {
+                t += 1;
+                c += 1;
+            }
+            _ => match last_star {
+                Some((star_t, star_c)) => {
+                    last_star = Some((star_t, star_c + 1));
+                    t = star_t + 1;
+                    c = star_c + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    tokens[t..].iter().all(|token| *token == Token::AnyString)
+}
+
+/// Returns `true` if `token` matches a single character `c`.
+fn char_matches(token: &Token, c: char, options: &GlobOptions) -> bool {
+    match token {
+        Token::Char(ch) if options.nocaseglob => ch.to_lowercase().eq(c.to_lowercase()),
+        Token::Char(ch) => *ch == c,
+        Token::AnyChar => true,
+        Token::Bracket { negated, items } => {
+            bracket_matches(*negated, items, c, options.nocaseglob)
+        }
+        Token::AnyString | Token::Extended { .. } => false,
+    }
+}
+
+/// A backtracking matcher for patterns with extended patterns.
+fn match_extended(tokens: &[Token], chars: &[char], options: &GlobOptions) -> bool {
+    match tokens.split_first() {
+        None => chars.is_empty(),
+        Some((Token::AnyString, rest)) => {
+            (0..=chars.len()).any(|i| match_extended(rest, &chars[i..], options))
+        }
+        Some((Token::Extended { op, alternatives }, rest)) => (0..=chars.len()).any(|i| {
+            extended_matches(*op, alternatives, &chars[..i], options)
+                && match_extended(rest, &chars[i..], options)
+        }),
+        Some((token, rest)) => match chars.split_first() {
+            Some((c, chars)) => {
+                char_matches(token, *c, options) && match_extended(rest, chars, options)
+            }
+            None => false,
+        },
+    }
+}
+
+/// Returns `true` if `chars` as a whole matches `op(alternatives)`.
+fn extended_matches(
+    op: char,
+    alternatives: &[Vec<Token>],
+    chars: &[char],
+    options: &GlobOptions,
+) -> bool {
+    let any = |chars: &[char]| {
+        alternatives
+            .iter()
+            .any(|alternative| match_extended(alternative, chars, options))
+    };
+
+    match op {
+        '?' => chars.is_empty() || any(chars),
+        '@' => any(chars),
+        '!' => !any(chars),
+        // `*(...)` and `+(...)`: repetitions.
+        _ if chars.is_empty() => op == '*' || any(chars),
+        _ => (1..=chars.len()).any(|i| {
+            any(&chars[..i])
+                && (i == chars.len() || extended_matches('*', alternatives, &chars[i..], options))
+        }),
+    }
+}
+
+/// Returns paths matching `pattern` in alphabetical order.
+pub fn glob(pattern: &str, options: &GlobOptions) -> Vec<String> {
+    let (mut paths, rest) = match pattern.strip_prefix('/') {
+        Some(rest) => (vec!["/".to_owned()], rest),
+        None => (vec![String::new()], pattern),
+    };
+
+    let components: Vec<&str> = rest.split('/').collect();
+    for (i, component) in components.iter().enumerate() {
+        let is_last = i == components.len() - 1;
+        let mut next = Vec::new();
+        for path in &paths {
+            if component.is_empty() {
+                // `a//b` or a trailing slash.
+                next.push(format!("{}/", path.trim_end_matches('/')));
+                continue;
+            }
+
+            if options.globstar && *component == "**" {
+                // `**/` matches zero or more directories; a trailing `**`
+                // matches every file and directory below `path`.
+                if !is_last {
+                    next.push(path.clone());
+                }
+                walk(path, is_last, options, &mut next);
+                continue;
+            }
+
+            let tokens = tokenize(component, options.extglob);
+            if tokens.iter().all(|token| matches!(token, Token::Char(_))) {
+                let candidate = format!("{}{}", path, unescape(&tokens));
+                next.push(if is_last {
+                    candidate
+                } else {
+                    format!("{}/", candidate)
+                });
+                continue;
+            }
+
+            let dir = if path.is_empty() { "." } else { path.as_str() };
+            let entries = match read_dir(dir) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+
+            let mut names: Vec<String> = entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| is_last || entry.path().is_dir())
+                .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                .filter(|name| matches(component, name, options))
+                .collect();
+            names.sort();
+            for name in names {
+                next.push(if is_last {
+                    format!("{}{}", path, name)
+                } else {
+                    format!("{}{}/", path, name)
+                });
+            }
+        }
+
+        paths = next;
+    }
+
+    // Literal components are not checked while walking.
+    paths.retain(|path| {
+        std::fs::symlink_metadata(path.trim_end_matches('/')).is_ok() || path == "/"
+    });
+    paths
+}
+
+/// Appends the entries below `path` in depth-first order. Directories
+/// end with `/` unless `include_files` is set. Symbolic links to
+/// directories are not followed.
+fn walk(path: &str, include_files: bool, options: &GlobOptions, paths: &mut Vec<String>) {
+    let dir = if path.is_empty() { "." } else { path };
+    let entries = match read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    let mut entries: Vec<(String, bool)> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| {
+            let is_dir = entry.file_type().map(|ty| ty.is_dir()).unwrap_or(false);
+            (entry.file_name().to_string_lossy().into_owned(), is_dir)
+        })
+        .filter(|(name, _)| options.dotglob || !name.starts_with('.'))
+        .collect();
+    entries.sort();
+    for (name, is_dir) in entries {
+        if is_dir {
+            let dir = format!("{}{}/", path, name);
+            paths.push(if include_files {
+                format!("{}{}", path, name)
+            } else {
+                dir.clone()
+            });
+            walk(&dir, include_files, options, paths);
+        } else if include_files {
+            paths.push(format!("{}{}", path, name));
+        }
+    }
+}
+
+/// Returns the characters in `tokens`, which must not be a pattern.
+fn unescape(tokens: &[Token]) -> String {
+    tokens
+        .iter()
+        .filter_map(|token| match token {
+            Token::Char(c) => Some(*c),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn match_patterns() {
+        let options = GlobOptions::default();
+        assert!(matches("*.rs", "main.rs", &options));
+        assert!(matches("m?in.*", "main.rs", &options));
+        assert!(matches("*a*b*", "xaybz", &options));
+        assert!(!matches("*.rs", "main.rsx", &options));
+        assert!(!matches("\\*", "a", &options));
+        assert!(matches("\\*", "*", &options));
+        assert!(!matches("*", ".hidden", &options));
+        assert!(matches(".*", ".hidden", &options));
+        assert!(!matches("*.RS", "main.rs", &options));
+    }
+
+    #[test]
+    fn match_brackets() {
+        let options = GlobOptions::default();
+        assert!(matches("[abc]", "b", &options));
+        assert!(!matches("[abc]", "d", &options));
+        assert!(matches("file[0-9].txt", "file7.txt", &options));
+        assert!(!matches("file[0-9].txt", "filex.txt", &options));
+        assert!(matches("[a-cx-z]*", "yes", &options));
+        assert!(matches("[!abc]", "d", &options));
+        assert!(matches("[^abc]", "d", &options));
+        assert!(!matches("[!abc]", "a", &options));
+
+        assert!(matches("[[:digit:]][[:alpha:]]", "1a", &options));
+        assert!(!matches("[[:digit:]]", "a", &options));
+        assert!(matches("[[:upper:][:space:]]*", " x", &options));
+        assert!(matches("[![:alnum:]]", "-", &options));
+
+        // `]` first in the brackets is a literal.
+        assert!(matches("[]]", "]", &options));
+        assert!(matches("[]a]", "a", &options));
+        assert!(matches("[!]]", "a", &options));
+        assert!(!matches("[!]]", "]", &options));
+        assert!(matches("[a-]", "-", &options));
+        assert!(matches("[\\]]", "]", &options));
+
+        // An unterminated bracket matches itself.
+        assert!(matches("[ab", "[ab", &options));
+        assert!(!is_pattern("\\[a]"));
+        assert!(!matches("[a]", ".", &options));
+
+        let options = GlobOptions {
+            nocaseglob: true,
+            ..Default::default()
+        };
+        assert!(matches("[a-c]", "B", &options));
+        assert!(!matches("[!a-c]", "B", &options));
+    }
+
+    #[test]
+    fn match_extended_patterns() {
+        let options = GlobOptions {
+            extglob: true,
+            ..Default::default()
+        };
+        assert!(matches("@(foo|bar)", "foo", &options));
+        assert!(matches("@(foo|bar)", "bar", &options));
+        assert!(!matches("@(foo|bar)", "foobar", &options));
+        assert!(matches("!(*.txt)", "main.rs", &options));
+        assert!(!matches("!(*.txt)", "notes.txt", &options));
+        assert!(matches("+(ab)", "abab", &options));
+        assert!(!matches("+(ab)", "", &options));
+        assert!(!matches("+(ab)", "aba", &options));
+        assert!(matches("x*(ab)y", "xy", &options));
+        assert!(matches("x*(ab|c)y", "xabcaby", &options));
+        assert!(matches("file?(.bak)", "file", &options));
+        assert!(matches("file?(.bak)", "file.bak", &options));
+        assert!(matches("*.@(rs|toml)", "Cargo.toml", &options));
+        assert!(matches("@(a|@(b|c))", "c", &options));
+        assert!(matches("@(a\\|b)", "a|b", &options));
+        assert!(!matches("!(*.txt)", ".hidden", &options));
+
+        // Without `extglob`, the parentheses are literal.
+        let options = GlobOptions::default();
+        assert!(!matches("@(foo|bar)", "foo", &options));
+        assert!(matches("@(foo|bar)", "@(foo|bar)", &options));
+        assert!(matches("?(a)", "x(a)", &options));
+    }
+
+    #[test]
+    fn globstar() {
+        let dir = std::env::temp_dir().join(format!("smash-globstar-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("a/b")).unwrap();
+        for name in ["x.rs", "a/y.rs", "a/b/z.rs", "a/b/z.txt"] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+
+        let prefix = format!("{}/", dir.display());
+        let glob = |pattern: &str, options: &GlobOptions| -> Vec<String> {
+            glob(&format!("{}{}", prefix, pattern), options)
+                .into_iter()
+                .map(|path| path.replace(&prefix, ""))
+                .collect()
+        };
+
+        // Without `globstar`, `**` is the same as `*`.
+        let mut options = GlobOptions::default();
+        assert_eq!(glob("**/*.rs", &options), vec!["a/y.rs"]);
+        assert_eq!(glob("**", &options), vec!["a", "x.rs"]);
+
+        options.globstar = true;
+        assert_eq!(
+            glob("**/*.rs", &options),
+            vec!["x.rs", "a/y.rs", "a/b/z.rs"]
+        );
+        assert_eq!(glob("a/**/z.*", &options), vec!["a/b/z.rs", "a/b/z.txt"]);
+        assert_eq!(glob("a/**/", &options), vec!["a/", "a/b/"]);
+        assert_eq!(
+            glob("**", &options),
+            vec!["a", "a/b", "a/b/z.rs", "a/b/z.txt", "a/y.rs", "x.rs"]
+        );
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
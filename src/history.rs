@@ -0,0 +1,423 @@
+//! Command lines entered at the prompt. Entries are numbered from 1.
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::time::SystemTime;
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum HistoryError {
+    #[error("{0}: event not found")]
+    EventNotFound(String),
+    #[error("^{0}: substitution failed")]
+    SubstitutionFailed(String),
+}
+
+#[derive(Debug, Default)]
+pub struct History {
+    entries: Vec<Entry>,
+    /// The number of entries in the history file already read into memory.
+    /// Entries appended by other shells are read by `read_new_lines`.
+    entries_read: usize,
+}
+
+#[derive(Debug)]
+struct Entry {
+    line: String,
+    /// The current directory where the command was entered.
+    dir: Option<String>,
+    /// When the command was entered, in seconds since the epoch.
+    time: Option<i64>,
+    /// Already in the history file: not written by `append_to_file`.
+    saved: bool,
+}
+
+impl History {
+    pub fn new() -> History {
+        History::default()
+    }
+
+    /// Appends a command line entered in the current directory. Blank lines
+    /// are ignored.
+    pub fn push(&mut self, line: &str) {
+        let dir = std::env::current_dir()
+            .ok()
+            .map(|dir| dir.to_string_lossy().into_owned());
+        self.push_in_dir(line, dir.as_deref());
+    }
+
+    pub fn push_in_dir(&mut self, line: &str, dir: Option<&str>) {
+        let line = line.trim_end_matches('\n');
+        if !line.trim().is_empty() {
+            self.entries.push(Entry {
+                line: line.to_owned(),
+                dir: dir.map(str::to_owned),
+                time: SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .ok()
+                    .map(|elapsed| elapsed.as_secs() as i64),
+                saved: false,
+            });
+        }
+    }
+
+    /// Replaces the most recent entry, e.g. `fc -s` records the command it
+    /// ran instead of itself.
+    pub fn replace_last(&mut self, line: &str) {
+        self.entries.pop();
+        self.push(line);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns the entry numbered `n`.
+    pub fn get(&self, n: usize) -> Option<&str> {
+        n.checked_sub(1)
+            .and_then(|index| self.entries.get(index))
+            .map(|entry| entry.line.as_str())
+    }
+
+    /// Returns when the entry numbered `n` was entered.
+    pub fn time(&self, n: usize) -> Option<i64> {
+        n.checked_sub(1)
+            .and_then(|index| self.entries.get(index))
+            .and_then(|entry| entry.time)
+    }
+
+    /// Returns the numbers of the entries entered in `dir`.
+    pub fn numbers_in_dir(&self, dir: &str) -> Vec<usize> {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.dir.as_deref() == Some(dir))
+            .map(|(index, _)| index + 1)
+            .collect()
+    }
+
+    /// Appends the entries in the history file at `path` to the history.
+    pub fn read_file(&mut self, path: &Path) -> std::io::Result<()> {
+        let entries = read_entries(path)?;
+        self.entries_read = entries.len();
+        self.entries.extend(entries);
+        Ok(())
+    }
+
+    /// Appends the entries added to the history file since it was last read
+    /// or written, e.g. by concurrent shells.
+    pub fn read_new_lines(&mut self, path: &Path) -> std::io::Result<()> {
+        let entries = read_entries(path)?;
+        let len = entries.len();
+        self.entries
+            .extend(entries.into_iter().skip(self.entries_read));
+        self.entries_read = len;
+        Ok(())
+    }
+
+    /// Appends the entries added since the history file was last read or
+    /// written to it. With `timestamps`, each entry is preceded by a
+    /// `#<seconds since the epoch> <directory>` line.
+    pub fn append_to_file(&mut self, path: &Path, timestamps: bool) -> std::io::Result<()> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        for entry in self.entries.iter_mut().filter(|entry| !entry.saved) {
+            write_entry(&mut file, entry, timestamps)?;
+            entry.saved = true;
+            self.entries_read += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Overwrites the history file with the history.
+    pub fn write_file(&mut self, path: &Path, timestamps: bool) -> std::io::Result<()> {
+        let mut file = File::create(path)?;
+        for entry in &mut self.entries {
+            write_entry(&mut file, entry, timestamps)?;
+            entry.saved = true;
+        }
+
+        self.entries_read = self.entries.len();
+        Ok(())
+    }
+
+    /// Returns the number of the most recent entry starting with `prefix`
+    /// among the first `len` entries.
+    pub fn find_prefix(&self, prefix: &str, len: usize) -> Option<usize> {
+        self.entries[..len.min(self.entries.len())]
+            .iter()
+            .rposition(|entry| entry.line.starts_with(prefix))
+            .map(|index| index + 1)
+    }
+
+    /// Performs history expansion (`!!`, `!N`, `!-N`, `!string`, and
+    /// `^old^new^`) on `line` before it is parsed. Returns `None` if there's
+    /// nothing to expand.
+    pub fn expand(&self, line: &str) -> Result<Option<String>, HistoryError> {
+        let len = self.len();
+        if let Some(rest) = line.strip_prefix('^') {
+            // `^old^new^`: replaces `old` in the previous command.
+            let (old, rest) = rest.split_once('^').unwrap_or((rest, ""));
+            let new = rest.strip_suffix('^').unwrap_or(rest);
+            let prev = self
+                .get(len)
+                .ok_or_else(|| HistoryError::EventNotFound("^".to_owned()))?;
+            if old.is_empty() || !prev.contains(old) {
+                return Err(HistoryError::SubstitutionFailed(old.to_owned()));
+            }
+
+            return Ok(Some(prev.replacen(old, new, 1)));
+        }
+
+        let chars: Vec<char> = line.chars().collect();
+        let mut expanded = String::with_capacity(line.len());
+        let mut in_single_quote = false;
+        let mut in_double_quote = false;
+        let mut changed = false;
+        let mut i = 0;
+        while i < chars.len() {
+            let ch = chars[i];
+            match ch {
+                '\\' if !in_single_quote => {
+                    expanded.push(ch);
+                    if let Some(next) = chars.get(i + 1) {
+                        expanded.push(*next);
+                    }
+
+                    i += 2;
+                    continue;
+                }
+                '\'' if !in_double_quote => in_single_quote = !in_single_quote,
+                '"' if !in_single_quote => in_double_quote = !in_double_quote,
+                // `$!` is a parameter and `${!name}` is an indirect expansion.
+                '!' if !in_single_quote
+                    && !chars[..i].ends_with(&['$'])
+                    && !chars[..i].ends_with(&['$', '{']) =>
+                {
+                    let (number, end) = match chars.get(i + 1) {
+                        Some('!') => (len, i + 2),
+                        Some(c) if c.is_ascii_digit() || *c == '-' => {
+                            let start = if *c == '-' { i + 2 } else { i + 1 };
+                            let end = chars[start..]
+                                .iter()
+                                .position(|c| !c.is_ascii_digit())
+                                .map(|pos| start + pos)
+                                .unwrap_or(chars.len());
+                            let n: usize = chars[start..end]
+                                .iter()
+                                .collect::<String>()
+                                .parse()
+                                .unwrap_or(0);
+                            let number = if *c == '-' {
+                                (len + 1).saturating_sub(n)
+                            } else {
+                                n
+                            };
+                            (number, end)
+                        }
+                        Some(c) if !c.is_whitespace() && !matches!(c, '=' | '(' | '"') => {
+                            let start = i + 1;
+                            let end = chars[start..]
+                                .iter()
+                                .position(|c| {
+                                    c.is_whitespace()
+                                        || matches!(c, ';' | '|' | '&' | ')' | '"' | '\'')
+                                })
+                                .map(|pos| start + pos)
+                                .unwrap_or(chars.len());
+                            if start == end {
+                                // Not an event (e.g. `hi!;`).
+                                expanded.push(ch);
+                                i += 1;
+                                continue;
+                            }
+
+                            let prefix: String = chars[start..end].iter().collect();
+                            let number = self.find_prefix(&prefix, len).unwrap_or(0);
+                            (number, end)
+                        }
+                        _ => {
+                            expanded.push(ch);
+                            i += 1;
+                            continue;
+                        }
+                    };
+
+                    let event: String = chars[i..end].iter().collect();
+                    let entry = self.get(number).ok_or(HistoryError::EventNotFound(event))?;
+                    expanded.push_str(entry);
+                    changed = true;
+                    i = end;
+                    continue;
+                }
+                _ => (),
+            }
+
+            expanded.push(ch);
+            i += 1;
+        }
+
+        Ok(changed.then_some(expanded))
+    }
+}
+
+fn write_entry(file: &mut File, entry: &Entry, timestamps: bool) -> std::io::Result<()> {
+    if let Some(time) = entry.time.filter(|_| timestamps) {
+        match &entry.dir {
+            Some(dir) => writeln!(file, "#{} {}", time, dir)?,
+            None => writeln!(file, "#{}", time)?,
+        }
+    }
+
+    writeln!(file, "{}", entry.line)
+}
+
+/// Reads the entries in a history file. A `#<seconds since the epoch>` line
+/// is the timestamp of the next entry, optionally followed by the directory
+/// where it was entered.
+fn read_entries(path: &Path) -> std::io::Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    let mut time = None;
+    let mut dir = None;
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        if let Some(marker) = line.strip_prefix('#') {
+            let (timestamp, marker_dir) = match marker.split_once(' ') {
+                Some((timestamp, marker_dir)) => (timestamp, Some(marker_dir)),
+                None => (marker, None),
+            };
+
+            if let Ok(timestamp) = timestamp.parse() {
+                time = Some(timestamp);
+                dir = marker_dir.map(str::to_owned);
+                continue;
+            }
+        }
+
+        entries.push(Entry {
+            line,
+            dir: dir.take(),
+            time: time.take(),
+            saved: true,
+        });
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn seeded_history() -> History {
+        let mut history = History::new();
+        history.push("echo one");
+        history.push("ls /tmp");
+        history.push("echo two");
+        history
+    }
+
+    #[test]
+    fn expand_events() {
+        let history = seeded_history();
+        let expand = |line| history.expand(line).unwrap();
+        assert_eq!(expand("!!"), Some("echo two".to_owned()));
+        assert_eq!(
+            expand("sudo !! | cat"),
+            Some("sudo echo two | cat".to_owned())
+        );
+        assert_eq!(expand("!1"), Some("echo one".to_owned()));
+        assert_eq!(expand("!-2; !-1"), Some("ls /tmp; echo two".to_owned()));
+        assert_eq!(expand("!ls"), Some("ls /tmp".to_owned()));
+        assert_eq!(expand("!ec"), Some("echo two".to_owned()));
+        assert_eq!(expand("^two^three^"), Some("echo three".to_owned()));
+        assert_eq!(expand("^two^three"), Some("echo three".to_owned()));
+    }
+
+    #[test]
+    fn no_expansion() {
+        let history = seeded_history();
+        let expand = |line| history.expand(line).unwrap();
+        assert_eq!(expand("echo '!!'"), None);
+        assert_eq!(expand("echo \\!!"), None);
+        assert_eq!(expand("echo ${!name}"), None);
+        assert_eq!(expand("[ ! -e a ]"), None);
+        assert_eq!(expand("echo hi!"), None);
+        assert_eq!(expand("echo hi!; ls"), None);
+        assert_eq!(expand("(echo hi!)"), None);
+        assert_eq!(expand("echo hi!|cat"), None);
+        assert_eq!(expand("echo hi!& wait"), None);
+        assert_eq!(expand("echo \"hi!'\""), None);
+        assert_eq!(expand("kill $!; wait $!"), None);
+        assert_eq!(expand("echo \"!!\""), Some("echo \"echo two\"".to_owned()));
+    }
+
+    #[test]
+    fn entries_in_dir() {
+        let mut history = History::new();
+        history.push_in_dir("make", Some("/src/a"));
+        history.push_in_dir("ls", Some("/src/b"));
+        history.push_in_dir("make test", Some("/src/a"));
+        history.push_in_dir("pwd", None);
+        assert_eq!(history.numbers_in_dir("/src/a"), vec![1, 3]);
+        assert_eq!(history.numbers_in_dir("/src/b"), vec![2]);
+        assert!(history.numbers_in_dir("/src").is_empty());
+    }
+
+    #[test]
+    fn timestamps() {
+        let mut history = History::new();
+        history.push("echo a");
+        assert!(history.time(1).unwrap() > 1_600_000_000);
+        assert_eq!(history.time(2), None);
+
+        let path = std::env::temp_dir().join(format!("smash-histtime-{}", std::process::id()));
+        std::fs::write(&path, "#1667466300\necho b\necho c\n").unwrap();
+        history.read_file(&path).unwrap();
+        assert_eq!(history.get(2), Some("echo b"));
+        assert_eq!(history.time(2), Some(1667466300));
+        assert_eq!(history.get(3), Some("echo c"));
+        assert_eq!(history.time(3), None);
+
+        history.write_file(&path, true).unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert!(written.starts_with('#'));
+        assert!(written.ends_with("\necho a\n#1667466300\necho b\necho c\n"));
+    }
+
+    #[test]
+    fn dirs_in_file() {
+        let mut history = History::new();
+        history.push_in_dir("make", Some("/src/a b"));
+        history.push_in_dir("ls", None);
+        let path = std::env::temp_dir().join(format!("smash-histdir-{}", std::process::id()));
+        history.write_file(&path, true).unwrap();
+
+        let mut read = History::new();
+        read.read_file(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(read.get(1), Some("make"));
+        assert_eq!(read.numbers_in_dir("/src/a b"), vec![1]);
+        assert_eq!(read.time(1), history.time(1));
+        assert_eq!(read.get(2), Some("ls"));
+        assert!(read.time(2).is_some());
+    }
+
+    #[test]
+    fn event_not_found() {
+        let history = seeded_history();
+        assert_eq!(
+            history.expand("!9"),
+            Err(HistoryError::EventNotFound("!9".to_owned()))
+        );
+        assert_eq!(
+            history.expand("!nothing"),
+            Err(HistoryError::EventNotFound("!nothing".to_owned()))
+        );
+        assert_eq!(
+            history.expand("^xyz^abc"),
+            Err(HistoryError::SubstitutionFailed("xyz".to_owned()))
+        );
+    }
+}
@@ -2,19 +2,25 @@ use crossterm::tty::IsTty;
 use tracing_subscriber::{self, fmt, prelude::*, EnvFilter};
 
 use event::SmashState;
+use process::{exec_shebang, run_script_file, ExitStatus};
 use shell::Shell;
-use variable::Value;
 
 #[macro_use]
 mod macros;
 
 mod builtins;
+mod columns;
+mod completion;
 mod eval;
 mod event;
 mod expand;
+mod glob;
+mod history;
 mod parser;
 mod path;
 mod process;
+mod prompt;
+mod quote;
 mod shell;
 mod variable;
 
@@ -26,11 +32,51 @@ fn main() {
 
     let mut shell = Shell::new();
 
-    for (key, value) in std::env::vars() {
-        shell.set(&key, Value::String(value.to_owned()), false);
-    }
+    shell.import_env(std::env::vars());
+
+    // An inherited `$PWD` may be stale.
+    shell.sync_pwd();
+    shell.export("PWD", true);
 
     let is_tty = std::io::stdout().is_tty();
+    let mut args: Vec<String> = std::env::args().collect();
+    // login(1) starts a login shell with `-` prepended to its name.
+    let mut login = args.first().is_some_and(|arg0| arg0.starts_with('-'));
+    if matches!(args.get(1).map(String::as_str), Some("-l" | "--login")) {
+        login = true;
+        args.remove(1);
+    }
+    shell.set_login(login);
+    if let Some(script) = args.get(1) {
+        // `smash script [arg ...]`: run the interpreter in `#!` if any.
+        shell.set_interactive(false);
+        if let Err(err) = exec_shebang(script, &args[2..]) {
+            smash_err!("{}", err);
+            let not_found = err
+                .downcast_ref::<std::io::Error>()
+                .is_some_and(|err| err.kind() == std::io::ErrorKind::NotFound);
+            std::process::exit(if not_found { 127 } else { 126 });
+        }
+
+        shell.load_startup_files();
+
+        std::process::exit(run_script_file(&mut shell, script, &args[2..]));
+    }
+
+    if !std::io::stdin().is_tty() {
+        // Read a script from a pipe or a file.
+        shell.set_interactive(false);
+        shell.load_startup_files();
+        let status = match shell.run_reader(std::io::stdin().lock()) {
+            ExitStatus::ExitedWith(status) => status,
+            ExitStatus::Running(_) => 0,
+        };
+        let status = shell.run_exit_trap(status);
+        std::process::exit(status);
+    }
+
     shell.set_interactive(is_tty);
-    SmashState::new(shell).run();
+    shell.load_startup_files();
+    let status = SmashState::new(shell).run();
+    std::process::exit(status);
 }
@@ -1,12 +1,98 @@
-use pest::iterators::Pair;
+use pest::error::InputLocation;
+use pest::iterators::{Pair, Pairs};
 use pest::Parser;
 use pest_derive::Parser;
 use tracing::debug;
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum ParseError {
-    Fatal(String),
+    Fatal(SyntaxError),
     Empty,
+    /// The script is valid so far but needs more lines (e.g. an unclosed `if`).
+    Incomplete,
+}
+
+/// A syntax error and where it is found in the script.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SyntaxError {
+    pub message: String,
+    /// The line and column (1-origin).
+    pub line: usize,
+    pub column: usize,
+}
+
+impl SyntaxError {
+    fn new(script: &str, offset: usize, message: String) -> SyntaxError {
+        let before = &script[..offset];
+        let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+        SyntaxError {
+            message,
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+
+    /// An unexpected token at `offset`.
+    fn near(script: &str, offset: usize) -> SyntaxError {
+        let rest = &script[offset..];
+        let token = if rest.starts_with('\n') {
+            "newline"
+        } else {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            &rest[..end]
+        };
+
+        SyntaxError::new(script, offset, format!("syntax error near '{}'", token))
+    }
+
+    /// The script ends in the middle of a command.
+    fn unexpected_eof(script: &str) -> SyntaxError {
+        if let Some(offset) = find_unclosed_quote(script) {
+            return SyntaxError::new(
+                script,
+                offset,
+                "syntax error: unterminated quoted string".to_owned(),
+            );
+        }
+
+        let trimmed = script.trim_end();
+        for op in ["&&", "||", "|"] {
+            if trimmed.ends_with(op) {
+                return SyntaxError::near(script, trimmed.len() - op.len());
+            }
+        }
+
+        SyntaxError::new(
+            script,
+            trimmed.len(),
+            "syntax error: unexpected end of file".to_owned(),
+        )
+    }
+}
+
+impl std::fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (line {})", self.message, self.line)
+    }
+}
+
+/// Returns the offset of the quote which is not closed until the end.
+fn find_unclosed_quote(script: &str) -> Option<usize> {
+    let mut quote: Option<(usize, char)> = None;
+    let mut chars = script.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match (quote, c) {
+            (Some((_, '\'')), '\'') | (Some((_, '"')), '"') => quote = None,
+            (Some((_, '\'')), _) => (),
+            (_, '\\') => {
+                chars.next();
+            }
+            (None, '\'') | (None, '"') => quote = Some((i, c)),
+            _ => (),
+        }
+    }
+
+    quote.map(|(offset, _)| offset)
 }
 
 #[derive(Parser)]
@@ -21,6 +107,8 @@ pub struct Ast {
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Term {
     pub code: String,
+    /// The line in the script where the term starts (1-origin).
+    pub line: usize,
     pub pipelines: Vec<Pipeline>,
     pub background: bool,
 }
@@ -38,11 +126,120 @@ pub enum RunIf {
 pub struct Pipeline {
     pub run_if: RunIf,
     pub commands: Vec<Command>, // Separated by `|'.
+    /// Prefixed by `time`.
+    pub timed: bool,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[allow(clippy::enum_variant_names)]
 pub enum Command {
-    SimpleCommand { argv: Vec<Word> },
+    SimpleCommand {
+        argv: Vec<Word>,
+        redirects: Vec<Redirection>,
+        /// Variables set only for the command (e.g. `IFS=: read`).
+        assignments: Vec<Assignment>,
+    },
+    /// `name=value` without a command.
+    Assignment {
+        assignments: Vec<Assignment>,
+    },
+    If {
+        condition: Vec<Term>,
+        then_part: Vec<Term>,
+        elif_parts: Vec<ElIf>,
+        else_part: Option<Vec<Term>>,
+    },
+    While {
+        condition: Vec<Term>,
+        body: Vec<Term>,
+    },
+    /// `select name [in word ...]; do body; done`. Without `in`, the words
+    /// are the positional parameters.
+    Select {
+        name: String,
+        words: Option<Vec<Word>>,
+        body: Vec<Term>,
+    },
+    /// `coproc [name] command`: runs `body` in background connected to the
+    /// shell by pipes.
+    Coproc {
+        name: Option<String>,
+        body: Box<Command>,
+        /// The source of `body`.
+        code: String,
+    },
+    /// `{ compound_list }`
+    Group {
+        terms: Vec<Term>,
+    },
+    /// `( compound_list )`
+    Subshell {
+        terms: Vec<Term>,
+    },
+    /// `name() command`
+    FunctionDefinition {
+        name: String,
+        body: Box<Command>,
+        /// The source of `body`.
+        code: String,
+    },
+    /// `return [n]`
+    Return {
+        status: Option<i32>,
+    },
+    Break,
+    Continue,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum RedirectionDirection {
+    /// `<`
+    Input,
+    /// `>`
+    Output,
+    /// `>>`
+    Append,
+    /// `<<<`
+    HereString,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum RedirectionTarget {
+    File(Word),
+    /// `>&2`
+    Fd(i32),
+    /// `>&$fd`: the fd is determined by expansion.
+    FdWord(Word),
+    /// `>&-`
+    Close,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Redirection {
+    pub fd: i32,
+    pub direction: RedirectionDirection,
+    pub target: RedirectionTarget,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Initializer {
+    String(Word),
+    /// `name=(a b c)`
+    Array(Vec<Word>),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Assignment {
+    pub name: String,
+    /// `name[index]=value`: assigns to an element of the array.
+    pub index: Option<Index>,
+    pub initializer: Initializer,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ElIf {
+    pub condition: Vec<Term>,
+    pub then_part: Vec<Term>,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -61,14 +258,142 @@ impl Word {
     }
 }
 
+/// The subscript of an array reference.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Index {
+    Number(usize),
+    /// An arithmetic expression (e.g. `${name[$i]}` or `${name[i + 1]}`).
+    /// A negative value counts from the end.
+    Expr(Expr),
+    /// `${name[@]}`: expands to all elements as separate words.
+    All,
+    /// `${name[*]}`: expands to all elements joined into a word if quoted.
+    AllJoined,
+}
+
+/// An arithmetic expression. Values are 64-bit signed integers.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Expr {
+    Number(i64),
+    /// `name` or `$name`: the value of the variable, or 0 if it's unset or
+    /// empty.
+    Variable(String),
+    /// `${...}` in an expression.
+    Parameter(Word),
+    /// `name = expr`
+    Assign(String, Box<Expr>),
+    /// `name++` (`increment`) or `name--`: the value before the update.
+    PostIncrement {
+        name: String,
+        increment: bool,
+    },
+    Negate(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    /// Comparisons evaluate to 1 if true, otherwise 0.
+    Eq,
+    Ne,
+    Ge,
+    Gt,
+    Le,
+    Lt,
+}
+
+/// An operator in `${name...}`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ParamOp {
+    /// `${#name}`: the number of characters, or the number of elements in
+    /// `${#name[@]}`.
+    Length,
+    /// `${name^pattern}` or `${name^^pattern}` (`all`): converts characters
+    /// matching the pattern (`?` if omitted) to uppercase.
+    Upper { all: bool, pattern: Option<Word> },
+    /// `${name,pattern}` or `${name,,pattern}`.
+    Lower { all: bool, pattern: Option<Word> },
+    /// `${name-word}` or `${name:-word}` (`colon`): expands to `word` if the
+    /// parameter is unset (or empty with `colon`). `${name=word}` and
+    /// `${name:=word}` (`assign`) also assign `word` to it.
+    Default {
+        word: Word,
+        colon: bool,
+        assign: bool,
+    },
+    /// `${name/pattern/string}` or `${name//pattern/string}` (`all`): replaces
+    /// the longest match of the pattern with the string.
+    Replace {
+        pattern: Word,
+        replacement: Word,
+        all: bool,
+    },
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Span {
     Literal(String),
+    /// `$name`, `${name}`, or `${name[index]}`.
+    Parameter {
+        name: String,
+        index: Option<Index>,
+        /// `${!name}`: the value of `name` is the name of the parameter.
+        indirect: bool,
+        op: Option<ParamOp>,
+        quoted: bool,
+    },
+    /// `$(command)` or `` `command` ``.
+    Command {
+        body: Vec<Term>,
+        quoted: bool,
+    },
+    /// `{a,b}` or `{1..3}`: expanded into a word for each alternative by
+    /// brace expansion. Left as `code` where brace expansion does not
+    /// happen (e.g. assignments).
+    Brace {
+        alternatives: Vec<Word>,
+        code: String,
+    },
+    /// `$(<file)`: the contents of `file`, read without running a command.
+    ReadFile {
+        path: Word,
+        quoted: bool,
+    },
+    /// `<(command)`, or `>(command)` if `output`: a path to a pipe
+    /// connected to `command`.
+    ProcessSubstitution {
+        body: Vec<Term>,
+        output: bool,
+    },
+    /// `~` or `~user`: the home directory.
+    Tilde(Option<String>),
+    /// Unquoted `*` in a pathname pattern.
+    AnyString,
+    /// Unquoted `?` in a pathname pattern.
+    AnyChar,
+    /// Unquoted `[...]` in a pathname pattern.
+    Bracket(String),
+    /// Unquoted `@(a|b)` and so on, a pattern only if `extglob` is set.
+    ExtGlob(String),
     // Internally used by the parser.
     LiteralChars(Vec<LiteralChar>),
 }
 
 pub fn parse(script: &str) -> Result<Ast, ParseError> {
+    match parse_incremental(script) {
+        Err(ParseError::Incomplete) => Err(ParseError::Fatal(SyntaxError::unexpected_eof(script))),
+        result => result,
+    }
+}
+
+/// Parses a script which may be continued by following lines. Returns
+/// `ParseError::Incomplete` instead of a syntax error if the script is
+/// invalid only because it ends too early.
+pub fn parse_incremental(script: &str) -> Result<Ast, ParseError> {
     match ShellParser::parse(Rule::script, script) {
         Ok(mut pairs) => {
             let terms = visit_compound_list(pairs.next().unwrap());
@@ -80,7 +405,55 @@ pub fn parse(script: &str) -> Result<Ast, ParseError> {
                 Ok(Ast { terms })
             }
         }
-        Err(err) => Err(ParseError::Fatal(err.to_string())),
+        Err(err) => {
+            let pos = match err.location {
+                InputLocation::Pos(pos) => pos,
+                InputLocation::Span((_, end)) => end,
+            };
+
+            // The parser got stuck at the end of the input: it expected more.
+            if script[pos..].trim().is_empty() || script.ends_with("\\\n") {
+                Err(ParseError::Incomplete)
+            } else {
+                debug!("parse error: {}", err);
+                Err(ParseError::Fatal(SyntaxError::near(script, pos)))
+            }
+        }
+    }
+}
+
+/// Accumulates lines from a streaming source (e.g. the interactive reader
+/// or a script file) until they form a complete script.
+pub struct IncrementalParser {
+    buf: String,
+}
+
+impl IncrementalParser {
+    pub fn new() -> IncrementalParser {
+        IncrementalParser { buf: String::new() }
+    }
+
+    /// Appends a line (including the trailing newline) and parses the
+    /// buffered lines. The buffer is consumed unless it is incomplete.
+    pub fn feed(&mut self, line: &str) -> Result<Ast, ParseError> {
+        self.buf.push_str(line);
+        let result = parse_incremental(&self.buf);
+        if result != Err(ParseError::Incomplete) {
+            self.buf.clear();
+        }
+
+        result
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    /// Parses the buffered lines at the end of the input.
+    pub fn finish(&mut self) -> Result<Ast, ParseError> {
+        let result = parse(&self.buf);
+        self.buf.clear();
+        result
     }
 }
 
@@ -115,9 +488,11 @@ fn visit_compound_list(pair: Pair<Rule>) -> Vec<Term> {
 
         if and_or_list.as_rule() == Rule::and_or_list {
             let code = and_or_list.as_str().to_owned().trim().to_owned();
+            let line = and_or_list.as_span().start_pos().line_col().0;
             let pipelines = visit_and_or_list(and_or_list, RunIf::Always);
             terms.push(Term {
                 code,
+                line,
                 pipelines,
                 background,
             });
@@ -135,8 +510,7 @@ fn visit_and_or_list(pair: Pair<Rule>, run_if: RunIf) -> Vec<Pipeline> {
     let mut terms = Vec::new();
     let mut inner = pair.into_inner();
     if let Some(pipeline) = inner.next() {
-        let commands = visit_pipeline(pipeline);
-        terms.push(Pipeline { commands, run_if });
+        terms.push(visit_pipeline(pipeline, run_if));
 
         let next_run_if = inner
             .next()
@@ -147,7 +521,8 @@ fn visit_and_or_list(pair: Pair<Rule>, run_if: RunIf) -> Vec<Pipeline> {
             })
             .unwrap_or(RunIf::Always);
 
-        if let Some(rest) = inner.next() {
+        // Skip newlines after `&&` or `||`.
+        if let Some(rest) = inner.find(|pair| pair.as_rule() == Rule::and_or_list) {
             terms.extend(visit_and_or_list(rest, next_run_if));
         }
     }
@@ -155,33 +530,149 @@ fn visit_and_or_list(pair: Pair<Rule>, run_if: RunIf) -> Vec<Pipeline> {
     terms
 }
 
-fn visit_pipeline(pair: Pair<Rule>) -> Vec<Command> {
+fn visit_pipeline(pair: Pair<Rule>, run_if: RunIf) -> Pipeline {
     let mut commands = Vec::new();
-    let inner = pair.into_inner();
-    for command in inner {
-        commands.push(visit_command(command));
+    let mut timed = false;
+    for inner in pair.into_inner() {
+        match inner.as_rule() {
+            Rule::time_keyword => timed = true,
+            Rule::command => commands.push(visit_command(inner)),
+            // Newlines after `|`.
+            _ => (),
+        }
     }
 
-    commands
+    Pipeline {
+        run_if,
+        commands,
+        timed,
+    }
 }
 
 fn visit_command(pair: Pair<Rule>) -> Command {
     let inner = pair.into_inner().next().unwrap();
     match inner.as_rule() {
         Rule::simple_command => visit_simple_command(inner),
+        Rule::if_command => visit_if_command(inner),
+        Rule::while_command => {
+            let mut inner = inner.into_inner();
+            let condition = visit_compound_list(inner.next().unwrap());
+            let body = visit_compound_list(inner.next().unwrap());
+            Command::While { condition, body }
+        }
+        Rule::select_command => {
+            let mut name = String::new();
+            let mut words = None;
+            let mut body = Vec::new();
+            for inner in inner.into_inner() {
+                match inner.as_rule() {
+                    Rule::var_name => name = inner.as_str().to_owned(),
+                    Rule::word_list => words = Some(inner.into_inner().map(visit_word).collect()),
+                    Rule::compound_list => body = visit_compound_list(inner),
+                    _ => unreachable!(),
+                }
+            }
+
+            Command::Select { name, words, body }
+        }
+        Rule::coproc_command => {
+            let mut name = None;
+            let mut body = None;
+            for inner in inner.into_inner() {
+                match inner.as_rule() {
+                    Rule::var_name => name = Some(inner.as_str().to_owned()),
+                    Rule::command => body = Some(inner),
+                    _ => unreachable!(),
+                }
+            }
+
+            let body = body.unwrap();
+            Command::Coproc {
+                name,
+                code: body.as_str().to_owned(),
+                body: Box::new(visit_command(body)),
+            }
+        }
+        Rule::break_command => Command::Break,
+        Rule::continue_command => Command::Continue,
+        Rule::assignment_command => Command::Assignment {
+            assignments: inner.into_inner().map(visit_assignment).collect(),
+        },
+        Rule::group => Command::Group {
+            terms: visit_compound_list(inner.into_inner().next().unwrap()),
+        },
+        Rule::subshell_group => Command::Subshell {
+            terms: visit_compound_list(inner.into_inner().next().unwrap()),
+        },
+        Rule::function_definition => {
+            let mut inner = inner.into_inner();
+            let name = inner.next().unwrap().as_str().to_owned();
+            let body = inner.next().unwrap();
+            Command::FunctionDefinition {
+                name,
+                code: body.as_str().to_owned(),
+                body: Box::new(visit_command(body)),
+            }
+        }
+        Rule::return_command => Command::Return {
+            status: inner
+                .into_inner()
+                .next()
+                .map(|num| num.as_str().parse().unwrap_or(255)),
+        },
         // TODO: support other rules
         _ => unimplemented!("rule {:?}", inner.as_rule()),
     }
 }
 
+fn visit_if_command(pair: Pair<Rule>) -> Command {
+    assert_eq!(pair.as_rule(), Rule::if_command);
+
+    let mut inner = pair.into_inner();
+    let condition = visit_compound_list(inner.next().unwrap());
+    let then_part = visit_compound_list(inner.next().unwrap());
+    let mut elif_parts = Vec::new();
+    let mut else_part = None;
+    for part in inner {
+        match part.as_rule() {
+            Rule::elif_part => {
+                let mut inner = part.into_inner();
+                let condition = visit_compound_list(inner.next().unwrap());
+                let then_part = visit_compound_list(inner.next().unwrap());
+                elif_parts.push(ElIf {
+                    condition,
+                    then_part,
+                });
+            }
+            Rule::else_part => {
+                else_part = Some(visit_compound_list(part.into_inner().next().unwrap()));
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    Command::If {
+        condition,
+        then_part,
+        elif_parts,
+        else_part,
+    }
+}
+
 fn visit_simple_command(pair: Pair<Rule>) -> Command {
     assert_eq!(pair.as_rule(), Rule::simple_command);
 
     let mut argv = Vec::new();
+    let mut redirects = Vec::new();
 
     let mut inner = pair.into_inner();
     debug!(?inner);
-    let _assignments_pairs = inner.next().unwrap().into_inner();
+    let assignments = inner
+        .next()
+        .unwrap()
+        .into_inner()
+        .map(visit_assignment)
+        .collect();
     let argv0 = inner.next().unwrap().into_inner().next().unwrap();
     let args = inner.next().unwrap().into_inner();
 
@@ -189,22 +680,171 @@ fn visit_simple_command(pair: Pair<Rule>) -> Command {
     for word_or_redirect in args {
         match word_or_redirect.as_rule() {
             Rule::word => argv.push(visit_word(word_or_redirect)),
-            Rule::redirect => (),
+            Rule::redirect => redirects.extend(visit_redirect(word_or_redirect)),
             _ => unreachable!(),
         }
     }
 
-    Command::SimpleCommand { argv }
+    Command::SimpleCommand {
+        argv,
+        redirects,
+        assignments,
+    }
+}
+
+fn visit_assignment(pair: Pair<Rule>) -> Assignment {
+    assert_eq!(pair.as_rule(), Rule::assignment);
+
+    let mut inner = pair.into_inner();
+    let name = inner.next().unwrap().as_str().to_owned();
+    let index = inner.next().unwrap();
+    let index = if index.as_str().is_empty() {
+        None
+    } else {
+        Some(visit_index(index))
+    };
+
+    let initializer = match inner.next() {
+        Some(initializer) => {
+            let initializer = initializer.into_inner().next().unwrap();
+            match initializer.as_rule() {
+                Rule::string_initializer => {
+                    let word = initializer.into_inner().next().unwrap();
+                    Initializer::String(visit_assignment_word(word))
+                }
+                Rule::array_initializer => {
+                    Initializer::Array(initializer.into_inner().map(visit_word).collect())
+                }
+                _ => unreachable!(),
+            }
+        }
+        None => Initializer::String(Word(vec![Span::Literal(String::new())])),
+    };
+
+    Assignment {
+        name,
+        index,
+        initializer,
+    }
+}
+
+/// `&>file` is expanded into `>file 2>&1`.
+fn visit_redirect(pair: Pair<Rule>) -> Vec<Redirection> {
+    assert_eq!(pair.as_rule(), Rule::redirect);
+
+    let mut inner = pair.into_inner();
+    let fd = inner.next().unwrap();
+    let both = fd.as_rule() == Rule::redirect_both;
+    let fd = fd.as_str();
+    let direction = match inner.next().unwrap().as_str() {
+        "<" => RedirectionDirection::Input,
+        ">" => RedirectionDirection::Output,
+        ">>" => RedirectionDirection::Append,
+        "<<<" => RedirectionDirection::HereString,
+        _ => unreachable!(),
+    };
+
+    let fd = match (fd.parse(), &direction) {
+        (Ok(fd), _) => fd,
+        (Err(_), RedirectionDirection::Input | RedirectionDirection::HereString) => 0,
+        (Err(_), _) => 1,
+    };
+
+    let target = inner.next().unwrap();
+    let target = match target.as_rule() {
+        Rule::word => RedirectionTarget::File(visit_word(target)),
+        Rule::redirect_to_fd => {
+            let target_fd = target.into_inner().next().unwrap();
+            match target_fd.as_rule() {
+                Rule::redirect_fd_word => {
+                    RedirectionTarget::FdWord(visit_word(target_fd.into_inner().next().unwrap()))
+                }
+                Rule::redirect_close => RedirectionTarget::Close,
+                _ => RedirectionTarget::Fd(target_fd.as_str().parse().unwrap_or(1)),
+            }
+        }
+        _ => unreachable!(),
+    };
+
+    let mut redirects = vec![Redirection {
+        fd,
+        direction,
+        target,
+    }];
+    if both {
+        redirects.push(Redirection {
+            fd: 2,
+            direction: RedirectionDirection::Output,
+            target: RedirectionTarget::Fd(1),
+        });
+    }
+
+    redirects
 }
 
 fn visit_word(pair: Pair<Rule>) -> Word {
-    visit_escaped_word(pair, false)
+    visit_escaped_word(pair, false, false)
+}
+
+/// Visits the value of an assignment, where a `~` after an unquoted `:` is
+/// also expanded (e.g. `PATH=~/bin:~/.local/bin`).
+fn visit_assignment_word(pair: Pair<Rule>) -> Word {
+    visit_escaped_word(pair, false, true)
+}
+
+fn visit_literal_span_in_assignment(pair: Pair<Rule>) -> Vec<Span> {
+    let raw = pair.as_str();
+    let mut spans = Vec::new();
+    // The beginning of the literal not pushed yet.
+    let mut start = 0;
+    let mut escaped = false;
+    let mut chars = raw.char_indices().peekable();
+    while let Some((i, ch)) = chars.next() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+
+        match ch {
+            '\\' => escaped = true,
+            ':' if raw[i + 1..].starts_with('~') => {
+                spans.push(Span::Literal(unescape(&raw[start..=i], None)));
+                // `~user` ends at `/` or `:`.
+                let tilde = &raw[i + 2..];
+                let end = tilde.find(['/', ':']).unwrap_or(tilde.len());
+                let user = &tilde[..end];
+                spans.push(Span::Tilde(if user.is_empty() {
+                    None
+                } else {
+                    Some(user.to_owned())
+                }));
+
+                start = i + 2 + end;
+                while chars.peek().is_some_and(|(j, _)| *j < start) {
+                    chars.next();
+                }
+            }
+            _ => (),
+        }
+    }
+
+    if start < raw.len() {
+        spans.push(Span::Literal(unescape(&raw[start..], None)));
+    }
+
+    spans
 }
 
 fn visit_escape_sequences(pair: Pair<Rule>, escaped_chars: Option<&str>) -> String {
+    unescape(pair.as_str(), escaped_chars)
+}
+
+/// Removes backslashes. If `escaped_chars` is given, only backslashes before
+/// them are removed.
+fn unescape(raw: &str, escaped_chars: Option<&str>) -> String {
     let mut s = String::new();
     let mut escaped = false;
-    for ch in pair.as_str().chars() {
+    for ch in raw.chars() {
         if escaped {
             escaped = false;
             if let Some(escaped_chars) = escaped_chars {
@@ -223,13 +863,212 @@ fn visit_escape_sequences(pair: Pair<Rule>, escaped_chars: Option<&str>) -> Stri
     s
 }
 
-fn visit_escaped_word(pair: Pair<Rule>, literal_chars: bool) -> Word {
+fn visit_param_span(pair: Pair<Rule>, quoted: bool) -> Span {
+    let rule = pair.as_rule();
+    let mut name = None;
+    let mut index = None;
+    let mut indirect = false;
+    let mut op = None;
+    for inner in pair.into_inner() {
+        match inner.as_rule() {
+            Rule::expandable_var_name => name = Some(inner.as_str().to_owned()),
+            Rule::length_op | Rule::index | Rule::indirect_op if inner.as_str().is_empty() => (),
+            Rule::indirect_op => indirect = true,
+            Rule::length_op => op = Some(ParamOp::Length),
+            Rule::case_opt => {
+                let mut inner = inner.into_inner();
+                let case_op = inner.next().unwrap().as_str();
+                let pattern = inner.next().map(visit_word);
+                let all = case_op.len() == 2;
+                op = Some(if case_op.starts_with('^') {
+                    ParamOp::Upper { all, pattern }
+                } else {
+                    ParamOp::Lower { all, pattern }
+                });
+            }
+            Rule::param_opt => {
+                let mut inner = inner.into_inner();
+                let param_op = inner.next().unwrap().as_str();
+                let word = inner.next().map(visit_word).unwrap_or(Word(Vec::new()));
+                op = Some(match param_op {
+                    "/" | "//" => {
+                        let (pattern, replacement) = split_replacement(word);
+                        ParamOp::Replace {
+                            pattern,
+                            replacement,
+                            all: param_op == "//",
+                        }
+                    }
+                    _ => ParamOp::Default {
+                        word,
+                        colon: param_op.starts_with(':'),
+                        assign: param_op.ends_with('='),
+                    },
+                });
+            }
+            Rule::index => index = Some(visit_index(inner)),
+            _ => unreachable!("{:?} in {:?}", inner.as_rule(), rule),
+        }
+    }
+
+    Span::Parameter {
+        name: name.unwrap(),
+        index,
+        indirect,
+        op,
+        quoted,
+    }
+}
+
+/// Splits the word in `${name/pattern/string}` into the pattern and the
+/// string at the first `/`.
+fn split_replacement(word: Word) -> (Word, Word) {
+    let mut pattern = Vec::new();
+    let mut spans = word.0.into_iter();
+    for span in spans.by_ref() {
+        match span {
+            Span::Literal(s) if s.contains('/') => {
+                let (before, after) = s.split_once('/').unwrap();
+                if !before.is_empty() {
+                    pattern.push(Span::Literal(before.to_owned()));
+                }
+
+                let mut replacement = Vec::new();
+                if !after.is_empty() {
+                    replacement.push(Span::Literal(after.to_owned()));
+                }
+                replacement.extend(spans);
+                return (Word(pattern), Word(replacement));
+            }
+            span => pattern.push(span),
+        }
+    }
+
+    (Word(pattern), Word(Vec::new()))
+}
+
+fn visit_command_span(pair: Pair<Rule>, quoted: bool) -> Span {
+    Span::Command {
+        body: visit_compound_list(pair.into_inner().next().unwrap()),
+        quoted,
+    }
+}
+
+fn visit_read_file_span(pair: Pair<Rule>, quoted: bool) -> Span {
+    Span::ReadFile {
+        path: visit_word(pair.into_inner().next().unwrap()),
+        quoted,
+    }
+}
+
+fn visit_index(pair: Pair<Rule>) -> Index {
+    let inner = pair.into_inner().next().unwrap();
+    match inner.as_rule() {
+        Rule::index_all if inner.as_str() == "@" => Index::All,
+        Rule::index_all => Index::AllJoined,
+        _ => match inner.as_str().trim().parse() {
+            Ok(n) => Index::Number(n),
+            Err(_) => Index::Expr(visit_expr(inner)),
+        },
+    }
+}
+
+/// Visits an arithmetic expression. The grammar nests the rest of a chain of
+/// binary operators (e.g. `b - c` in `a - b - c`), so the chain is flattened
+/// first to apply the operators from left to right.
+fn visit_expr(pair: Pair<Rule>) -> Expr {
+    match pair.as_rule() {
+        Rule::expr | Rule::arith | Rule::term => {
+            let rule = pair.as_rule();
+            let mut operands = Vec::new();
+            let mut ops = Vec::new();
+            let mut pair = pair;
+            loop {
+                let mut inner = pair.into_inner();
+                operands.push(visit_expr(inner.next().unwrap()));
+                match (inner.next(), inner.next()) {
+                    (Some(op), Some(rest)) if rest.as_rule() == rule => {
+                        ops.push(binary_op(op.as_str()));
+                        pair = rest;
+                    }
+                    _ => break,
+                }
+            }
+
+            let mut operands = operands.into_iter();
+            let first = operands.next().unwrap();
+            ops.into_iter().zip(operands).fold(first, |lhs, (op, rhs)| {
+                Expr::Binary(op, Box::new(lhs), Box::new(rhs))
+            })
+        }
+        Rule::assign => {
+            let mut inner = pair.into_inner();
+            let first = inner.next().unwrap();
+            match first.as_rule() {
+                Rule::var_name => {
+                    let name = first.as_str().to_owned();
+                    let value = visit_expr(inner.nth(1).unwrap());
+                    Expr::Assign(name, Box::new(value))
+                }
+                _ => visit_expr(first),
+            }
+        }
+        Rule::factor => {
+            let mut inner = pair.into_inner();
+            let negative = inner.next().unwrap().as_str() == "-";
+            let primary = inner.next().unwrap();
+            let postfix = inner.next().unwrap().as_str();
+            let expr = match primary.as_rule() {
+                Rule::num => Expr::Number(primary.as_str().parse().unwrap_or(i64::MAX)),
+                Rule::var_name if !postfix.is_empty() => Expr::PostIncrement {
+                    name: primary.as_str().to_owned(),
+                    increment: postfix == "++",
+                },
+                Rule::var_name => Expr::Variable(primary.as_str().to_owned()),
+                Rule::param_ex_span => {
+                    Expr::Parameter(Word(vec![visit_param_span(primary, false)]))
+                }
+                _ => visit_expr(primary),
+            };
+
+            if negative {
+                Expr::Negate(Box::new(expr))
+            } else {
+                expr
+            }
+        }
+        rule => unreachable!("{:?} in an expression", rule),
+    }
+}
+
+fn binary_op(op: &str) -> BinaryOp {
+    match op {
+        "+" => BinaryOp::Add,
+        "-" => BinaryOp::Sub,
+        "*" => BinaryOp::Mul,
+        "/" => BinaryOp::Div,
+        "==" => BinaryOp::Eq,
+        "!=" => BinaryOp::Ne,
+        ">=" => BinaryOp::Ge,
+        ">" => BinaryOp::Gt,
+        "<=" => BinaryOp::Le,
+        _ => BinaryOp::Lt,
+    }
+}
+
+fn visit_escaped_word(pair: Pair<Rule>, literal_chars: bool, in_assignment: bool) -> Word {
     assert_eq!(pair.as_rule(), Rule::word);
 
+    let spans = visit_spans(pair.into_inner(), literal_chars, in_assignment);
+    debug!("spans: {:?}", spans);
+    Word(spans)
+}
+
+fn visit_spans(pairs: Pairs<Rule>, literal_chars: bool, in_assignment: bool) -> Vec<Span> {
     let mut spans = Vec::new();
-    for span in pair.into_inner() {
+    for span in pairs {
         match span.as_rule() {
-            Rule::literal_span if literal_chars => {
+            Rule::literal_span | Rule::brace_literal_span if literal_chars => {
                 let mut chars = Vec::new();
                 for ch in span.into_inner() {
                     match ch.as_rule() {
@@ -246,11 +1085,54 @@ fn visit_escaped_word(pair: Pair<Rule>, literal_chars: bool) -> Word {
                 }
                 spans.push(Span::LiteralChars(chars));
             }
-            Rule::literal_span if !literal_chars => {
+            Rule::literal_span | Rule::brace_literal_span if !literal_chars && in_assignment => {
+                spans.extend(visit_literal_span_in_assignment(span));
+            }
+            Rule::literal_span | Rule::brace_literal_span if !literal_chars => {
                 spans.push(Span::Literal(visit_escape_sequences(span, None)));
             }
+            Rule::brace_span => spans.push(visit_brace_span(span)),
+            Rule::tilde_span => {
+                let user = span
+                    .into_inner()
+                    .next()
+                    .map(|user| user.as_str().to_owned());
+                spans.push(Span::Tilde(user));
+            }
+            Rule::param_span | Rule::param_ex_span => {
+                spans.push(visit_param_span(span, false));
+            }
+            Rule::read_file_span => spans.push(visit_read_file_span(span, false)),
+            Rule::command_span | Rule::backtick_span => {
+                spans.push(visit_command_span(span, false));
+            }
+            Rule::proc_subst_span => {
+                let mut inner = span.into_inner();
+                let output = inner.next().unwrap().as_str() == ">(";
+                spans.push(Span::ProcessSubstitution {
+                    body: visit_compound_list(inner.next().unwrap()),
+                    output,
+                });
+            }
+            Rule::assign_like_prefix => {
+                spans.push(Span::Literal(span.as_str().to_owned()));
+            }
+            Rule::any_string_span => spans.push(Span::AnyString),
+            Rule::any_char_span => spans.push(Span::AnyChar),
+            Rule::bracket_span => spans.push(Span::Bracket(span.as_str().to_owned())),
+            Rule::extglob_span => spans.push(Span::ExtGlob(span.as_str().to_owned())),
+            Rule::single_quoted_span => {
+                let literal = span.into_inner().map(|inner| inner.as_str()).collect();
+                spans.push(Span::Literal(literal));
+            }
             Rule::double_quoted_span => {
-                for span_in_quote in span.into_inner() {
+                let mut inner = span.into_inner().peekable();
+                if inner.peek().is_none() {
+                    // `""` is an empty word, not nothing.
+                    spans.push(Span::Literal(String::new()));
+                }
+
+                for span_in_quote in inner {
                     match span_in_quote.as_rule() {
                         Rule::literal_in_double_quoted_span => {
                             spans.push(Span::Literal(visit_escape_sequences(
@@ -258,6 +1140,15 @@ fn visit_escaped_word(pair: Pair<Rule>, literal_chars: bool) -> Word {
                                 Some("\"`$"),
                             )));
                         }
+                        Rule::param_span | Rule::param_ex_span => {
+                            spans.push(visit_param_span(span_in_quote, true));
+                        }
+                        Rule::read_file_span => {
+                            spans.push(visit_read_file_span(span_in_quote, true))
+                        }
+                        Rule::command_span | Rule::backtick_span => {
+                            spans.push(visit_command_span(span_in_quote, true));
+                        }
                         rule => unreachable!("{:?}", rule),
                     }
                 }
@@ -269,13 +1160,98 @@ fn visit_escaped_word(pair: Pair<Rule>, literal_chars: bool) -> Word {
         }
     }
 
-    debug!("spans: {:?}", spans);
-    Word(spans)
+    spans
+}
+
+fn visit_brace_span(pair: Pair<Rule>) -> Span {
+    let code = pair.as_str().to_owned();
+    let inner = pair.into_inner().next().unwrap();
+    let alternatives = match inner.as_rule() {
+        Rule::brace_list => inner
+            .into_inner()
+            .map(|item| Word(visit_spans(item.into_inner(), false, false)))
+            .collect(),
+        Rule::brace_sequence => {
+            let mut inner = inner.into_inner();
+            let start = inner.next().unwrap().as_str();
+            let end = inner.next().unwrap().as_str();
+            let step = inner.next().map(|step| step.as_str());
+            match brace_sequence(start, end, step) {
+                Some(items) => items
+                    .into_iter()
+                    .map(|item| Word(vec![Span::Literal(item)]))
+                    .collect(),
+                // e.g. `{1..a}` is not expanded.
+                None => vec![Word(vec![Span::Literal(code.clone())])],
+            }
+        }
+        _ => unreachable!(),
+    };
+
+    Span::Brace { alternatives, code }
+}
+
+/// Generates `{start..end..step}`: integers (zero-padded if either end is)
+/// or letters.
+fn brace_sequence(start: &str, end: &str, step: Option<&str>) -> Option<Vec<String>> {
+    let step = step
+        .and_then(|step| step.parse::<i64>().ok())
+        .map(|step| step.unsigned_abs().max(1))
+        .unwrap_or(1) as usize;
+
+    if let (Ok(first), Ok(last)) = (start.parse::<i64>(), end.parse::<i64>()) {
+        let is_padded = |s: &str| {
+            let digits = s.trim_start_matches('-');
+            digits.len() > 1 && digits.starts_with('0')
+        };
+        let width = if is_padded(start) || is_padded(end) {
+            start.len().max(end.len())
+        } else {
+            0
+        };
+
+        let numbers: Vec<i64> = if first <= last {
+            (first..=last).step_by(step).collect()
+        } else {
+            (last..=first).rev().step_by(step).collect()
+        };
+
+        return Some(
+            numbers
+                .into_iter()
+                .map(|n| format!("{:0width$}", n, width = width))
+                .collect(),
+        );
+    }
+
+    let (first, last) = match (start.chars().next(), end.chars().next()) {
+        (Some(first), Some(last)) if first.is_ascii_alphabetic() && last.is_ascii_alphabetic() => {
+            (first as u8, last as u8)
+        }
+        _ => return None,
+    };
+
+    let letters: Vec<u8> = if first <= last {
+        (first..=last).step_by(step).collect()
+    } else {
+        (last..=first).rev().step_by(step).collect()
+    };
+
+    Some(
+        letters
+            .into_iter()
+            .map(|c| (c as char).to_string())
+            .collect(),
+    )
 }
 
 #[cfg(test)]
 mod test {
-    use super::{parse, Ast, Command, Pipeline, RunIf, Span, Term, Word};
+    use super::{
+        brace_sequence, parse, parse_incremental, Assignment, Ast, Command, IncrementalParser,
+        Index, Initializer, ParseError, Pipeline, Redirection, RedirectionDirection,
+        RedirectionTarget, RunIf, Span, Term, Word,
+    };
 
     macro_rules! literal_word_vec {
         ($($x:expr), *) => {
@@ -290,15 +1266,388 @@ mod test {
             Ok(Ast {
                 terms: vec![Term {
                     code: "ls -G /tmp".into(),
+                    line: 1,
                     pipelines: vec![Pipeline {
                         run_if: RunIf::Always,
                         commands: vec![Command::SimpleCommand {
-                            argv: literal_word_vec!["ls", "-G", "/tmp"]
+                            argv: literal_word_vec!["ls", "-G", "/tmp"],
+                            redirects: vec![],
+                            assignments: vec![],
                         }],
+                        timed: false,
                     }],
                     background: false
                 }],
             })
         );
     }
+
+    #[test]
+    pub fn test_redirections() {
+        let ast = parse("echo 2 > a 2>>b <c >&2").unwrap();
+        assert_eq!(
+            ast.terms[0].pipelines[0].commands,
+            vec![Command::SimpleCommand {
+                argv: literal_word_vec!["echo", "2"],
+                redirects: vec![
+                    Redirection {
+                        fd: 1,
+                        direction: RedirectionDirection::Output,
+                        target: RedirectionTarget::File(Word(vec![Span::Literal("a".into())])),
+                    },
+                    Redirection {
+                        fd: 2,
+                        direction: RedirectionDirection::Append,
+                        target: RedirectionTarget::File(Word(vec![Span::Literal("b".into())])),
+                    },
+                    Redirection {
+                        fd: 0,
+                        direction: RedirectionDirection::Input,
+                        target: RedirectionTarget::File(Word(vec![Span::Literal("c".into())])),
+                    },
+                    Redirection {
+                        fd: 1,
+                        direction: RedirectionDirection::Output,
+                        target: RedirectionTarget::Fd(2),
+                    },
+                ],
+                assignments: vec![],
+            }]
+        );
+    }
+
+    #[test]
+    pub fn test_redirect_close() {
+        let ast = parse("cmd <&- 3>&-").unwrap();
+        assert_eq!(
+            ast.terms[0].pipelines[0].commands,
+            vec![Command::SimpleCommand {
+                argv: literal_word_vec!["cmd"],
+                redirects: vec![
+                    Redirection {
+                        fd: 0,
+                        direction: RedirectionDirection::Input,
+                        target: RedirectionTarget::Close,
+                    },
+                    Redirection {
+                        fd: 3,
+                        direction: RedirectionDirection::Output,
+                        target: RedirectionTarget::Close,
+                    },
+                ],
+                assignments: vec![],
+            }]
+        );
+    }
+
+    #[test]
+    pub fn test_redirect_both() {
+        let ast = parse("cmd &>>log & cmd &> /dev/null").unwrap();
+        let both = |direction, file: &str| {
+            vec![
+                Redirection {
+                    fd: 1,
+                    direction,
+                    target: RedirectionTarget::File(Word(vec![Span::Literal(file.into())])),
+                },
+                Redirection {
+                    fd: 2,
+                    direction: RedirectionDirection::Output,
+                    target: RedirectionTarget::Fd(1),
+                },
+            ]
+        };
+        assert_eq!(
+            ast.terms[0].pipelines[0].commands,
+            vec![Command::SimpleCommand {
+                argv: literal_word_vec!["cmd"],
+                redirects: both(RedirectionDirection::Append, "log"),
+                assignments: vec![],
+            }]
+        );
+        assert!(ast.terms[0].background);
+        assert_eq!(
+            ast.terms[1].pipelines[0].commands,
+            vec![Command::SimpleCommand {
+                argv: literal_word_vec!["cmd"],
+                redirects: both(RedirectionDirection::Output, "/dev/null"),
+                assignments: vec![],
+            }]
+        );
+    }
+
+    #[test]
+    pub fn test_read_file_span() {
+        let ast = parse("echo $(<file) $(/bin/cat <file)").unwrap();
+        let argv = match &ast.terms[0].pipelines[0].commands[0] {
+            Command::SimpleCommand { argv, .. } => argv.clone(),
+            _ => unreachable!(),
+        };
+        assert_eq!(
+            argv[1],
+            Word(vec![Span::ReadFile {
+                path: Word(vec![Span::Literal("file".into())]),
+                quoted: false,
+            }])
+        );
+        assert!(matches!(argv[2].0[0], Span::Command { .. }));
+    }
+
+    #[test]
+    pub fn test_brace_span() {
+        let ast = parse("echo a{b,$c}").unwrap();
+        let argv = match &ast.terms[0].pipelines[0].commands[0] {
+            Command::SimpleCommand { argv, .. } => argv.clone(),
+            _ => unreachable!(),
+        };
+        assert_eq!(
+            argv[1],
+            Word(vec![
+                Span::Literal("a".into()),
+                Span::Brace {
+                    alternatives: vec![
+                        Word(vec![Span::Literal("b".into())]),
+                        Word(vec![Span::Parameter {
+                            name: "c".into(),
+                            index: None,
+                            indirect: false,
+                            op: None,
+                            quoted: false,
+                        }]),
+                    ],
+                    code: "{b,$c}".into(),
+                },
+            ])
+        );
+    }
+
+    #[test]
+    pub fn test_brace_sequence() {
+        let seq = |start, end, step| brace_sequence(start, end, step).unwrap();
+        assert_eq!(seq("1", "3", None), vec!["1", "2", "3"]);
+        assert_eq!(seq("3", "-1", None), vec!["3", "2", "1", "0", "-1"]);
+        assert_eq!(seq("1", "10", Some("-4")), vec!["1", "5", "9"]);
+        assert_eq!(seq("08", "10", None), vec!["08", "09", "10"]);
+        assert_eq!(seq("-1", "01", None), vec!["-1", "00", "01"]);
+        assert_eq!(seq("e", "a", Some("2")), vec!["e", "c", "a"]);
+        assert_eq!(brace_sequence("1", "a", None), None);
+    }
+
+    #[test]
+    pub fn test_assignments() {
+        let ast = parse("IFS= a=(x y) read v <<< $(echo z)").unwrap();
+        assert_eq!(
+            ast.terms[0].pipelines[0].commands,
+            vec![Command::SimpleCommand {
+                argv: literal_word_vec!["read", "v"],
+                redirects: vec![Redirection {
+                    fd: 0,
+                    direction: RedirectionDirection::HereString,
+                    target: RedirectionTarget::File(Word(vec![Span::Command {
+                        body: parse("echo z").unwrap().terms,
+                        quoted: false,
+                    }])),
+                }],
+                assignments: vec![
+                    Assignment {
+                        name: "IFS".into(),
+                        index: None,
+                        initializer: Initializer::String(Word(vec![Span::Literal("".into())])),
+                    },
+                    Assignment {
+                        name: "a".into(),
+                        index: None,
+                        initializer: Initializer::Array(literal_word_vec!["x", "y"]),
+                    },
+                ],
+            }]
+        );
+
+        let ast = parse("a[1]=x").unwrap();
+        assert_eq!(
+            ast.terms[0].pipelines[0].commands,
+            vec![Command::Assignment {
+                assignments: vec![Assignment {
+                    name: "a".into(),
+                    index: Some(Index::Number(1)),
+                    initializer: Initializer::String(Word(vec![Span::Literal("x".into())])),
+                }],
+            }]
+        );
+
+        let ast = parse("a=1 b=").unwrap();
+        assert_eq!(
+            ast.terms[0].pipelines[0].commands,
+            vec![Command::Assignment {
+                assignments: vec![
+                    Assignment {
+                        name: "a".into(),
+                        index: None,
+                        initializer: Initializer::String(Word(vec![Span::Literal("1".into())])),
+                    },
+                    Assignment {
+                        name: "b".into(),
+                        index: None,
+                        initializer: Initializer::String(Word(vec![Span::Literal("".into())])),
+                    },
+                ],
+            }]
+        );
+    }
+
+    #[test]
+    pub fn test_array_references() {
+        let ast = parse("echo ${a[@]} \"${a[*]}\" ${a[1]}").unwrap();
+        let param = |index, quoted| {
+            Word(vec![Span::Parameter {
+                name: "a".into(),
+                index: Some(index),
+                indirect: false,
+                op: None,
+                quoted,
+            }])
+        };
+
+        assert_eq!(
+            ast.terms[0].pipelines[0].commands,
+            vec![Command::SimpleCommand {
+                argv: vec![
+                    Word(vec![Span::Literal("echo".into())]),
+                    param(Index::All, false),
+                    param(Index::AllJoined, true),
+                    param(Index::Number(1), false),
+                ],
+                redirects: vec![],
+                assignments: vec![],
+            }]
+        );
+    }
+
+    #[test]
+    pub fn test_newlines() {
+        let codes =
+            |ast: &Ast| -> Vec<String> { ast.terms.iter().map(|t| t.code.clone()).collect() };
+
+        let ast = parse("echo a\n\necho b; echo c\n").unwrap();
+        assert_eq!(codes(&ast), vec!["echo a", "echo b", "echo c"]);
+
+        // A newline after `&&` or `|` continues the command.
+        let ast = parse("a &&\n  b |\n  c\n").unwrap();
+        assert_eq!(ast.terms.len(), 1);
+        assert_eq!(ast.terms[0].pipelines.len(), 2);
+        assert_eq!(ast.terms[0].pipelines[1].run_if, RunIf::Success);
+        assert_eq!(ast.terms[0].pipelines[1].commands.len(), 2);
+
+        let ast = parse("while a\ndo\n  b\n  c\ndone\n").unwrap();
+        assert_eq!(ast.terms.len(), 1);
+        match &ast.terms[0].pipelines[0].commands[0] {
+            Command::While { condition, body } => {
+                assert_eq!(condition.len(), 1);
+                assert_eq!(
+                    body.iter().map(|t| t.code.as_str()).collect::<Vec<_>>(),
+                    vec!["b", "c"]
+                );
+            }
+            command => panic!("unexpected command: {:?}", command),
+        }
+    }
+
+    #[test]
+    pub fn test_functions() {
+        let ast = parse("f() { echo a; return 3; }\n").unwrap();
+        assert_eq!(
+            ast.terms[0].pipelines[0].commands,
+            vec![Command::FunctionDefinition {
+                name: "f".into(),
+                code: "{ echo a; return 3; }".into(),
+                body: Box::new(Command::Group {
+                    terms: vec![
+                        Term {
+                            code: "echo a".into(),
+                            line: 1,
+                            pipelines: vec![Pipeline {
+                                run_if: RunIf::Always,
+                                commands: vec![Command::SimpleCommand {
+                                    argv: literal_word_vec!["echo", "a"],
+                                    redirects: vec![],
+                                    assignments: vec![],
+                                }],
+                                timed: false,
+                            }],
+                            background: false,
+                        },
+                        Term {
+                            code: "return 3".into(),
+                            line: 1,
+                            pipelines: vec![Pipeline {
+                                run_if: RunIf::Always,
+                                commands: vec![Command::Return { status: Some(3) }],
+                                timed: false,
+                            }],
+                            background: false,
+                        },
+                    ],
+                }),
+            }]
+        );
+    }
+
+    #[test]
+    pub fn test_incremental() {
+        let mut parser = IncrementalParser::new();
+        assert_eq!(parser.feed("if true\n"), Err(ParseError::Incomplete));
+        assert_eq!(parser.feed("then\n"), Err(ParseError::Incomplete));
+        assert_eq!(parser.feed("  ls /tmp\n"), Err(ParseError::Incomplete));
+        assert!(!parser.is_empty());
+
+        let ast = parser.feed("fi\n").unwrap();
+        assert!(parser.is_empty());
+        assert_eq!(
+            ast.terms[0].pipelines[0].commands,
+            vec![Command::If {
+                condition: vec![Term {
+                    code: "true".into(),
+                    line: 1,
+                    pipelines: vec![Pipeline {
+                        run_if: RunIf::Always,
+                        commands: vec![Command::SimpleCommand {
+                            argv: literal_word_vec!["true"],
+                            redirects: vec![],
+                            assignments: vec![],
+                        }],
+                        timed: false,
+                    }],
+                    background: false
+                }],
+                then_part: vec![Term {
+                    code: "ls /tmp".into(),
+                    line: 3,
+                    pipelines: vec![Pipeline {
+                        run_if: RunIf::Always,
+                        commands: vec![Command::SimpleCommand {
+                            argv: literal_word_vec!["ls", "/tmp"],
+                            redirects: vec![],
+                            assignments: vec![],
+                        }],
+                        timed: false,
+                    }],
+                    background: false
+                }],
+                elif_parts: vec![],
+                else_part: None,
+            }]
+        );
+
+        assert_eq!(parse_incremental("ls |\n"), Err(ParseError::Incomplete));
+        assert_eq!(parse_incremental("ls &&"), Err(ParseError::Incomplete));
+        assert_eq!(
+            parse_incremental("echo \"abc\n"),
+            Err(ParseError::Incomplete)
+        );
+        assert!(matches!(
+            parse_incremental("ls )\n"),
+            Err(ParseError::Fatal(_))
+        ));
+        assert!(matches!(parse("if true\n"), Err(ParseError::Fatal(_))));
+    }
 }
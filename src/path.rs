@@ -1,5 +1,60 @@
+use nix::unistd::{access, AccessFlags};
 use std::collections::HashMap;
 use std::fs::read_dir;
+use std::path::Path;
+
+/// Replaces the leading home directory in `path` with `~` for display
+/// (e.g. `/home/user/src` -> `~/src`).
+pub fn abbreviate_home(path: &str) -> String {
+    match dirs::home_dir() {
+        Some(home_dir) => replace_home(path, &home_dir.to_string_lossy()),
+        None => path.to_owned(),
+    }
+}
+
+fn replace_home(path: &str, home_dir: &str) -> String {
+    let home_dir = home_dir.trim_end_matches('/');
+    if home_dir.is_empty() {
+        return path.to_owned();
+    }
+
+    match path.strip_prefix(home_dir) {
+        // Only if the home directory is a whole component: `/home/user2` is
+        // not in `/home/user`.
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => format!("~{}", rest),
+        _ => path.to_owned(),
+    }
+}
+
+/// The number of insertions, deletions, substitutions, and transpositions of
+/// adjacent characters to turn `a` into `b` (the optimal string alignment
+/// variant of the Damerau-Levenshtein distance).
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // d[i][j] is the distance between a[..i] and b[..j].
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+
+    d[a.len()][b.len()]
+}
 
 pub struct PathTable {
     /// `$PATH`
@@ -43,7 +98,94 @@ impl PathTable {
         }
     }
 
+    /// Searches `$PATH` for `cmd` and updates the table. Returns `false` if
+    /// it's not found.
+    pub fn rehash_command(&mut self, cmd: &str) -> bool {
+        match self.search(cmd).into_iter().next() {
+            Some(path) => {
+                self.table.insert(cmd.to_owned(), path);
+                true
+            }
+            None => {
+                self.table.remove(cmd);
+                false
+            }
+        }
+    }
+
     pub fn lookup(&self, cmd: &str) -> Option<&str> {
         self.table.get(cmd).map(String::as_str)
     }
+
+    /// The command closest to a missing command `cmd` if it looks like a
+    /// typo of it: one edit away, or two for a longer name.
+    pub fn suggest(&self, cmd: &str) -> Option<&str> {
+        let max_distance = (cmd.chars().count() / 3).clamp(1, 2);
+        self.table
+            .keys()
+            .map(|name| (edit_distance(cmd, name), name))
+            .filter(|(distance, _)| *distance <= max_distance)
+            .min()
+            .map(|(_, name)| name.as_str())
+    }
+
+    /// Searches `$PATH` for executables named `cmd` in order. Unlike `lookup`,
+    /// it reflects the current file system instead of the table.
+    pub fn search(&self, cmd: &str) -> Vec<String> {
+        self.path
+            .split(':')
+            .filter(|bin_dir| !bin_dir.is_empty())
+            .map(|bin_dir| Path::new(bin_dir).join(cmd))
+            .filter(|path| path.is_file() && access(path, AccessFlags::X_OK).is_ok())
+            .map(|path| path.to_string_lossy().into_owned())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn distance() {
+        assert_eq!(edit_distance("git", "git"), 0);
+        assert_eq!(edit_distance("gti", "git"), 1);
+        assert_eq!(edit_distance("gt", "git"), 1);
+        assert_eq!(edit_distance("giit", "git"), 1);
+        assert_eq!(edit_distance("gut", "git"), 1);
+        assert_eq!(edit_distance("pyhtno", "python"), 2);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn suggest_command() {
+        let dir = std::env::temp_dir().join(format!("smash-suggest-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["git", "grep", "python"] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+
+        let mut table = PathTable::new();
+        table.scan(&dir.to_string_lossy());
+        assert_eq!(table.suggest("gti"), Some("git"));
+        assert_eq!(table.suggest("grpe"), Some("grep"));
+        assert_eq!(table.suggest("pyhton"), Some("python"));
+        assert_eq!(table.suggest("xyzzy"), None);
+        assert_eq!(table.suggest("gx"), None);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn abbreviate_home_dir() {
+        assert_eq!(replace_home("/home/user", "/home/user"), "~");
+        assert_eq!(replace_home("/home/user/src/a", "/home/user"), "~/src/a");
+        assert_eq!(replace_home("/home/user/src", "/home/user/"), "~/src");
+        assert_eq!(replace_home("/home/user2", "/home/user"), "/home/user2");
+        assert_eq!(
+            replace_home("/tmp/home/user", "/home/user"),
+            "/tmp/home/user"
+        );
+        assert_eq!(replace_home("/etc", "/"), "/etc");
+    }
 }
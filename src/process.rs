@@ -1,15 +1,25 @@
 use crate::builtins::{BuiltinCommandContext, BuiltinCommandError};
 use crate::shell::Shell;
+use crate::variable::Value;
 
-use nix::sys::signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal};
+use nix::fcntl::{open, OFlag};
+use nix::sys::signal::{kill, killpg, sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal};
+use nix::sys::stat::Mode;
 use nix::sys::termios::{tcgetattr, tcsetattr, SetArg::TCSADRAIN, Termios};
 use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
-use nix::unistd::{execv, fork, getpid, setpgid, tcsetpgrp, ForkResult, Pid};
-use std::cell::RefCell;
+use nix::unistd::{
+    close, dup2, execv, execve, fork, getpid, isatty, setpgid, tcsetpgrp, unlink, ForkResult, Pid,
+};
+use std::cell::{Cell, RefCell};
 use std::ffi::CString;
 use std::fmt;
+use std::io::{BufRead, Read, Seek, SeekFrom, Write};
+use std::os::unix::io::{FromRawFd, IntoRawFd, RawFd};
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
-use tracing::debug;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tracing::{debug, warn};
 
 /// The process execution context.
 #[derive(Debug, Copy, Clone)]
@@ -18,6 +28,14 @@ pub struct Context {
     /// The process should be executed in background.
     pub background: bool,
     pub interactive: bool,
+    /// Ignore SIGHUP and move the output off the terminal (`nohup`).
+    pub nohup: bool,
+    /// The stdin and stdout given by the pipeline (`0` and `1` if not piped).
+    pub stdin: RawFd,
+    pub stdout: RawFd,
+    /// The read end of the pipe `stdout` writes to: a forked child closes it
+    /// so that it gets EPIPE once the next command exits.
+    pub pipe_out: Option<RawFd>,
 }
 
 /// The exit status or reason why the command exited.
@@ -27,6 +45,50 @@ pub enum ExitStatus {
     Running(Pid),
 }
 
+/// Reads from a file descriptor without buffering: a builtin must not
+/// consume more input than it uses since the rest belongs to the commands
+/// that follow (e.g. `read` in a script piped into the shell).
+pub struct FdReader(pub RawFd);
+
+impl Read for FdReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            match nix::unistd::read(self.0, buf) {
+                Err(nix::errno::Errno::EINTR) => continue,
+                result => return result.map_err(std::io::Error::from),
+            }
+        }
+    }
+}
+
+/// A redirection whose target has been expanded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Redirection {
+    /// Opens `path` with `flags` as `fd`.
+    File {
+        fd: RawFd,
+        path: String,
+        flags: OFlag,
+    },
+    /// Makes `fd` a copy of `target_fd` (`>&2`).
+    Fd { fd: RawFd, target_fd: RawFd },
+    /// Makes `body` readable from `fd` (`<<<`).
+    HereString { fd: RawFd, body: String },
+    /// Closes `fd` (`>&-`).
+    Close { fd: RawFd },
+}
+
+impl Redirection {
+    pub fn fd(&self) -> RawFd {
+        match self {
+            Redirection::File { fd, .. }
+            | Redirection::Fd { fd, .. }
+            | Redirection::HereString { fd, .. }
+            | Redirection::Close { fd } => *fd,
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum ProcessState {
     Running,
@@ -36,7 +98,18 @@ pub enum ProcessState {
     Stopped(Pid),
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+/// The state of a job as a whole. A job goes from `Running` (or `Stopped`)
+/// to `Done`, and stays in the job table until its status is reported or
+/// retrieved by `wait`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum JobState {
+    Running,
+    Stopped,
+    /// Contains the exit status of the last process.
+    Done(i32),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct JobId(usize);
 
 impl JobId {
@@ -56,10 +129,14 @@ impl fmt::Display for JobId {
 pub struct Job {
     id: JobId,
     pub pgid: Pid,
+    /// The command line as typed. `jobs` and the notices print it as is
+    /// instead of quoting it with `shell_quote`: it's shell syntax already.
     pub cmd: String,
     // TODO: Remove entries in shell.states on destruction.
     pub processes: Vec<Pid>,
     pub termios: RefCell<Option<Termios>>,
+    /// Don't send SIGHUP to the job when the shell exits (`disown -h`).
+    pub nohup: Cell<bool>,
 }
 
 impl Job {
@@ -70,6 +147,25 @@ impl Job {
             cmd,
             processes,
             termios: RefCell::new(None),
+            nohup: Cell::new(false),
+        }
+    }
+
+    #[inline]
+    pub fn id(&self) -> JobId {
+        self.id
+    }
+
+    pub fn state(&self, shell: &Shell) -> JobState {
+        if self.completed(shell) {
+            match shell.get_process_state(*self.processes.last().unwrap()) {
+                Some(ProcessState::Completed(status)) => JobState::Done(*status),
+                _ => JobState::Done(0),
+            }
+        } else if self.stopped(shell) {
+            JobState::Stopped
+        } else {
+            JobState::Running
         }
     }
 
@@ -80,34 +176,171 @@ impl Job {
         })
     }
 
+    /// No process is running and some are stopped (the others may have
+    /// completed, e.g. `cat file | less` after `cat` exits).
     pub fn stopped(&self, shell: &Shell) -> bool {
-        self.processes.iter().all(|pid| {
-            let state = shell.get_process_state(*pid).unwrap();
-            matches!(state, ProcessState::Stopped(_))
-        })
+        let states: Vec<&ProcessState> = self
+            .processes
+            .iter()
+            .map(|pid| shell.get_process_state(*pid).unwrap())
+            .collect();
+        !states.contains(&&ProcessState::Running)
+            && states
+                .iter()
+                .any(|state| matches!(state, ProcessState::Stopped(_)))
     }
 }
 
-pub fn run_internal_command(shell: &mut Shell, argv: &[String]) -> anyhow::Result<ExitStatus> {
+/// Resolves a job specifier: `%N` or `N` (the job number N) or `%%`/`%+`
+/// (the current job).
+pub fn resolve_job_spec(shell: &Shell, spec: &str) -> Option<Rc<Job>> {
+    match spec {
+        "%%" | "%+" => shell.current_job(),
+        _ => {
+            let id = spec.strip_prefix('%').unwrap_or(spec).parse().ok()?;
+            shell.jobs().get(&JobId::new(id)).cloned()
+        }
+    }
+}
+
+pub fn run_internal_command(
+    ctx: &Context,
+    shell: &mut Shell,
+    argv: &[String],
+    redirects: &[Redirection],
+) -> anyhow::Result<ExitStatus> {
     let command = match crate::builtins::builtin_command(argv[0].as_str()) {
         Some(func) => func,
         _ => return Err(BuiltinCommandError::NotFound.into()),
     };
 
-    // TODO: support redirections
+    // Builtins run in the shell process: redirect its file descriptors
+    // during the command and restore them afterwards.
+    Ok(with_redirections(redirects, || {
+        let mut builtin_ctx = BuiltinCommandContext::new(argv, shell, ctx);
+        for redirect in redirects {
+            builtin_ctx.redirected(redirect.fd());
+        }
+
+        match command.run(&mut builtin_ctx) {
+            Ok(status) => status,
+            Err(err) => {
+                writeln!(builtin_ctx.stderr(), "smash: {}: {}", command.name(), err).ok();
+                ExitStatus::ExitedWith(1)
+            }
+        }
+    }))
+}
+
+/// Runs `f` in the shell process with its file descriptors redirected and
+/// restores them afterwards.
+pub fn with_redirections<F>(redirects: &[Redirection], f: F) -> ExitStatus
+where
+    F: FnOnce() -> ExitStatus,
+{
+    let saved_fds: Vec<(RawFd, Option<RawFd>)> = redirects
+        .iter()
+        .map(|redirect| (redirect.fd(), save_fd(redirect.fd())))
+        .collect();
+
+    let result = match apply_redirections(redirects) {
+        Ok(()) => f(),
+        Err(err) => {
+            smash_err!("{}", err);
+            ExitStatus::ExitedWith(1)
+        }
+    };
+
+    std::io::stdout().flush().ok();
+    std::io::stderr().flush().ok();
+    for (fd, saved) in saved_fds.into_iter().rev() {
+        match saved {
+            Some(saved) => {
+                dup2(saved, fd).ok();
+                close(saved).ok();
+            }
+            None => {
+                close(fd).ok();
+            }
+        }
+    }
+
+    result
+}
+
+/// Duplicates `fd` out of the way of redirections. Returns `None` if `fd` is
+/// not open.
+fn save_fd(fd: RawFd) -> Option<RawFd> {
+    nix::fcntl::fcntl(fd, nix::fcntl::FcntlArg::F_DUPFD_CLOEXEC(10)).ok()
+}
+
+/// Opens the redirection targets and installs them as the file descriptors
+/// of the current process.
+pub fn apply_redirections(redirects: &[Redirection]) -> anyhow::Result<()> {
+    for redirect in redirects {
+        match redirect {
+            Redirection::File { fd, path, flags } => {
+                let mode = Mode::from_bits_truncate(0o644);
+                let file_fd = open(path.as_str(), *flags, mode)
+                    .map_err(|err| anyhow::anyhow!("{}: {}", path, err.desc()))?;
+                if file_fd != *fd {
+                    dup2(file_fd, *fd)?;
+                    close(file_fd)?;
+                }
+            }
+            Redirection::Fd { fd, target_fd } => {
+                dup2(*target_fd, *fd)
+                    .map_err(|err| anyhow::anyhow!("{}: {}", target_fd, err.desc()))?;
+            }
+            Redirection::HereString { fd, body } => {
+                let file_fd = here_string_file(body)?;
+                dup2(file_fd, *fd)?;
+                close(file_fd)?;
+            }
+            Redirection::Close { fd } => {
+                // Closing a descriptor which is not open is not an error.
+                close(*fd).ok();
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes a here string into an unlinked temporary file and returns it
+/// opened at the beginning. Unlike a pipe, it does not block on a large body.
+fn here_string_file(body: &str) -> anyhow::Result<RawFd> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let path = std::env::temp_dir().join(format!(
+        "smash-here-{}-{}",
+        getpid(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
 
-    let result = command.run(&mut BuiltinCommandContext { argv, shell });
+    let flags = OFlag::O_RDWR | OFlag::O_CREAT | OFlag::O_EXCL | OFlag::O_CLOEXEC;
+    let fd = open(&path, flags, Mode::from_bits_truncate(0o600))?;
+    unlink(&path).ok();
 
-    Ok(result)
+    let mut file = unsafe { std::fs::File::from_raw_fd(fd) };
+    let result = file
+        .write_all(body.as_bytes())
+        .and_then(|_| file.seek(SeekFrom::Start(0)));
+    let fd = file.into_raw_fd();
+    if let Err(err) = result {
+        close(fd).ok();
+        return Err(err.into());
+    }
+
+    Ok(fd)
 }
 
 pub fn run_external_command(
     ctx: &Context,
     shell: &mut Shell,
     argv: Vec<String>,
+    redirects: &[Redirection],
+    assignments: &[(String, String)],
 ) -> anyhow::Result<ExitStatus> {
-    // TODO: support redirections
-
     let argv0 = if argv[0].starts_with('/') || argv[0].starts_with("./") {
         CString::new(argv[0].as_str())?
     } else {
@@ -115,7 +348,13 @@ pub fn run_external_command(
             Some(path) => CString::new(path)?,
             None => {
                 smash_err!("command not found `{}`", argv[0]);
-                return Ok(ExitStatus::ExitedWith(1));
+                if shell.is_shopt_set("correct") {
+                    if let Some(suggestion) = shell.path_table().suggest(&argv[0]) {
+                        smash_err!("did you mean '{}'?", suggestion);
+                    }
+                }
+
+                return Ok(ExitStatus::ExitedWith(127));
             }
         }
     };
@@ -125,61 +364,240 @@ pub fn run_external_command(
         args.push(CString::new(arg)?);
     }
 
+    let envs = environ(shell, assignments)?;
+
     // Spawn a child.
     match unsafe { fork() }.expect("failed to fork") {
         ForkResult::Parent { child } => Ok(ExitStatus::Running(child)),
         ForkResult::Child => {
-            // Create or join a process group.
-            if ctx.interactive {
-                let pid = getpid();
-                let pgid = match ctx.pgid {
-                    Some(pgid) => {
-                        setpgid(pid, pgid).expect("failed to setpgid");
-                        pgid
-                    }
-                    None => {
-                        setpgid(pid, pid).expect("failed to setpgid");
-                        pid
-                    }
-                };
-
-                if !ctx.background {
-                    set_terminal_process_group(pgid);
-                    restore_terminal_attrs(shell.shell_termios.as_ref().unwrap());
-                }
-
-                // Accept job-control-related signals (refer https://www.gnu.org/software/libc/manual/html_node/Launching-Jobs.html)
-                let action = SigAction::new(SigHandler::SigDfl, SaFlags::empty(), SigSet::empty());
-                unsafe {
-                    sigaction(Signal::SIGINT, &action).expect("failed to sigaction");
-                    sigaction(Signal::SIGQUIT, &action).expect("failed to sigaction");
-                    sigaction(Signal::SIGTSTP, &action).expect("failed to sigaction");
-                    sigaction(Signal::SIGTTIN, &action).expect("failed to sigaction");
-                    sigaction(Signal::SIGTTOU, &action).expect("failed to sigaction");
-                    sigaction(Signal::SIGCHLD, &action).expect("failed to sigaction");
-                }
+            join_job(shell, ctx);
+            if ctx.nohup {
+                detach_from_hangup();
             }
 
-            // TODO: support assigns and exported variables
+            if let Err(err) = apply_redirections(redirects) {
+                smash_err!("{}", err);
+                std::process::exit(1);
+            }
 
             let args: Vec<&std::ffi::CStr> = args.iter().map(|s| s.as_c_str()).collect();
-            match execv(&argv0, &args) {
+            match execve(&argv0, &args, &envs) {
                 Ok(_) => {
                     unreachable!();
                 }
                 Err(nix::errno::Errno::EACCES) => {
                     smash_err!("Failed to exec {:?} (EACCESS). chmod(1) may help.", argv0);
-                    std::process::exit(1);
+                    std::process::exit(126);
+                }
+                Err(nix::errno::Errno::ENOENT) => {
+                    smash_err!("command not found `{}`", argv0.to_string_lossy());
+                    std::process::exit(127);
+                }
+                Err(nix::errno::Errno::ENOEXEC) => {
+                    // Not a binary nor a script with `#!`: run it as a shell
+                    // script.
+                    for (name, value) in assignments {
+                        shell.set(name, Value::String(value.clone()), false);
+                        shell.export(name, true);
+                    }
+
+                    let args: Vec<String> = args[1..]
+                        .iter()
+                        .map(|arg| arg.to_string_lossy().into_owned())
+                        .collect();
+                    let status = run_script_file(shell, &argv0.to_string_lossy(), &args);
+                    std::process::exit(status);
                 }
                 Err(err) => {
                     smash_err!("Failed to exec {:?} ({})", argv0, err);
-                    std::process::exit(1);
+                    std::process::exit(126);
                 }
             }
         }
     }
 }
 
+/// Called in a forked child of a job: creates or joins the process group of
+/// the job if job control is enabled.
+pub fn join_job(shell: &Shell, ctx: &Context) {
+    if !ctx.interactive {
+        return;
+    }
+
+    let pid = getpid();
+    let pgid = match ctx.pgid {
+        Some(pgid) => {
+            setpgid(pid, pgid).expect("failed to setpgid");
+            pgid
+        }
+        None => {
+            setpgid(pid, pid).expect("failed to setpgid");
+            pid
+        }
+    };
+
+    if !ctx.background {
+        set_terminal_process_group(pgid);
+        restore_terminal_attrs(shell.shell_termios.as_ref().unwrap());
+    }
+
+    // Accept job-control-related signals (refer https://www.gnu.org/software/libc/manual/html_node/Launching-Jobs.html)
+    let action = SigAction::new(SigHandler::SigDfl, SaFlags::empty(), SigSet::empty());
+    unsafe {
+        sigaction(Signal::SIGINT, &action).expect("failed to sigaction");
+        sigaction(Signal::SIGQUIT, &action).expect("failed to sigaction");
+        sigaction(Signal::SIGTSTP, &action).expect("failed to sigaction");
+        sigaction(Signal::SIGTTIN, &action).expect("failed to sigaction");
+        sigaction(Signal::SIGTTOU, &action).expect("failed to sigaction");
+        sigaction(Signal::SIGCHLD, &action).expect("failed to sigaction");
+    }
+}
+
+/// Runs a script file with `args` as the positional parameters in the current
+/// process and returns the exit status.
+pub fn run_script_file(shell: &mut Shell, path: &str, args: &[String]) -> i32 {
+    let file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(err) => {
+            smash_err!("{}: {}", path, err);
+            return if err.kind() == std::io::ErrorKind::NotFound {
+                127
+            } else {
+                126
+            };
+        }
+    };
+
+    shell.enter_subshell();
+    shell.set_args(args.to_vec());
+    let status = match shell.run_reader(std::io::BufReader::new(file)) {
+        ExitStatus::ExitedWith(status) => status,
+        ExitStatus::Running(_) => 0,
+    };
+
+    let status = shell.run_exit_trap(status);
+    shell.stdout().flush().ok();
+    status
+}
+
+/// The environment of a command run from the shell: the exported variables
+/// at the moment and the assignments given to the command, which precede
+/// exported variables of the same name.
+pub fn environ(shell: &Shell, assignments: &[(String, String)]) -> anyhow::Result<Vec<CString>> {
+    let mut envs = Vec::new();
+    for (name, value) in shell.exported_vars() {
+        if assignments.iter().all(|(assigned, _)| *assigned != name) {
+            envs.push(CString::new(format!("{}={}", name, value))?);
+        }
+    }
+
+    for (name, value) in assignments {
+        envs.push(CString::new(format!("{}={}", name, value))?);
+    }
+
+    Ok(envs)
+}
+
+/// Executes the interpreter in the `#!interpreter [arg]` line of a script
+/// as the kernel does. Returns `Ok` if the script does not have the line or
+/// the interpreter is this shell.
+pub fn exec_shebang(path: &str, args: &[String]) -> anyhow::Result<()> {
+    // Reported with the path like `run_script_file` does.
+    let file = std::fs::File::open(path)
+        .map_err(|err| std::io::Error::new(err.kind(), format!("{}: {}", path, err)))?;
+    let mut first_line = String::new();
+    std::io::BufReader::new(file).read_line(&mut first_line)?;
+    let line = match first_line.strip_prefix("#!") {
+        Some(line) => line.trim(),
+        None => return Ok(()),
+    };
+
+    let (interpreter, arg) = match line.split_once(char::is_whitespace) {
+        Some((interpreter, arg)) => (interpreter, Some(arg.trim())),
+        None => (line, None),
+    };
+
+    if interpreter.is_empty() || runs_this_shell(interpreter, arg) {
+        return Ok(());
+    }
+
+    let mut argv = vec![CString::new(interpreter)?];
+    if let Some(arg) = arg {
+        argv.push(CString::new(arg)?);
+    }
+
+    argv.push(CString::new(path)?);
+    for arg in args {
+        argv.push(CString::new(arg.as_str())?);
+    }
+
+    let err = match execv(&argv[0], &argv) {
+        Ok(_) => unreachable!(),
+        Err(err) => err,
+    };
+
+    anyhow::bail!("{}: {}: bad interpreter: {}", path, interpreter, err.desc())
+}
+
+/// Returns true if `#!interpreter arg` (including `#!/usr/bin/env smash`)
+/// runs the current executable, which would exec itself forever.
+fn runs_this_shell(interpreter: &str, arg: Option<&str>) -> bool {
+    let program = match (Path::new(interpreter).file_name(), arg) {
+        (Some(name), Some(arg)) if name == "env" => {
+            let paths = std::env::var_os("PATH").unwrap_or_default();
+            match std::env::split_paths(&paths)
+                .map(|dir| dir.join(arg))
+                .find(|path| path.is_file())
+            {
+                Some(path) => path,
+                None => return false,
+            }
+        }
+        _ => PathBuf::from(interpreter),
+    };
+
+    match (
+        std::fs::canonicalize(program),
+        std::env::current_exe().and_then(std::fs::canonicalize),
+    ) {
+        (Ok(program), Ok(exe)) => program == exe,
+        _ => false,
+    }
+}
+
+/// Prepares the current (child) process for `nohup`: ignore SIGHUP and, if
+/// the output goes to the terminal, append it to `nohup.out` instead.
+fn detach_from_hangup() {
+    let action = SigAction::new(SigHandler::SigIgn, SaFlags::empty(), SigSet::empty());
+    unsafe {
+        sigaction(Signal::SIGHUP, &action).expect("failed to sigaction");
+    }
+
+    if !isatty(1).unwrap_or(false) {
+        return;
+    }
+
+    let flags = OFlag::O_WRONLY | OFlag::O_CREAT | OFlag::O_APPEND;
+    let mode = Mode::S_IRUSR | Mode::S_IWUSR;
+    let mut candidates = vec![std::path::PathBuf::from("nohup.out")];
+    if let Some(home_dir) = dirs::home_dir() {
+        candidates.push(home_dir.join("nohup.out"));
+    }
+
+    for path in candidates {
+        if let Ok(fd) = open(&path, flags, mode) {
+            smash_err!("nohup: ignoring input and appending output to {:?}", path);
+            dup2(fd, 1).expect("failed to dup2");
+            if isatty(2).unwrap_or(false) {
+                dup2(fd, 2).expect("failed to dup2");
+            }
+            return;
+        }
+    }
+
+    smash_err!("nohup: failed to open nohup.out");
+}
+
 pub fn run_in_foreground(shell: &mut Shell, job: &Rc<Job>) -> ProcessState {
     debug!("run_in_foreground");
     set_terminal_process_group(job.pgid);
@@ -197,19 +615,108 @@ pub fn run_in_foreground(shell: &mut Shell, job: &Rc<Job>) -> ProcessState {
     status
 }
 
+/// Sends a signal to all processes in the job. Note that only an interactive
+/// shell puts each job into its own process group.
+pub fn kill_job<T: Into<Option<Signal>> + Copy>(
+    shell: &Shell,
+    job: &Job,
+    signal: T,
+) -> nix::Result<()> {
+    if shell.interactive() {
+        return killpg(job.pgid, signal);
+    }
+
+    for pid in &job.processes {
+        if !matches!(
+            shell.get_process_state(*pid),
+            Some(ProcessState::Completed(_))
+        ) {
+            kill(*pid, signal)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Exits the shell (`exit` and `set -e`) with `status` unless the EXIT trap
+/// exits with another one.
+pub fn exit_shell(shell: &mut Shell, status: i32) -> ! {
+    let status = shell.run_exit_trap(status);
+    shell.stdout().flush().ok();
+    hangup_jobs(shell);
+    std::process::exit(status);
+}
+
+/// Sends SIGHUP to the jobs before the shell exits except for ones marked by
+/// `disown -h`. Stopped jobs are continued as well so that they can handle it.
+pub fn hangup_jobs(shell: &Shell) {
+    for job in shell.jobs().values() {
+        if job.nohup.get() || job.completed(shell) {
+            continue;
+        }
+
+        kill_job(shell, job, Signal::SIGHUP).ok();
+        if job.stopped(shell) {
+            kill_job(shell, job, Signal::SIGCONT).ok();
+        }
+    }
+}
+
+/// Resumes a stopped job (`fg` and `bg`). A job continued in the foreground
+/// is waited for; otherwise `ProcessState::Running` is returned immediately.
+pub fn continue_job(shell: &mut Shell, job: &Rc<Job>, background: bool) -> ProcessState {
+    for pid in &job.processes {
+        if let Some(ProcessState::Stopped(_)) = shell.get_process_state(*pid) {
+            shell.set_process_state(*pid, ProcessState::Running);
+        }
+    }
+
+    if !background && shell.interactive() {
+        // Give back the terminal modes the job had when it got stopped.
+        set_terminal_process_group(job.pgid);
+        if let Some(termios) = job.termios.borrow().as_ref() {
+            restore_terminal_attrs(termios);
+        }
+    }
+
+    if let Err(err) = kill_job(shell, job, Signal::SIGCONT) {
+        smash_err!("failed to continue the job {}: {}", job.id, err);
+    }
+
+    if background {
+        ProcessState::Running
+    } else if shell.interactive() {
+        run_in_foreground(shell, job)
+    } else {
+        wait_for_job(shell, job)
+    }
+}
+
 pub fn wait_for_job(shell: &mut Shell, job: &Rc<Job>) -> ProcessState {
-    loop {
-        if job.completed(shell) || job.stopped(shell) {
-            break;
+    while !job.completed(shell) && !job.stopped(shell) {
+        let running = job
+            .processes
+            .iter()
+            .find(|pid| shell.get_process_state(**pid) == Some(&ProcessState::Running));
+        if let Some(pid) = running {
+            wait_for_process(shell, *pid, false);
         }
 
-        wait_for_any_process(shell, false);
+        // Processes of other jobs finished meanwhile update their own states.
+        while wait_for_any_process(shell, true).is_some() {}
     }
 
-    // Get the exit status of the last process.
-    let state = shell
+    // Get the exit status of the last process. A stopped job is reported as
+    // stopped even if the last process has completed.
+    let last = shell
         .get_process_state(*job.processes.iter().last().unwrap())
         .cloned();
+    let state = match last {
+        Some(ProcessState::Completed(_)) if job.stopped(shell) => {
+            Some(ProcessState::Stopped(job.pgid))
+        }
+        state => state,
+    };
 
     match state {
         Some(ProcessState::Completed(_)) => {
@@ -219,41 +726,93 @@ pub fn wait_for_job(shell: &mut Shell, job: &Rc<Job>) -> ProcessState {
         }
         Some(ProcessState::Stopped(_)) => {
             smash_err!("[{}] Stopped: {}", job.id, job.cmd);
+            // `fg` and `bg` without arguments resume this job.
+            shell.last_fore_job = Some(job.clone());
             state.unwrap()
         }
         _ => unreachable!(),
     }
 }
 
+/// Waits for a state change of one of the processes started by the shell.
+/// Only the shell's own children are waited for (not `waitpid(-1)`): other
+/// shells in the same process, e.g. in tests, reap their own children.
 pub fn wait_for_any_process(shell: &mut Shell, no_block: bool) -> Option<Pid> {
+    loop {
+        let pids: Vec<Pid> = shell.live_processes();
+        if pids.is_empty() {
+            return None;
+        }
+
+        for pid in pids {
+            if wait_for_process(shell, pid, true) {
+                return Some(pid);
+            }
+        }
+
+        if no_block {
+            return None;
+        }
+
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/// Waits for a state change of `pid` and updates its state. Returns false if
+/// `no_block` is set and nothing has changed.
+pub fn wait_for_process(shell: &mut Shell, pid: Pid, no_block: bool) -> bool {
     let options = if no_block {
-        WaitPidFlag::WUNTRACED | WaitPidFlag::WNOHANG
+        WaitPidFlag::WUNTRACED | WaitPidFlag::WCONTINUED | WaitPidFlag::WNOHANG
     } else {
-        WaitPidFlag::WUNTRACED
+        WaitPidFlag::WUNTRACED | WaitPidFlag::WCONTINUED
     };
 
-    let result = waitpid(None, Some(options));
+    let result = waitpid_ignoring_eintr(|| waitpid(pid, Some(options)));
     let (pid, state) = match result {
         Ok(WaitStatus::Exited(pid, status)) => {
             debug!("exited: pid={} status={}", pid, status);
             (pid, ProcessState::Completed(status))
         }
-        Ok(WaitStatus::Signaled(pid, _signal, _)) => {
-            // The `pid` process has been killed by `_signal`.
-            (pid, ProcessState::Completed(-1))
+        Ok(WaitStatus::Signaled(pid, signal, _)) => {
+            // The `pid` process has been killed by `signal`.
+            (pid, ProcessState::Completed(128 + signal as i32))
         }
         Ok(WaitStatus::Stopped(pid, _signal)) => (pid, ProcessState::Stopped(pid)),
-        Err(nix::errno::Errno::ECHILD) | Ok(WaitStatus::StillAlive) => {
-            // No childs to be reported.
-            return None;
+        Ok(WaitStatus::Continued(pid)) => {
+            // Resumed by SIGCONT (e.g. `kill -CONT` from another terminal).
+            debug!("continued: pid={}", pid);
+            (pid, ProcessState::Running)
+        }
+        Ok(WaitStatus::StillAlive) => return false,
+        Err(nix::errno::Errno::ECHILD) => {
+            // Not our child (e.g. a job of the parent shell in a subshell):
+            // the exit status is unknown.
+            warn!("waitpid: {} is not a child", pid);
+            (pid, ProcessState::Completed(127))
         }
         status => {
-            panic!("unexpected waitpid event: {:?}", status);
+            warn!("unexpected waitpid event: {:?}", status);
+            return false;
         }
     };
 
     shell.set_process_state(pid, state);
-    Some(pid)
+    true
+}
+
+/// Calls `wait` repeatedly until it is not interrupted by a signal (`EINTR`).
+fn waitpid_ignoring_eintr<F>(mut wait: F) -> nix::Result<WaitStatus>
+where
+    F: FnMut() -> nix::Result<WaitStatus>,
+{
+    loop {
+        match wait() {
+            Err(nix::errno::Errno::EINTR) => {
+                debug!("waitpid: interrupted by a signal, retrying");
+            }
+            result => return result,
+        }
+    }
 }
 
 pub fn set_terminal_process_group(pgid: Pid) {
@@ -264,6 +823,26 @@ pub fn restore_terminal_attrs(termios: &Termios) {
     tcsetattr(0, TCSADRAIN, termios).expect("failed to tcsetattr");
 }
 
+/// Reaps background processes without blocking and removes the completed
+/// jobs, which are to be reported at the next prompt. Until then, their
+/// status can be retrieved by `wait`.
+pub fn collect_finished_jobs(shell: &mut Shell) -> Vec<Rc<Job>> {
+    while wait_for_any_process(shell, true).is_some() {}
+
+    let mut finished: Vec<Rc<Job>> = shell
+        .jobs()
+        .values()
+        .filter(|job| job.completed(shell))
+        .cloned()
+        .collect();
+    finished.sort_by_key(|job| job.id());
+    for job in &finished {
+        destroy_job(shell, job);
+    }
+
+    finished
+}
+
 pub fn destroy_job(shell: &mut Shell, job: &Rc<Job>) {
     // TODO: support background jobs
 
@@ -275,3 +854,171 @@ pub fn destroy_job(shell: &mut Shell, job: &Rc<Job>) {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::shell::{SharedBuffer, FD_LOCK};
+
+    #[test]
+    fn wait_for_jobs_finished_in_any_order() {
+        let mut shell = Shell::with_io(std::io::empty(), std::io::sink(), std::io::sink());
+        shell.run_script("/bin/sleep 0.3 &");
+        let slow = shell.current_job().unwrap();
+        shell.run_script("/bin/sleep 0.05 &");
+        let fast = shell.current_job().unwrap();
+
+        // The fast job is reaped while waiting for the slow one.
+        assert_eq!(wait_for_job(&mut shell, &slow), ProcessState::Completed(0));
+        assert!(fast.completed(&shell));
+        assert_eq!(wait_for_job(&mut shell, &fast), ProcessState::Completed(0));
+    }
+
+    #[test]
+    fn retain_status_of_finished_job() {
+        let mut shell = Shell::with_io(std::io::empty(), std::io::sink(), std::io::sink());
+        shell.run_script("/bin/sh -c 'exit 5' &");
+        let job = shell.current_job().unwrap();
+        // The background job is reaped while waiting for the foreground one.
+        shell.run_script("/bin/sleep 0.2");
+        assert_eq!(job.state(&shell), JobState::Done(5));
+        assert!(shell.jobs().contains_key(&job.id()));
+
+        assert_eq!(shell.run_script("wait %1"), ExitStatus::ExitedWith(5));
+        assert!(shell.jobs().is_empty());
+    }
+
+    #[test]
+    fn job_spec_without_percent() {
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        shell.run_script("/bin/sleep 0.1 &");
+        let job = shell.current_job().unwrap();
+        assert_eq!(resolve_job_spec(&shell, "1").unwrap().id(), job.id());
+        assert_eq!(resolve_job_spec(&shell, "%1").unwrap().id(), job.id());
+        assert!(resolve_job_spec(&shell, "2").is_none());
+        assert!(resolve_job_spec(&shell, "x").is_none());
+
+        assert_eq!(shell.run_script("fg 1"), ExitStatus::ExitedWith(0));
+        assert_eq!(stdout.contents(), "/bin/sleep 0.1\n");
+        assert!(shell.jobs().is_empty());
+        assert_eq!(shell.run_script("fg 1"), ExitStatus::ExitedWith(1));
+    }
+
+    #[test]
+    fn wait_for_non_child() {
+        let mut shell = Shell::with_io(std::io::empty(), std::io::sink(), std::io::sink());
+        // pid 1 is not our child: its exit status is unknown.
+        let init = Pid::from_raw(1);
+        let job = shell.create_job("init".to_owned(), init, vec![init]);
+        assert_eq!(wait_for_job(&mut shell, &job), ProcessState::Completed(127));
+        assert!(shell.jobs().is_empty());
+    }
+
+    #[test]
+    fn retry_interrupted_waitpid() {
+        let pid = Pid::from_raw(12345);
+        let mut calls = 0;
+        let result = waitpid_ignoring_eintr(|| {
+            calls += 1;
+            if calls < 3 {
+                Err(nix::errno::Errno::EINTR)
+            } else {
+                Ok(WaitStatus::Exited(pid, 0))
+            }
+        });
+
+        assert_eq!(result, Ok(WaitStatus::Exited(pid, 0)));
+        assert_eq!(calls, 3);
+    }
+
+    #[test]
+    fn waitpid_errors_are_not_retried() {
+        let mut calls = 0;
+        let result = waitpid_ignoring_eintr(|| {
+            calls += 1;
+            Err(nix::errno::Errno::ECHILD)
+        });
+
+        assert_eq!(result, Err(nix::errno::Errno::ECHILD));
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn run_text_file_as_script() {
+        let _lock = FD_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        use std::os::unix::fs::PermissionsExt;
+
+        let base = std::env::temp_dir().join(format!("smash-enoexec-{}", std::process::id()));
+        let script = format!("{}.sh", base.display());
+        let out = format!("{}.out", base.display());
+        std::fs::write(
+            &script,
+            format!("x=hello\necho $x $1 > {}\n/bin/false\n", out),
+        )
+        .unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut shell = Shell::new();
+        let status = shell.run_script(&format!("{} world", script));
+        assert_eq!(status, ExitStatus::ExitedWith(1));
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "hello world\n");
+
+        std::fs::remove_file(&script).ok();
+        std::fs::remove_file(&out).ok();
+    }
+
+    #[test]
+    fn bad_interpreter_in_shebang() {
+        // Executing interpreters is tested by running the binary in
+        // tests/smash.rs.
+        let base = std::env::temp_dir().join(format!("smash-shebang-{}", std::process::id()));
+        let script = format!("{}.txt", base.display());
+        std::fs::write(&script, "#!/no/such/interpreter\n").unwrap();
+        let err = exec_shebang(&script, &[]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "{}: /no/such/interpreter: bad interpreter: No such file or directory",
+                script
+            )
+        );
+
+        std::fs::write(&script, "echo hello\n").unwrap();
+        assert!(exec_shebang(&script, &[]).is_ok());
+        std::fs::remove_file(&script).ok();
+    }
+
+    #[test]
+    fn shebang_of_this_shell() {
+        let exe = std::env::current_exe().unwrap();
+        assert!(runs_this_shell(exe.to_str().unwrap(), None));
+        assert!(!runs_this_shell("/bin/sh", None));
+        assert!(!runs_this_shell("/usr/bin/env", Some("sh")));
+        assert!(!runs_this_shell("/no/such/interpreter", None));
+    }
+
+    #[test]
+    fn not_found_or_not_executable() {
+        let path = std::env::temp_dir().join(format!("smash-noexec-{}", std::process::id()));
+        std::fs::write(&path, "").unwrap();
+
+        let mut shell = Shell::with_io(std::io::empty(), std::io::sink(), std::io::sink());
+        assert_eq!(
+            shell.run_script("no-such-command"),
+            ExitStatus::ExitedWith(127)
+        );
+        assert_eq!(shell.last_status(), 127);
+        assert_eq!(
+            shell.run_script("/no/such/command"),
+            ExitStatus::ExitedWith(127)
+        );
+        assert_eq!(
+            shell.run_script(&path.to_string_lossy()),
+            ExitStatus::ExitedWith(126)
+        );
+        assert_eq!(shell.last_status(), 126);
+
+        std::fs::remove_file(&path).ok();
+    }
+}
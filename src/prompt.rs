@@ -0,0 +1,189 @@
+//! `$PS1` escape sequences.
+use crate::path::abbreviate_home;
+use crate::shell::Shell;
+
+use nix::libc;
+use nix::unistd::{gethostname, getuid};
+use std::ffi::CString;
+use std::time::Duration;
+
+/// Expands the backslash escapes in `ps1`:
+///
+/// - `\u`: the user name
+/// - `\h`: the host name up to the first `.`
+/// - `\w`: the current directory (`~` for home)
+/// - `\W`: the last component of the current directory
+/// - `\$`: `#` for root or `$` otherwise
+/// - `\t` / `\A`: the current time (`HH:MM:SS` / `HH:MM`)
+/// - `\D{format}`: the current time formatted by `strftime(3)`
+/// - `\E`: how long the last command took
+/// - `\n` / `\\`: a newline / a backslash
+pub fn expand_prompt(shell: &Shell, ps1: &str, elapsed: Option<Duration>) -> String {
+    let mut prompt = String::new();
+    let mut chars = ps1.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            prompt.push(ch);
+            continue;
+        }
+
+        match chars.next() {
+            Some('u') => {
+                if let Some(var) = shell.get("USER") {
+                    prompt.push_str(var.as_str());
+                }
+            }
+            Some('h') => {
+                let hostname = hostname();
+                prompt.push_str(hostname.split('.').next().unwrap_or_default());
+            }
+            Some('w') => prompt.push_str(&abbreviate_home(&current_dir())),
+            Some('W') => {
+                let cwd = abbreviate_home(&current_dir());
+                match cwd.rsplit_once('/') {
+                    Some((_, name)) if !name.is_empty() => prompt.push_str(name),
+                    _ => prompt.push_str(&cwd),
+                }
+            }
+            Some('$') => prompt.push(if getuid().is_root() { '#' } else { '$' }),
+            Some('t') => prompt.push_str(&format_time("%H:%M:%S", &local_time())),
+            Some('A') => prompt.push_str(&format_time("%H:%M", &local_time())),
+            Some('D') => {
+                let rest = chars.as_str();
+                match rest.strip_prefix('{').and_then(|rest| rest.split_once('}')) {
+                    Some((format, after)) => {
+                        // An empty format is the locale's time representation.
+                        let format = if format.is_empty() { "%X" } else { format };
+                        prompt.push_str(&format_time(format, &local_time()));
+                        chars = after.chars();
+                    }
+                    None => prompt.push_str("\\D"),
+                }
+            }
+            Some('E') => {
+                if let Some(elapsed) = elapsed {
+                    prompt.push_str(&format_elapsed(elapsed));
+                }
+            }
+            Some('n') => prompt.push('\n'),
+            Some('\\') => prompt.push('\\'),
+            Some(ch) => {
+                prompt.push('\\');
+                prompt.push(ch);
+            }
+            None => prompt.push('\\'),
+        }
+    }
+
+    prompt
+}
+
+fn current_dir() -> String {
+    std::env::current_dir()
+        .map(|dir| dir.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn hostname() -> String {
+    gethostname()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn local_time() -> libc::tm {
+    // SAFETY: `time` accepts a null pointer.
+    local_time_at(unsafe { libc::time(std::ptr::null_mut()) })
+}
+
+/// Converts seconds since the epoch to the local time.
+pub fn local_time_at(time: libc::time_t) -> libc::tm {
+    // SAFETY: `tm` is a plain C struct and `localtime_r` fills it.
+    unsafe {
+        let mut tm = std::mem::zeroed();
+        libc::localtime_r(&time, &mut tm);
+        tm
+    }
+}
+
+/// Formats `tm` by `strftime(3)`.
+pub fn format_time(format: &str, tm: &libc::tm) -> String {
+    let format = match CString::new(format) {
+        Ok(format) => format,
+        Err(_) => return String::new(),
+    };
+
+    let mut buf = vec![0u8; 256];
+    // SAFETY: `strftime` writes at most `buf.len()` bytes into `buf`.
+    let len = unsafe {
+        libc::strftime(
+            buf.as_mut_ptr() as *mut libc::c_char,
+            buf.len(),
+            format.as_ptr(),
+            tm,
+        )
+    };
+
+    String::from_utf8_lossy(&buf[..len]).into_owned()
+}
+
+/// Formats a duration like `850ms`, `12.3s`, or `2m5s`.
+pub fn format_elapsed(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    if secs == 0 {
+        format!("{}ms", elapsed.as_millis())
+    } else if secs < 60 {
+        format!("{:.1}s", elapsed.as_secs_f64())
+    } else if secs < 3600 {
+        format!("{}m{}s", secs / 60, secs % 60)
+    } else {
+        format!("{}h{}m{}s", secs / 3600, secs % 3600 / 60, secs % 60)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::shell::FD_LOCK;
+    use crate::variable::Value;
+
+    #[test]
+    fn format_fixed_time() {
+        // SAFETY: all-zero is a valid `tm`.
+        let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+        tm.tm_year = 2022 - 1900;
+        tm.tm_mon = 10;
+        tm.tm_mday = 3;
+        tm.tm_hour = 9;
+        tm.tm_min = 5;
+        assert_eq!(format_time("%H:%M", &tm), "09:05");
+        assert_eq!(format_time("%Y-%m-%d", &tm), "2022-11-03");
+        assert_eq!(format_time("", &tm), "");
+    }
+
+    #[test]
+    fn elapsed() {
+        assert_eq!(format_elapsed(Duration::from_millis(850)), "850ms");
+        assert_eq!(format_elapsed(Duration::from_millis(12_340)), "12.3s");
+        assert_eq!(format_elapsed(Duration::from_secs(125)), "2m5s");
+        assert_eq!(format_elapsed(Duration::from_secs(3725)), "1h2m5s");
+    }
+
+    #[test]
+    fn expand_escapes() {
+        let _lock = FD_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let mut shell = Shell::new();
+        shell.set("USER", Value::String("alice".to_owned()), false);
+        let elapsed = Some(Duration::from_millis(5));
+        assert_eq!(
+            expand_prompt(&shell, "\\u [\\E]\\n\\\\ \\q", elapsed),
+            "alice [5ms]\n\\ \\q"
+        );
+        assert_eq!(expand_prompt(&shell, "[\\E]", None), "[]");
+
+        let time = expand_prompt(&shell, "\\D{%H:%M}", None);
+        assert_eq!(time.len(), 5);
+        assert_eq!(&time[2..3], ":");
+        assert_eq!(expand_prompt(&shell, "\\D{%%}x", None), "%x");
+        assert_eq!(expand_prompt(&shell, "\\D", None), "\\D");
+    }
+}
@@ -0,0 +1,82 @@
+//! Quoting strings for output that can be read back by the shell.
+
+/// Characters that never need quoting.
+fn is_safe(ch: char) -> bool {
+    ch.is_alphanumeric() || "_-./,:@%+=".contains(ch)
+}
+
+/// Quotes `s` so that the shell reads it back as a single word: as is if
+/// nothing needs quoting, otherwise in single quotes. An embedded single
+/// quote is written as `\'` outside of them.
+pub fn shell_quote(s: &str) -> String {
+    if s.is_empty() {
+        return "''".to_owned();
+    }
+
+    if s.chars().all(is_safe) {
+        return s.to_owned();
+    }
+
+    let mut quoted = String::with_capacity(s.len() + 2);
+    let mut in_quotes = false;
+    for ch in s.chars() {
+        if ch == '\'' {
+            if in_quotes {
+                quoted.push('\'');
+                in_quotes = false;
+            }
+            quoted.push_str("\\'");
+        } else {
+            if !in_quotes {
+                quoted.push('\'');
+                in_quotes = true;
+            }
+            quoted.push(ch);
+        }
+    }
+
+    if in_quotes {
+        quoted.push('\'');
+    }
+    quoted
+}
+
+#[cfg(test)]
+mod test {
+    use super::shell_quote;
+    use crate::shell::{SharedBuffer, Shell};
+
+    #[test]
+    fn quote() {
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote("abc"), "abc");
+        assert_eq!(shell_quote("/usr/bin:a=b,c@d%e+f"), "/usr/bin:a=b,c@d%e+f");
+        assert_eq!(shell_quote("a b"), "'a b'");
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+        assert_eq!(shell_quote("'"), "\\'");
+        assert_eq!(shell_quote("''x"), "\\'\\''x'");
+        assert_eq!(shell_quote("$HOME"), "'$HOME'");
+        assert_eq!(shell_quote("*?[a]~"), "'*?[a]~'");
+        assert_eq!(shell_quote("a\nb"), "'a\nb'");
+    }
+
+    #[test]
+    fn read_back() {
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        for s in [
+            "",
+            "plain",
+            "two words",
+            "it's",
+            "'quoted'",
+            "\"double\" $var `cmd` $(cmd) \\ * ? [a] {a,b} ~ # ; & | < > ( )",
+            "tab\tand\nnewline",
+            "日本語 テスト",
+        ] {
+            stdout.clear();
+            shell.run_script(&format!("printf '%s|' {}", shell_quote(s)));
+            assert_eq!(stdout.contents(), format!("{}|", s));
+        }
+    }
+}
@@ -1,18 +1,73 @@
 use crate::eval::eval;
-use crate::parser;
+use crate::glob::GlobOptions;
+use crate::history::History;
+use crate::parser::{self, IncrementalParser};
 use crate::path::PathTable;
-use crate::process::{ExitStatus, Job, JobId, ProcessState};
-use crate::variable::Value;
+use crate::process::{wait_for_process, ExitStatus, FdReader, Job, JobId, ProcessState};
+use crate::variable::{Frame, Value, Variable};
 
 use nix::sys::termios::{tcgetattr, Termios};
-use nix::unistd::{getpid, Pid};
+use nix::unistd::{close, getpid, Pid};
 use std::collections::HashMap;
+use std::io::{BufRead, Read, Write};
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::RawFd;
 use std::rc::Rc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tracing::debug;
 
+/// Options accepted by `set -o` and `set +o` with their single-letter flags
+/// (e.g. `set -f` for `set -o noglob`).
+pub const SET_OPTIONS: &[(&str, Option<char>)] = &[
+    ("emacs", None),
+    ("errexit", Some('e')),
+    ("ignoreeof", None),
+    ("noglob", Some('f')),
+    ("nounset", Some('u')),
+    ("pipefail", None),
+    ("vi", None),
+    ("xtrace", Some('x')),
+];
+
+/// The maximum depth of function calls if `$FUNCNEST` is not set, low enough
+/// for the stack of a debug build.
+pub const DEFAULT_FUNCNEST: usize = 200;
+
+/// Options accepted by `shopt -s` and `shopt -u`.
+pub const SHOPT_OPTIONS: &[&str] = &[
+    "autocd",
+    "cdspell",
+    "correct",
+    "dirhistory",
+    "dotglob",
+    "extglob",
+    "globstar",
+    "histappend",
+    "nocaseglob",
+    "nullglob",
+];
+
+/// Set by `break` or `continue` until the innermost loop handles it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoopControl {
+    Break,
+    Continue,
+}
+
+/// A function defined by `name() command`.
+pub struct Function {
+    pub body: parser::Command,
+    /// The source of `body`.
+    pub code: String,
+    /// Passed to child processes (`export -f`).
+    pub exported: bool,
+}
+
 pub struct Shell {
     last_status: i32,
     pub interactive: bool,
+    /// Started as a login shell.
+    login: bool,
     path_table: PathTable,
     pub shell_termios: Option<Termios>,
     states: HashMap<Pid, ProcessState>,
@@ -21,13 +76,81 @@ pub struct Shell {
     pub last_fore_job: Option<Rc<Job>>,
     pid_job_mapping: HashMap<Pid, Rc<Job>>,
     cd_stack: Vec<String>,
+    history: History,
+    /// `exit` has already been refused because of stopped jobs.
+    exit_warned: bool,
+    options: HashMap<&'static str, bool>,
+    shopt_options: HashMap<&'static str, bool>,
+    global: Frame,
+    functions: HashMap<String, Rc<Function>>,
+    /// Key is a command name and value is the function registered by
+    /// `complete -F`.
+    completions: HashMap<String, String>,
+    /// Defined by `alias name=value`.
+    aliases: HashMap<String, String>,
+    /// Key is a condition (e.g. `EXIT`) and value is the command set by
+    /// `trap`.
+    traps: HashMap<String, String>,
+    /// The traps of the parent shell in a subshell: they aren't run but are
+    /// printed by `trap -p` (e.g. `saved=$(trap -p)`) until a trap is set or
+    /// removed in the subshell.
+    parent_traps: Option<HashMap<String, String>>,
+    /// A trap is being run: traps are not run recursively.
+    in_trap: bool,
+    /// The number of `if`, `elif`, and `while` conditions being run.
+    condition_depth: usize,
+    /// The positional parameters (`$1`, `$2`, ...).
+    args: Vec<String>,
+    /// The index of the next option character in the argument at `$OPTIND`
+    /// (see `getopts`).
+    getopts_offset: usize,
+    /// The names of the functions being called: the innermost one is the
+    /// last. `$FUNCNAME` lists them in the reverse order.
+    function_names: Vec<String>,
+    /// Set by `return` until the function returns.
+    returning: bool,
+    /// Set when `$FUNCNEST` is exceeded until all functions return.
+    aborting_functions: bool,
+    /// The pipe ends and processes of `<(...)` and `>(...)` in the command
+    /// being run.
+    process_substitutions: Vec<(RawFd, Pid)>,
+    /// The number of loops being run.
+    loop_depth: usize,
+    loop_control: Option<LoopControl>,
+    /// The baseline of `$SECONDS`.
+    seconds_since: Instant,
+    /// The state of the `$RANDOM` generator.
+    random_seed: u32,
+    /// `$LINENO`.
+    lineno: usize,
+    /// The number of lines before the script being parsed (see `run_reader`).
+    line_offset: usize,
+    stdin: Box<dyn Read>,
+    /// The file descriptor `stdin` reads from, if any.
+    stdin_fd: Option<RawFd>,
+    stdout: Box<dyn Write>,
+    stderr: Box<dyn Write>,
 }
 
 impl Shell {
     pub fn new() -> Self {
+        let mut shell = Shell::with_io(FdReader(0), std::io::stdout(), std::io::stderr());
+        shell.stdin_fd = Some(0);
+        shell
+    }
+
+    /// Creates a shell whose builtins read from and write to the given
+    /// streams instead of the process's stdin/stdout/stderr.
+    pub fn with_io<I, O, E>(stdin: I, stdout: O, stderr: E) -> Self
+    where
+        I: Read + 'static,
+        O: Write + 'static,
+        E: Write + 'static,
+    {
         Self {
             last_status: 0,
             interactive: false,
+            login: false,
             path_table: PathTable::new(),
             shell_termios: None,
             states: HashMap::new(),
@@ -36,6 +159,73 @@ impl Shell {
             last_fore_job: None,
             pid_job_mapping: HashMap::new(),
             cd_stack: Vec::new(),
+            history: History::new(),
+            exit_warned: false,
+            options: SET_OPTIONS
+                .iter()
+                .map(|(name, _)| (*name, *name == "emacs"))
+                .collect(),
+            shopt_options: SHOPT_OPTIONS.iter().map(|name| (*name, false)).collect(),
+            global: Frame::new(),
+            functions: HashMap::new(),
+            completions: HashMap::new(),
+            aliases: HashMap::new(),
+            traps: HashMap::new(),
+            parent_traps: None,
+            in_trap: false,
+            condition_depth: 0,
+            args: Vec::new(),
+            getopts_offset: 1,
+            function_names: Vec::new(),
+            returning: false,
+            aborting_functions: false,
+            process_substitutions: Vec::new(),
+            loop_depth: 0,
+            loop_control: None,
+            seconds_since: Instant::now(),
+            random_seed: std::process::id()
+                ^ SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|time| time.subsec_nanos())
+                    .unwrap_or(0),
+            lineno: 0,
+            line_offset: 0,
+            stdin: Box::new(stdin),
+            stdin_fd: None,
+            stdout: Box::new(stdout),
+            stderr: Box::new(stderr),
+        }
+    }
+
+    /// Prepares a forked child to run a subshell (e.g. `$(...)`): builtins
+    /// write to the process's stdout which the parent reads from.
+    pub fn enter_subshell(&mut self) {
+        self.interactive = false;
+        self.stdin = Box::new(FdReader(0));
+        self.stdout = Box::new(std::io::stdout());
+        self.parent_traps = Some(std::mem::take(&mut self.traps));
+        self.sync_pwd();
+    }
+
+    /// Sets `$PWD` to the current directory unless it already refers to it
+    /// (e.g. through a symbolic link).
+    pub fn sync_pwd(&mut self) {
+        let cwd = match std::env::current_dir() {
+            Ok(cwd) => cwd,
+            Err(_) => return,
+        };
+
+        let is_same_file = |path: &str| match (std::fs::metadata(path), std::fs::metadata(&cwd)) {
+            (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+            _ => false,
+        };
+
+        if !self
+            .get("PWD")
+            .is_some_and(|pwd| is_same_file(pwd.as_str()))
+        {
+            let cwd = cwd.to_string_lossy().into_owned();
+            self.set("PWD", Value::String(cwd), false);
         }
     }
 
@@ -56,6 +246,427 @@ impl Shell {
                 self.path_table.scan(path);
             }
         }
+
+        // Assigning to `$OPTIND` makes `getopts` start over from the
+        // beginning of an argument (e.g. `OPTIND=1` to parse another list).
+        if key == "OPTIND" {
+            self.getopts_offset = 1;
+        }
+
+        // Assigning to `$SECONDS` or `$RANDOM` resets the baseline or the seed.
+        if let Value::String(ref value) = value {
+            match key {
+                "SECONDS" => {
+                    let seconds = Duration::from_secs(value.parse().unwrap_or(0));
+                    self.seconds_since = Instant::now()
+                        .checked_sub(seconds)
+                        .unwrap_or_else(Instant::now);
+                    return;
+                }
+                "RANDOM" => {
+                    self.random_seed = value.parse().unwrap_or(0);
+                    return;
+                }
+                _ => (),
+            }
+        }
+
+        self.global.set(key, value);
+    }
+
+    /// Marks a variable to be passed to child processes (`export`) or not
+    /// (`export -n`).
+    pub fn export(&mut self, key: &str, exported: bool) {
+        self.global.set_exported(key, exported);
+    }
+
+    /// The environment variables passed to child processes. Exported
+    /// functions are encoded as in bash: `BASH_FUNC_name%%=() body`.
+    pub fn exported_vars(&self) -> Vec<(String, String)> {
+        let mut vars: Vec<(String, String)> = self
+            .global
+            .iter()
+            .filter(|(_, var)| var.is_exported() && var.value().is_some())
+            .map(|(name, var)| (name.clone(), var.as_str().to_owned()))
+            .collect();
+
+        for (name, function) in &self.functions {
+            if function.exported {
+                vars.push((
+                    format!("BASH_FUNC_{}%%", name),
+                    format!("() {}", function.code),
+                ));
+            }
+        }
+
+        vars
+    }
+
+    /// Imports environment variables given to the shell, including exported
+    /// functions.
+    pub fn import_env<I: Iterator<Item = (String, String)>>(&mut self, vars: I) {
+        for (key, value) in vars {
+            let function_name = key
+                .strip_prefix("BASH_FUNC_")
+                .and_then(|key| key.strip_suffix("%%"));
+            match function_name {
+                Some(name) if value.starts_with("()") => {
+                    self.import_function(name, &value);
+                }
+                _ => {
+                    self.set(&key, Value::String(value), false);
+                    self.export(&key, true);
+                }
+            }
+        }
+    }
+
+    fn import_function(&mut self, name: &str, value: &str) {
+        if !crate::variable::is_valid_name(name) {
+            return;
+        }
+
+        if let Ok(ast) = parser::parse(&format!("{}{}", name, value)) {
+            let definition = ast.terms.first().and_then(|term| term.pipelines.first());
+            if let Some(parser::Command::FunctionDefinition { body, code, .. }) =
+                definition.and_then(|pipeline| pipeline.commands.first())
+            {
+                self.define_function(name, (**body).clone(), code.clone());
+                self.export_function(name, true);
+            }
+        }
+    }
+
+    pub fn define_function(&mut self, name: &str, body: parser::Command, code: String) {
+        let function = Function {
+            body,
+            code,
+            exported: false,
+        };
+
+        self.functions.insert(name.to_owned(), Rc::new(function));
+    }
+
+    pub fn get_function(&self, name: &str) -> Option<Rc<Function>> {
+        self.functions.get(name).cloned()
+    }
+
+    pub fn function_names(&self) -> impl Iterator<Item = &str> {
+        self.functions.keys().map(String::as_str)
+    }
+
+    /// Marks a function to be passed to child processes. Returns `false` if
+    /// it's not defined.
+    pub fn export_function(&mut self, name: &str, exported: bool) -> bool {
+        match self.functions.get(name) {
+            Some(function) => {
+                let function = Function {
+                    body: function.body.clone(),
+                    code: function.code.clone(),
+                    exported,
+                };
+
+                self.functions.insert(name.to_owned(), Rc::new(function));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The function which generates completions for arguments of `cmd`.
+    pub fn completion(&self, cmd: &str) -> Option<&str> {
+        self.completions.get(cmd).map(String::as_str)
+    }
+
+    pub fn alias(&self, name: &str) -> Option<&str> {
+        self.aliases.get(name).map(String::as_str)
+    }
+
+    /// Returns aliases sorted by the name.
+    pub fn aliases(&self) -> Vec<(&str, &str)> {
+        let mut aliases: Vec<(&str, &str)> = self
+            .aliases
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        aliases.sort();
+        aliases
+    }
+
+    pub fn set_alias(&mut self, name: &str, value: &str) {
+        self.aliases.insert(name.to_owned(), value.to_owned());
+    }
+
+    /// Returns `false` if `name` is not an alias.
+    pub fn remove_alias(&mut self, name: &str) -> bool {
+        self.aliases.remove(name).is_some()
+    }
+
+    pub fn remove_all_aliases(&mut self) {
+        self.aliases.clear();
+    }
+
+    pub fn completions(&self) -> &HashMap<String, String> {
+        &self.completions
+    }
+
+    pub fn set_completion(&mut self, cmd: &str, function: &str) {
+        self.completions.insert(cmd.to_owned(), function.to_owned());
+    }
+
+    /// Returns `false` if no completion is registered for `cmd`.
+    pub fn remove_completion(&mut self, cmd: &str) -> bool {
+        self.completions.remove(cmd).is_some()
+    }
+
+    /// The positional parameters (`$1`, `$2`, ...).
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    pub fn getopts_offset(&self) -> usize {
+        self.getopts_offset
+    }
+
+    pub fn set_getopts_offset(&mut self, offset: usize) {
+        self.getopts_offset = offset;
+    }
+
+    /// Replaces the positional parameters (`set -- arg ...`).
+    pub fn set_args(&mut self, args: Vec<String>) {
+        self.args = args;
+    }
+
+    /// Prepares a function call. Returns the positional parameters of the
+    /// caller to be restored by `leave_function`.
+    pub fn enter_function(&mut self, name: &str, args: Vec<String>) -> Vec<String> {
+        self.function_names.push(name.to_owned());
+        self.update_funcname();
+        std::mem::replace(&mut self.args, args)
+    }
+
+    pub fn leave_function(&mut self, saved_args: Vec<String>) {
+        self.returning = false;
+        // Still in the function.
+        self.run_trap("RETURN");
+        self.function_names.pop();
+        self.update_funcname();
+        if self.function_names.is_empty() {
+            self.aborting_functions = false;
+        }
+
+        self.args = saved_args;
+    }
+
+    /// Sets `$FUNCNAME` to the functions being called from the innermost
+    /// one. It's unset outside of functions.
+    fn update_funcname(&mut self) {
+        if self.function_names.is_empty() {
+            self.unset("FUNCNAME");
+        } else {
+            let names = self.function_names.iter().rev().cloned().collect();
+            self.set("FUNCNAME", Value::Array(names), false);
+        }
+    }
+
+    pub fn function_depth(&self) -> usize {
+        self.function_names.len()
+    }
+
+    /// The maximum depth of function calls: `$FUNCNEST` if it's a positive
+    /// number, or `DEFAULT_FUNCNEST` to avoid a stack overflow.
+    pub fn max_function_depth(&self) -> usize {
+        self.get("FUNCNEST")
+            .and_then(|var| var.as_str().parse().ok())
+            .filter(|max| *max > 0)
+            .unwrap_or(DEFAULT_FUNCNEST)
+    }
+
+    /// Makes all functions being called return.
+    pub fn abort_functions(&mut self) {
+        self.aborting_functions = true;
+    }
+
+    pub fn aborting_functions(&self) -> bool {
+        self.aborting_functions
+    }
+
+    pub fn in_function(&self) -> bool {
+        !self.function_names.is_empty()
+    }
+
+    pub fn enter_condition(&mut self) {
+        self.condition_depth += 1;
+    }
+
+    pub fn leave_condition(&mut self) {
+        self.condition_depth -= 1;
+    }
+
+    /// In the condition of `if`, `elif`, or `while`, where a failure is not
+    /// an error.
+    pub fn in_condition(&self) -> bool {
+        self.condition_depth > 0
+    }
+
+    /// Set by `return`: the rest of the function is skipped.
+    pub fn returning(&self) -> bool {
+        self.returning
+    }
+
+    pub fn set_returning(&mut self) {
+        self.returning = true;
+    }
+
+    pub fn add_process_substitution(&mut self, fd: RawFd, pid: Pid) {
+        self.process_substitutions.push((fd, pid));
+        // Reaped along with jobs if not waited for.
+        self.set_process_state(pid, ProcessState::Running);
+    }
+
+    pub fn process_substitution_fds(&self) -> Vec<RawFd> {
+        self.process_substitutions
+            .iter()
+            .map(|(fd, _)| *fd)
+            .collect()
+    }
+
+    /// Closes the pipes of process substitutions after the command and waits
+    /// for the processes unless the command is run in background.
+    pub fn finish_process_substitutions(&mut self, wait: bool) {
+        for (fd, pid) in std::mem::take(&mut self.process_substitutions) {
+            close(fd).ok();
+            // It may have been reaped while waiting for the command.
+            if wait && self.get_process_state(pid) == Some(&ProcessState::Running) {
+                wait_for_process(self, pid, false);
+            }
+        }
+    }
+
+    pub fn enter_loop(&mut self) {
+        self.loop_depth += 1;
+    }
+
+    pub fn leave_loop(&mut self) {
+        self.loop_depth -= 1;
+        self.loop_control = None;
+    }
+
+    pub fn in_loop(&self) -> bool {
+        self.loop_depth > 0
+    }
+
+    /// Set by `break` or `continue`: the rest of the loop body is skipped.
+    pub fn loop_control(&self) -> Option<LoopControl> {
+        self.loop_control
+    }
+
+    pub fn set_loop_control(&mut self, control: Option<LoopControl>) {
+        self.loop_control = control;
+    }
+
+    /// Whether the rest of the commands should be skipped by `return`,
+    /// `break`, or `continue`.
+    pub fn interrupted(&self) -> bool {
+        self.returning || self.aborting_functions || self.loop_control.is_some()
+    }
+
+    /// `$SECONDS`: the seconds since the shell started or it's assigned.
+    pub fn seconds(&self) -> u64 {
+        self.seconds_since.elapsed().as_secs()
+    }
+
+    /// `$RANDOM`: a pseudo-random integer between 0 and 32767.
+    pub fn random(&mut self) -> u16 {
+        self.random_seed = self
+            .random_seed
+            .wrapping_mul(1103515245)
+            .wrapping_add(12345);
+        ((self.random_seed >> 16) & 0x7fff) as u16
+    }
+
+    /// Sets `$LINENO` to a line in the script being run.
+    pub fn set_lineno(&mut self, line: usize) {
+        self.lineno = self.line_offset + line;
+    }
+
+    pub fn lineno(&self) -> usize {
+        self.lineno
+    }
+
+    pub fn unset(&mut self, key: &str) {
+        if key == "OPTIND" {
+            self.getopts_offset = 1;
+        }
+
+        self.global.remove(key);
+    }
+
+    pub fn get(&self, key: &str) -> Option<Rc<Variable>> {
+        self.global.get(key)
+    }
+
+    pub fn stdin(&mut self) -> &mut dyn Read {
+        &mut *self.stdin
+    }
+
+    /// Returns the file descriptor `stdin()` reads from, or `None` if it
+    /// is not backed by one (see `with_io`).
+    pub fn stdin_fd(&self) -> Option<RawFd> {
+        self.stdin_fd
+    }
+
+    pub fn stdout(&mut self) -> &mut dyn Write {
+        &mut *self.stdout
+    }
+
+    pub fn stderr(&mut self) -> &mut dyn Write {
+        &mut *self.stderr
+    }
+
+    pub fn is_option_set(&self, name: &str) -> bool {
+        self.options.get(name).copied().unwrap_or(false)
+    }
+
+    /// Returns `false` if `name` is not a valid option.
+    pub fn set_option(&mut self, name: &str, value: bool) -> bool {
+        match self.options.get_mut(name) {
+            Some(option) => {
+                *option = value;
+                // The editing modes exclude each other.
+                match name {
+                    "emacs" if value => self.set_option("vi", false),
+                    "vi" if value => self.set_option("emacs", false),
+                    _ => true,
+                }
+            }
+            None => false,
+        }
+    }
+
+    pub fn is_shopt_set(&self, name: &str) -> bool {
+        self.shopt_options.get(name).copied().unwrap_or(false)
+    }
+
+    /// Returns `false` if `name` is not a valid option.
+    pub fn set_shopt(&mut self, name: &str, value: bool) -> bool {
+        match self.shopt_options.get_mut(name) {
+            Some(option) => {
+                *option = value;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The options for pathname expansion.
+    pub fn glob_options(&self) -> GlobOptions {
+        GlobOptions {
+            dotglob: self.is_shopt_set("dotglob"),
+            globstar: self.is_shopt_set("globstar"),
+            nocaseglob: self.is_shopt_set("nocaseglob"),
+            extglob: self.is_shopt_set("extglob"),
+        }
     }
 
     pub fn pushd(&mut self, path: String) {
@@ -66,11 +677,32 @@ impl Shell {
         self.cd_stack.pop()
     }
 
+    /// The directories pushed by `cd` (the most recent one last).
+    pub fn dir_stack(&self) -> &[String] {
+        &self.cd_stack
+    }
+
     #[inline]
     pub fn interactive(&self) -> bool {
         self.interactive
     }
 
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    pub fn history_mut(&mut self) -> &mut History {
+        &mut self.history
+    }
+
+    pub fn login(&self) -> bool {
+        self.login
+    }
+
+    pub fn set_login(&mut self, login: bool) {
+        self.login = login;
+    }
+
     pub fn get_process_state(&self, pid: Pid) -> Option<&ProcessState> {
         self.states.get(&pid)
     }
@@ -79,20 +711,45 @@ impl Shell {
         self.states.insert(pid, state);
     }
 
+    /// Returns the child processes which have not completed yet.
+    pub fn live_processes(&self) -> Vec<Pid> {
+        let mut pids: Vec<Pid> = self
+            .states
+            .iter()
+            .filter(|(_, state)| !matches!(state, ProcessState::Completed(_)))
+            .map(|(pid, _)| *pid)
+            .collect();
+        pids.sort();
+        pids
+    }
+
     pub fn set_last_status(&mut self, status: i32) {
         self.last_status = status;
     }
 
+    #[inline]
+    pub fn last_status(&self) -> i32 {
+        self.last_status
+    }
+
     pub fn path_table(&self) -> &PathTable {
         &self.path_table
     }
 
+    pub fn path_table_mut(&mut self) -> &mut PathTable {
+        &mut self.path_table
+    }
+
     pub fn run_script(&mut self, script: &str) -> ExitStatus {
         self.run_script_with_stdio(script)
     }
 
+    /// The field separators: `$IFS` or `" \t\n"` if it's unset.
     pub fn ifs(&self) -> String {
-        "\n\t ".to_string()
+        match self.get("IFS") {
+            Some(var) => var.as_str().to_owned(),
+            None => " \t\n".to_string(),
+        }
     }
 
     pub fn create_job(&mut self, name: String, pgid: Pid, childs: Vec<Pid>) -> Rc<Job> {
@@ -107,6 +764,91 @@ impl Shell {
         job
     }
 
+    pub fn jobs(&self) -> &HashMap<JobId, Rc<Job>> {
+        &self.jobs
+    }
+
+    /// The job `%%` refers to: the last job stopped in the foreground or,
+    /// if there's none, the most recently numbered one.
+    pub fn current_job(&self) -> Option<Rc<Job>> {
+        if let Some(job) = &self.last_fore_job {
+            if self.jobs.contains_key(&job.id()) {
+                return Some(job.clone());
+            }
+        }
+
+        self.jobs
+            .iter()
+            .max_by_key(|(id, _)| **id)
+            .map(|(_, job)| job.clone())
+    }
+
+    /// Whether `exit` may leave the shell: the first attempt is refused
+    /// if there're stopped jobs so that they are not lost by accident.
+    pub fn confirm_exit(&mut self) -> bool {
+        let has_stopped_jobs = self.jobs.values().any(|job| job.stopped(self));
+        if has_stopped_jobs && !self.exit_warned {
+            self.exit_warned = true;
+            return false;
+        }
+
+        true
+    }
+
+    /// Returns traps sorted by the condition.
+    pub fn traps(&self) -> Vec<(&str, &str)> {
+        let mut traps: Vec<(&str, &str)> = self
+            .parent_traps
+            .as_ref()
+            .unwrap_or(&self.traps)
+            .iter()
+            .map(|(condition, command)| (condition.as_str(), command.as_str()))
+            .collect();
+        traps.sort();
+        traps
+    }
+
+    pub fn set_trap(&mut self, condition: &str, command: &str) {
+        self.parent_traps = None;
+        self.traps.insert(condition.to_owned(), command.to_owned());
+    }
+
+    pub fn remove_trap(&mut self, condition: &str) {
+        self.parent_traps = None;
+        self.traps.remove(condition);
+    }
+
+    /// Runs the trap on a pseudo-signal (`DEBUG`, `ERR`, or `RETURN`), if
+    /// any. As in bash, `DEBUG` and `ERR` are not inherited by functions.
+    /// Traps are not run from another trap. `$?` is preserved.
+    pub fn run_trap(&mut self, condition: &str) {
+        if self.in_trap || (self.in_function() && condition != "RETURN") {
+            return;
+        }
+
+        if let Some(command) = self.traps.get(condition).cloned() {
+            let status = self.last_status;
+            self.in_trap = true;
+            self.run_script(&command);
+            self.in_trap = false;
+            self.last_status = status;
+        }
+    }
+
+    /// Runs the `EXIT` trap, if any, before the shell exits with `status`.
+    /// The trap sees `status` as `$?` and it's restored afterwards so that
+    /// the shell exits with it. Returns the status to exit with.
+    pub fn run_exit_trap(&mut self, status: i32) -> i32 {
+        // Removed first so that `exit` in the trap does not run it again.
+        if let Some(command) = self.traps.remove("EXIT") {
+            self.last_status = status;
+            self.run_script(&command);
+            self.last_status = status;
+        }
+
+        status
+    }
+
     pub fn jobs_mut(&mut self) -> &mut HashMap<JobId, Rc<Job>> {
         &mut self.jobs
     }
@@ -120,18 +862,275 @@ impl Shell {
         JobId::new(id)
     }
 
+    /// `$HOME`, or the user's home directory if it is not set.
+    pub fn home_dir(&self) -> Option<String> {
+        match self.get("HOME") {
+            Some(home) => Some(home.as_str().to_owned()),
+            None => dirs::home_dir().map(|dir| dir.to_string_lossy().into_owned()),
+        }
+    }
+
+    /// Runs `~/.smash_profile` in a login shell, or `~/.smashrc` in an
+    /// interactive shell which is not a login shell. A missing file is
+    /// skipped.
+    pub fn load_startup_files(&mut self) {
+        let name = if self.login {
+            ".smash_profile"
+        } else if self.interactive {
+            ".smashrc"
+        } else {
+            return;
+        };
+
+        let home_dir = match self.home_dir() {
+            Some(home_dir) => home_dir,
+            None => return,
+        };
+
+        let path = std::path::Path::new(&home_dir).join(name);
+        if let Ok(file) = std::fs::File::open(&path) {
+            debug!("loading {}", path.display());
+            self.run_reader(std::io::BufReader::new(file));
+        }
+    }
+
+    /// Reads a script from a streaming source (e.g. a pipe) and runs each
+    /// command as soon as its lines are complete.
+    pub fn run_reader<R: BufRead>(&mut self, mut reader: R) -> ExitStatus {
+        let mut parser = IncrementalParser::new();
+        let mut last_status = ExitStatus::ExitedWith(0);
+        let mut line = String::new();
+        // The number of lines read so far and the ones before the buffered
+        // lines in `parser`.
+        let mut lines_read = 0;
+        let mut lines_parsed = 0;
+        loop {
+            line.clear();
+            match reader.read_line(&mut line) {
+                Ok(0) => break,
+                Ok(_) => (),
+                Err(err) => {
+                    smash_err!("failed to read the script: {}", err);
+                    return ExitStatus::ExitedWith(1);
+                }
+            }
+
+            lines_read += 1;
+            match parser.feed(&line) {
+                Ok(ast) => {
+                    self.line_offset = lines_parsed;
+                    last_status = eval(self, &ast);
+                }
+                Err(parser::ParseError::Incomplete) => continue,
+                Err(parser::ParseError::Empty) => (),
+                Err(parser::ParseError::Fatal(err)) => {
+                    self.report_syntax_error(err, lines_parsed);
+                    last_status = ExitStatus::ExitedWith(-1);
+                }
+            }
+
+            lines_parsed = lines_read;
+        }
+
+        if !parser.is_empty() {
+            if let Err(parser::ParseError::Fatal(err)) = parser.finish() {
+                self.report_syntax_error(err, lines_parsed);
+            }
+
+            return ExitStatus::ExitedWith(-1);
+        }
+
+        last_status
+    }
+
+    fn report_syntax_error(&mut self, mut err: parser::SyntaxError, line_offset: usize) {
+        err.line += line_offset;
+        writeln!(self.stderr(), "smash: {}", err).ok();
+    }
+
     /// Parse and run a script in the given context
     pub fn run_script_with_stdio(&mut self, script: &str) -> ExitStatus {
+        match self.try_run_script(script) {
+            Ok(status) => status,
+            Err(err) => {
+                debug!("parse error: {:?}", err);
+                ExitStatus::ExitedWith(-1)
+            }
+        }
+    }
+
+    /// Parses and runs a script. Unlike `run_script`, a syntax error is
+    /// reported as `Err` so that it can be told apart from a command which
+    /// exited with a non-zero status.
+    pub fn try_run_script(&mut self, script: &str) -> Result<ExitStatus, parser::ParseError> {
         match parser::parse(script) {
-            Ok(ast) => eval(self, &ast),
+            Ok(ast) => {
+                // Restored for a script run by `eval` in the middle of another.
+                let saved_line_offset = std::mem::replace(&mut self.line_offset, 0);
+                let status = eval(self, &ast);
+                self.line_offset = saved_line_offset;
+                Ok(status)
+            }
             Err(parser::ParseError::Empty) => {
                 // Just ignore.
-                ExitStatus::ExitedWith(0)
-            }
-            Err(parser::ParseError::Fatal(err)) => {
-                debug!("parse error: {}", err);
-                ExitStatus::ExitedWith(-1)
+                Ok(ExitStatus::ExitedWith(0))
             }
+            Err(err) => Err(err),
         }
     }
 }
+
+/// Held by tests which depend on or change the current directory of the
+/// process.
+#[cfg(test)]
+pub static CWD_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+/// Held by tests which use the standard descriptors of the process. Builtins
+/// with redirections run in the process with its descriptors redirected, so
+/// output of other tests could end up in the redirection targets. Taken
+/// after `CWD_LOCK`.
+#[cfg(test)]
+pub static FD_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+/// An in-memory stream which can be inspected after being passed to
+/// `Shell::with_io`.
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct SharedBuffer(Rc<std::cell::RefCell<Vec<u8>>>);
+
+#[cfg(test)]
+impl SharedBuffer {
+    pub fn new() -> SharedBuffer {
+        SharedBuffer::default()
+    }
+
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).into_owned()
+    }
+
+    pub fn clear(&self) {
+        self.0.borrow_mut().clear();
+    }
+}
+
+#[cfg(test)]
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn syntax_errors_are_distinct_from_failures() {
+        let _lock = FD_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let mut shell = Shell::new();
+        assert_eq!(shell.try_run_script(""), Ok(ExitStatus::ExitedWith(0)));
+        assert!(matches!(
+            shell.try_run_script("if true\n"),
+            Err(parser::ParseError::Fatal(_))
+        ));
+        assert!(matches!(
+            shell.try_run_script("ls )"),
+            Err(parser::ParseError::Fatal(_))
+        ));
+        assert_eq!(
+            shell.try_run_script("if /bin/true; then /bin/false; fi"),
+            Ok(ExitStatus::ExitedWith(1))
+        );
+    }
+
+    #[test]
+    fn capture_builtin_output() {
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        shell.run_script("echo hello   world; echo -n bye");
+        assert_eq!(stdout.contents(), "hello world\nbye");
+    }
+
+    #[test]
+    fn lineno() {
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        shell.run_script("/bin/true\n\necho $LINENO\nif /bin/true; then\n  echo $LINENO\nfi");
+        assert_eq!(stdout.contents(), "3\n5\n");
+
+        let script = "/bin/true\nif /bin/true\nthen\n  echo $LINENO\nfi\necho $LINENO\n";
+        shell.run_reader(script.as_bytes());
+        assert_eq!(stdout.contents(), "3\n5\n4\n6\n");
+
+        stdout.clear();
+        let script = "/bin/true\n\n\nif /bin/true; then eval /bin/true; echo $LINENO; fi\n";
+        shell.run_reader(script.as_bytes());
+        assert_eq!(stdout.contents(), "4\n");
+    }
+
+    #[test]
+    fn funcname() {
+        let stdout = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), stdout.clone(), std::io::sink());
+        shell.run_script("inner() { echo \"${FUNCNAME[@]}\" $FUNCNAME ${#FUNCNAME[@]}; }");
+        shell.run_script("outer() { inner; echo \"${FUNCNAME[@]}\"; }; outer");
+        shell.run_script("echo \"[${FUNCNAME[@]}]\"");
+        assert_eq!(stdout.contents(), "inner outer inner 2\nouter\n[]\n");
+    }
+
+    #[test]
+    fn startup_files() {
+        let home = std::env::temp_dir().join(format!("smash-home-{}", std::process::id()));
+        std::fs::create_dir_all(&home).unwrap();
+        std::fs::write(home.join(".smash_profile"), "FROM=profile\n").unwrap();
+        std::fs::write(home.join(".smashrc"), "FROM=rc\n").unwrap();
+        let home_value = || Value::String(home.to_string_lossy().into_owned());
+
+        let mut shell = Shell::with_io(std::io::empty(), std::io::sink(), std::io::sink());
+        shell.set("HOME", home_value(), false);
+        shell.set_login(true);
+        shell.load_startup_files();
+        assert_eq!(shell.get("FROM").unwrap().as_str(), "profile");
+
+        let mut shell = Shell::with_io(std::io::empty(), std::io::sink(), std::io::sink());
+        shell.set("HOME", home_value(), false);
+        shell.interactive = true;
+        shell.load_startup_files();
+        assert_eq!(shell.get("FROM").unwrap().as_str(), "rc");
+
+        // Neither is loaded in a non-interactive, non-login shell.
+        let mut shell = Shell::with_io(std::io::empty(), std::io::sink(), std::io::sink());
+        shell.set("HOME", home_value(), false);
+        shell.load_startup_files();
+        assert!(shell.get("FROM").is_none());
+
+        std::fs::remove_dir_all(home).ok();
+    }
+
+    #[test]
+    fn report_syntax_errors() {
+        let stderr = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), std::io::sink(), stderr.clone());
+        shell.run_reader("echo a\necho 'b\n\n".as_bytes());
+        assert_eq!(
+            stderr.contents(),
+            "smash: syntax error: unterminated quoted string (line 2)\n"
+        );
+
+        let stderr = SharedBuffer::new();
+        let mut shell = Shell::with_io(std::io::empty(), std::io::sink(), stderr.clone());
+        assert_eq!(
+            shell.run_reader("echo a\n\necho b |\n".as_bytes()),
+            ExitStatus::ExitedWith(-1)
+        );
+        shell.run_reader("echo a\nls | | cat\n".as_bytes());
+        assert_eq!(
+            stderr.contents(),
+            "smash: syntax error near '|' (line 3)\nsmash: syntax error near '|' (line 2)\n"
+        );
+    }
+}
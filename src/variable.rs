@@ -1,6 +1,17 @@
 use std::collections::HashMap;
 use std::rc::Rc;
 
+/// Whether `name` is a valid variable name (`[A-Za-z_][A-Za-z0-9_]*`).
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) if first.is_ascii_alphabetic() || first == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum Value {
     String(String),
@@ -12,11 +23,16 @@ pub enum Value {
 pub struct Variable {
     // The inner value. `None` represents *null*.
     value: Option<Value>,
+    /// Passed to child processes as an environment variable (`export`).
+    exported: bool,
 }
 
 impl Variable {
     pub fn new(value: Option<Value>) -> Variable {
-        Variable { value }
+        Variable {
+            value,
+            exported: false,
+        }
     }
 
     #[inline]
@@ -24,6 +40,11 @@ impl Variable {
         &self.value
     }
 
+    #[inline]
+    pub fn is_exported(&self) -> bool {
+        self.exported
+    }
+
     pub fn as_str(&self) -> &str {
         match &self.value {
             Some(Value::String(value)) => value,
@@ -53,7 +74,39 @@ impl Frame {
     }
 
     pub fn set(&mut self, key: &str, value: Value) {
+        // An exported variable remains exported after an assignment.
+        let mut var = Variable::new(Some(value));
+        var.exported = self.vars.get(key).is_some_and(|var| var.exported);
+        self.vars.insert(key.into(), Rc::new(var));
+    }
+
+    /// Sets or clears the exported flag. An unset variable is created as
+    /// *null* so that it's exported once a value is assigned.
+    pub fn set_exported(&mut self, key: &str, exported: bool) {
+        let value = self.vars.get(key).and_then(|var| var.value.clone());
         self.vars
-            .insert(key.into(), Rc::new(Variable::new(Some(value))));
+            .insert(key.into(), Rc::new(Variable { value, exported }));
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Rc<Variable>)> {
+        self.vars.iter()
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<Rc<Variable>> {
+        self.vars.remove(key)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn valid_names() {
+        assert!(is_valid_name("_a1"));
+        assert!(is_valid_name("PATH"));
+        assert!(!is_valid_name("1abc"));
+        assert!(!is_valid_name("a-b"));
+        assert!(!is_valid_name(""));
     }
 }
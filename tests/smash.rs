@@ -0,0 +1,278 @@
+//! Runs the built binary for behavior which involves the whole process: its
+//! exit status, signals, `exec`, and the standard descriptors which builtins
+//! redirect in the shell process (the test harness writes to its stdout any
+//! time).
+use nix::sys::signal::{kill, Signal};
+use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
+use nix::unistd::Pid;
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::time::{Duration, Instant};
+
+const SMASH: &str = env!("CARGO_BIN_EXE_smash");
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("smash-it-{}-{}", std::process::id(), name))
+}
+
+fn write_script(path: &Path, body: &str) {
+    std::fs::write(path, body).unwrap();
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+/// Runs `command` with `input` as its stdin, killing it if it does not
+/// finish in 10 seconds.
+fn run(command: &mut Command, input: &str) -> Output {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // Closed to let the child see EOF.
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(input.as_bytes()).unwrap();
+    drop(stdin);
+
+    let started_at = Instant::now();
+    while child.try_wait().unwrap().is_none() {
+        if started_at.elapsed() > Duration::from_secs(10) {
+            child.kill().ok();
+            child.wait().ok();
+            panic!("timed out: {:?}", command);
+        }
+
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    child.wait_with_output().unwrap()
+}
+
+/// Runs `script` read from stdin and returns what it printed to stdout.
+fn smash(script: &str) -> String {
+    let output = run(Command::new(SMASH).current_dir("/"), script);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Waits for `pid` to stop for up to 10 seconds.
+fn wait_for_stop(pid: Pid) -> WaitStatus {
+    let started_at = Instant::now();
+    loop {
+        match waitpid(pid, Some(WaitPidFlag::WUNTRACED | WaitPidFlag::WNOHANG)).unwrap() {
+            WaitStatus::StillAlive if started_at.elapsed() < Duration::from_secs(10) => {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            WaitStatus::StillAlive => {
+                kill(pid, Signal::SIGKILL).ok();
+                panic!("timed out: {}", pid);
+            }
+            status => return status,
+        }
+    }
+}
+
+#[test]
+fn interpreter_in_shebang() {
+    let script = temp_path("cat.txt");
+    write_script(&script, "#!/bin/cat\nhello\n");
+    let output = run(Command::new(SMASH).arg(&script), "");
+    std::fs::remove_file(&script).ok();
+
+    // `/bin/cat` prints the script including the `#!` line.
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"#!/bin/cat\nhello\n");
+}
+
+#[test]
+fn script_arguments() {
+    let script = temp_path("args.sh");
+    write_script(&script, "echo \"$# $1 $2\"\n");
+    let output = run(Command::new(SMASH).arg(&script).args(["a b", "c"]), "");
+    std::fs::remove_file(&script).ok();
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"2 a b c\n");
+}
+
+#[test]
+fn missing_script() {
+    let script = temp_path("missing.sh");
+    let output = run(Command::new(SMASH).arg(&script), "");
+    assert_eq!(output.status.code(), Some(127));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains(&format!("{}: No such file", script.display())));
+}
+
+#[test]
+fn shebang_of_smash_itself() {
+    let script = temp_path("self.sh");
+    write_script(&script, &format!("#!{}\necho hello\n", SMASH));
+    let output = run(&mut Command::new(&script), "");
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"hello\n");
+
+    let dir = Path::new(SMASH).parent().unwrap();
+    write_script(&script, "#!/usr/bin/env smash\necho hello\n");
+    let output = run(Command::new(&script).env("PATH", dir), "");
+    std::fs::remove_file(&script).ok();
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"hello\n");
+}
+
+#[test]
+fn suspend_and_continue() {
+    let script = temp_path("suspend.sh");
+    write_script(&script, "suspend\nexit 7\n");
+    // In its own process group to stop only the shell.
+    let mut child = Command::new(SMASH)
+        .arg(&script)
+        .process_group(0)
+        .spawn()
+        .unwrap();
+    let pid = Pid::from_raw(child.id() as i32);
+
+    assert_eq!(
+        wait_for_stop(pid),
+        WaitStatus::Stopped(pid, Signal::SIGSTOP)
+    );
+    kill(pid, Signal::SIGCONT).unwrap();
+    assert_eq!(child.wait().unwrap().code(), Some(7));
+    std::fs::remove_file(&script).ok();
+}
+
+#[test]
+fn logout_from_login_shell() {
+    let script = temp_path("logout.sh");
+    write_script(&script, "logout 3\nexit 5\n");
+    let login = run(Command::new(SMASH).arg("-l").arg(&script), "");
+    let non_login = run(Command::new(SMASH).arg(&script), "");
+    std::fs::remove_file(&script).ok();
+
+    assert_eq!(login.status.code(), Some(3));
+    // `logout` fails and the script goes on.
+    assert_eq!(non_login.status.code(), Some(5));
+}
+
+#[test]
+fn redirect_builtin_output() {
+    let path = temp_path("builtin.txt");
+    let script = format!(
+        "echo hello > {0}; echo world >> {0}; /bin/cat {0}; pwd > {0}; /bin/cat {0}",
+        path.display()
+    );
+    assert_eq!(smash(&script), "hello\nworld\n/\n");
+    std::fs::remove_file(&path).ok();
+}
+
+#[test]
+fn special_redirection_targets() {
+    let path = temp_path("special.txt");
+    let script = format!(
+        "echo hi 2> {0} >/dev/stderr; /bin/echo there 3>> {0} >/dev/fd/3; /bin/cat {0}",
+        path.display()
+    );
+    assert_eq!(smash(&script), "hi\nthere\n");
+
+    // `N>&-` closes the descriptor in the command.
+    let script = format!(
+        "/bin/sh -c 'echo x >&3' 3>{0} 3>&- || echo failed; /bin/cat {0}; /bin/cat <&- || echo failed",
+        path.display()
+    );
+    assert_eq!(smash(&script), "failed\nfailed\n");
+    std::fs::remove_file(&path).ok();
+}
+
+#[test]
+fn write_to_fd_opened_by_exec() {
+    let path = temp_path("exec.txt");
+    let script = format!(
+        "exec 57>{0}; echo a >&57
+        # Children inherit the descriptor.
+        /bin/echo b >&57; /bin/sh -c 'echo c >>/dev/fd/57'
+        exec 57>&-; echo d >&57 || echo closed; /bin/cat {0}",
+        path.display()
+    );
+    assert_eq!(smash(&script), "closed\na\nb\nc\n");
+    std::fs::remove_file(&path).ok();
+}
+
+#[test]
+fn builtins_in_pipelines() {
+    assert_eq!(smash("echo hello | /bin/cat | /bin/cat"), "hello\n");
+}
+
+#[test]
+fn while_in_pipelines() {
+    let script = r#"echo hi | while read y; do echo "[$y]"; done
+        while /bin/true; do echo a; break; done | /usr/bin/tr a-z A-Z
+        # The loop runs in a subshell.
+        echo a | while read z; do break; done; echo "z=$z""#;
+    assert_eq!(smash(script), "[hi]\nA\nz=\n");
+}
+
+#[test]
+fn compound_commands_in_pipelines() {
+    let script = r#"{ echo a; echo b; } | /usr/bin/tr a-z A-Z
+        echo hi | { /bin/cat; }
+        printf abc | { read -n 1 x; echo "[$x]"; }
+        if /bin/true; then echo c; fi | /usr/bin/tr a-z A-Z
+        echo 2 | select x in d e; do echo $x; break; done
+        echo f | { read z; }; echo "z=$z"
+        /bin/true | { /bin/false; } | /bin/true; echo ${PIPESTATUS[@]}"#;
+    assert_eq!(smash(script), "A\nB\nhi\n[a]\nC\ne\nz=\n0 1 0\n");
+}
+
+#[test]
+fn subshells() {
+    let script = r#"( cd /tmp; x=1; echo $PWD ); echo "$PWD x=$x"
+        ( exit 3 ); echo $?
+        ( echo a; echo b ) | /usr/bin/tr a-z A-Z
+        f() ( cd /usr; echo $PWD ); f; echo $PWD"#;
+    assert_eq!(smash(script), "/tmp\n/ x=\n3\nA\nB\n/usr\n/\n");
+}
+
+#[test]
+fn errexit() {
+    let script = "set -e; /bin/false; echo not reached";
+    let output = run(&mut Command::new(SMASH), script);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(output.stdout, b"");
+
+    // Failures in conditions and before `&&` or `||` don't exit.
+    let script = "set -e
+        /bin/false || echo a
+        if /bin/false; then echo no; fi
+        while /bin/false; do echo no; done
+        /bin/false && echo no
+        trap 'echo exiting' EXIT
+        echo b; /bin/sh -c 'exit 3'; echo not reached";
+    let output = run(&mut Command::new(SMASH), script);
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(output.stdout, b"a\nb\nexiting\n");
+}
+
+#[test]
+fn nounset() {
+    let script = "set -u; trap 'echo exiting' EXIT; echo $nope; echo not reached";
+    let output = run(&mut Command::new(SMASH), script);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(output.stdout, b"exiting\n");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("nope: unbound variable"));
+
+    // So does an arithmetic index and a builtin before the last command of a
+    // pipeline.
+    let output = run(
+        &mut Command::new(SMASH),
+        "set -u; a=(1); echo ${a[x + 1]}; echo no",
+    );
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(output.stdout, b"");
+    let output = run(
+        &mut Command::new(SMASH),
+        "set -u; echo ${x:-a}; echo $y | /bin/cat",
+    );
+    assert_eq!(output.stdout, b"a\n");
+}